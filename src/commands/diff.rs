@@ -3,92 +3,182 @@
 use std::path::Path;
 
 use anyhow::{Context, Result};
+use serde::Serialize;
 
+use crate::commands::OutputFormat;
+use crate::commands::plan::{self, PlanState};
 use crate::config::manifest::SystemManifest;
-use crate::deploy::lock::LockFile;
+use crate::config::overrides::Overrides;
+use crate::deploy::lock::{LockEntry, LockFile};
+use crate::deploy::state::StateDir;
+use crate::diff::unified::{self, Hunk};
 use crate::resolve::resolver::{compute_hash, resolve_file};
 
-pub fn run(stow_dir: &Path, _target_dir: &Path, package_filter: Option<&str>) -> Result<()> {
+/// Options for `towboat diff`.
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+    pub format: OutputFormat,
+    /// Emit ANSI colors in text output.
+    pub color: bool,
+    /// Unchanged lines shown around each hunk.
+    pub context: usize,
+    /// Also diff drifted resolved files against a fresh resolve.
+    pub drifted: bool,
+    /// Simulated tags and variables.
+    pub overrides: Overrides,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            format: OutputFormat::Text,
+            color: false,
+            context: unified::DEFAULT_CONTEXT,
+            drifted: false,
+            overrides: Overrides::default(),
+        }
+    }
+}
+
+/// Kind of change reported for a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// Tracked file whose resolved content would change.
+    Modified,
+    /// File that would be deployed for the first time.
+    Added,
+    /// Tracked file that would be removed.
+    Removed,
+    /// Tracked file whose target path would change.
+    Retargeted,
+    /// Resolved file was edited after sync; sync preserves it (only with `--drifted`).
+    Drifted,
+}
+
+impl ChangeKind {
+    /// Single-letter code used by `--format porcelain`.
+    pub fn code(&self) -> char {
+        match self {
+            ChangeKind::Modified => 'M',
+            ChangeKind::Added => 'A',
+            ChangeKind::Removed => 'D',
+            ChangeKind::Retargeted => 'R',
+            ChangeKind::Drifted => 'E',
+        }
+    }
+}
+
+/// Per-file diff record, as emitted by `--format json` and `--format porcelain`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffRecord {
+    pub package: String,
+    /// Source path relative to the stow directory.
+    pub source: String,
+    /// Target path relative to the target directory.
+    pub target: String,
+    /// Target recorded in the lock, for retargeted files.
+    pub previous_target: Option<String>,
+    pub change: ChangeKind,
+    /// Hash of the old side of the diff: the locked resolved hash, or the fresh
+    /// resolve for drifted files. `None` for added files.
+    pub old_hash: Option<String>,
+    /// Hash of the new side of the diff: a fresh resolve, or the edited resolved
+    /// file for drifted files. `None` for removed files.
+    pub new_hash: Option<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+#[derive(Serialize)]
+struct DiffReport<'a> {
+    files: &'a [DiffRecord],
+}
+
+pub fn run(
+    stow_dir: &Path,
+    _target_dir: &Path,
+    package_filter: Option<&str>,
+    options: &DiffOptions,
+) -> Result<()> {
     let manifest_path = stow_dir.join("towboat.toml");
-    let manifest = SystemManifest::load(&manifest_path).context("Failed to load towboat.toml")?;
+    let mut manifest =
+        SystemManifest::load(&manifest_path).context("Failed to load towboat.toml")?;
+    options.overrides.apply(&mut manifest)?;
 
     let active_tags = manifest.active_tags();
-    let towboat_dir = stow_dir.join(".towboat");
-    let resolved_dir = towboat_dir.join("resolved");
-    let lock_path = towboat_dir.join("towboat.lock");
-    let lock = LockFile::load(&lock_path).unwrap_or_default();
-
-    let packages: Vec<(&str, &crate::config::manifest::PackageEntry)> =
-        if let Some(name) = package_filter {
-            match manifest.packages.get(name) {
-                Some(entry) => vec![(name, entry)],
-                None => anyhow::bail!("Package '{name}' not found in towboat.toml"),
-            }
-        } else {
-            manifest
-                .packages
-                .iter()
-                .map(|(k, v)| (k.as_str(), v))
-                .collect()
-        };
+    let state = StateDir::new(stow_dir, &manifest.system)?;
+    let resolved_dir = state.resolved_dir();
+    let lock = LockFile::load(&state.lock_path()).unwrap_or_default();
 
-    let mut any_changes = false;
+    let plan = plan::plan(stow_dir, &manifest, &active_tags, package_filter)?;
+    let mut records = Vec::new();
 
-    for (pkg_name, pkg_entry) in &packages {
-        let pkg_dir = stow_dir.join(pkg_name);
-        if !pkg_dir.exists() {
+    for pkg in &plan.packages {
+        if matches!(pkg.state, PlanState::Missing) {
             continue;
         }
+        let pkg_name = pkg.name;
+        let discovered = pkg.files();
 
-        let config = crate::config::resolve_package_config(pkg_name, &pkg_dir, pkg_entry)?;
-
-        let discovered =
-            crate::discovery::walker::discover_package(&pkg_dir, &config, &active_tags)?;
-
-        for file in &discovered {
+        for file in discovered {
             let source_relative = format!("{pkg_name}/{}", file.relative_path.display());
+            let target = file.target_path.display().to_string();
 
             let (new_content, _had_tags) =
                 resolve_file(&file.source_path, &active_tags, &manifest.variables)?;
 
             let new_hash = compute_hash(&new_content);
 
-            // Check against lock
-            if let Some(lock_entry) = lock.find(pkg_name, &source_relative) {
-                if new_hash != lock_entry.resolved_hash {
-                    // Content would change
-                    if !any_changes {
-                        println!("Changes that would be applied on next sync:\n");
-                        any_changes = true;
-                    }
-                    println!(
-                        "  M {pkg_name}/{} -> {}",
-                        file.relative_path.display(),
-                        file.target_path.display()
-                    );
-
-                    // Show basic diff info
-                    let resolved_path = resolved_dir
-                        .join(pkg_name)
-                        .join(file.relative_path.to_string_lossy().as_ref());
-                    if resolved_path.exists() {
-                        let old_content = std::fs::read_to_string(&resolved_path)?;
-                        let old_lines: Vec<&str> = old_content.lines().collect();
-                        let new_lines: Vec<&str> = new_content.lines().collect();
-                        print_simple_diff(&old_lines, &new_lines);
-                    }
-                }
-            } else {
-                // New file
-                if !any_changes {
-                    println!("Changes that would be applied on next sync:\n");
-                    any_changes = true;
+            let resolved_path = resolved_dir.join(pkg_name).join(&file.relative_path);
+            let on_disk = std::fs::read_to_string(&resolved_path).ok();
+
+            let Some(lock_entry) = lock.find(pkg_name, &source_relative) else {
+                records.push(DiffRecord {
+                    package: pkg_name.to_string(),
+                    source: source_relative,
+                    target,
+                    previous_target: None,
+                    change: ChangeKind::Added,
+                    old_hash: None,
+                    hunks: unified::hunks("", &new_content, options.context),
+                    new_hash: Some(new_hash),
+                });
+                continue;
+            };
+
+            let retargeted = lock_entry.target != target;
+            if retargeted || new_hash != lock_entry.resolved_hash {
+                let old_content = on_disk.unwrap_or_default();
+                records.push(DiffRecord {
+                    package: pkg_name.to_string(),
+                    source: source_relative,
+                    target,
+                    previous_target: retargeted.then(|| lock_entry.target.clone()),
+                    change: if retargeted {
+                        ChangeKind::Retargeted
+                    } else {
+                        ChangeKind::Modified
+                    },
+                    old_hash: Some(lock_entry.resolved_hash.clone()),
+                    hunks: unified::hunks(&old_content, &new_content, options.context),
+                    new_hash: Some(new_hash),
+                });
+            } else if options.drifted
+                && let Some(edited) = on_disk
+            {
+                let edited_hash = compute_hash(&edited);
+                if edited_hash != lock_entry.resolved_hash {
+                    records.push(DiffRecord {
+                        package: pkg_name.to_string(),
+                        source: source_relative,
+                        target,
+                        previous_target: None,
+                        change: ChangeKind::Drifted,
+                        old_hash: Some(new_hash),
+                        new_hash: Some(edited_hash),
+                        hunks: unified::hunks(&new_content, &edited, options.context),
+                    });
                 }
-                println!(
-                    "  + {pkg_name}/{} -> {} (new)",
-                    file.relative_path.display(),
-                    file.target_path.display()
-                );
             }
         }
 
@@ -100,50 +190,105 @@ pub fn run(stow_dir: &Path, _target_dir: &Path, package_filter: Option<&str>) ->
 
         for entry in lock.entries_for_package(pkg_name) {
             if !current_sources.contains(&entry.source) {
-                if !any_changes {
-                    println!("Changes that would be applied on next sync:\n");
-                    any_changes = true;
-                }
-                println!("  - {} (would be removed)", entry.target);
+                records.push(removed_record(entry, &resolved_dir, options.context));
             }
         }
     }
 
-    if !any_changes {
-        println!("No changes would be applied.");
+    // Packages in lock but not in manifest
+    if package_filter.is_none() {
+        for entry in lock.entries() {
+            if !manifest.packages.contains_key(&entry.package) {
+                records.push(removed_record(entry, &resolved_dir, options.context));
+            }
+        }
+    }
+
+    match options.format {
+        OutputFormat::Text => print_text(&records, options.color),
+        OutputFormat::Json => {
+            let json = serde_json::to_string_pretty(&DiffReport { files: &records })?;
+            println!("{json}");
+        }
+        OutputFormat::Porcelain => {
+            for record in &records {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    record.change.code(),
+                    record.package,
+                    record.source,
+                    record.target
+                );
+            }
+        }
     }
 
     Ok(())
 }
 
-fn print_simple_diff(old: &[&str], new: &[&str]) {
-    // Simple line-by-line comparison
-    let max_lines = old.len().max(new.len());
-    let mut showed_diff = false;
+/// A lock entry the next sync would unlink.
+fn removed_record(entry: &LockEntry, resolved_dir: &Path, context: usize) -> DiffRecord {
+    let old_content = std::fs::read_to_string(resolved_dir.join(&entry.source)).unwrap_or_default();
+    DiffRecord {
+        package: entry.package.clone(),
+        source: entry.source.clone(),
+        target: entry.target.clone(),
+        previous_target: None,
+        change: ChangeKind::Removed,
+        old_hash: Some(entry.resolved_hash.clone()),
+        new_hash: None,
+        hunks: unified::hunks(&old_content, "", context),
+    }
+}
 
-    for i in 0..max_lines {
-        let old_line = old.get(i).copied();
-        let new_line = new.get(i).copied();
+fn print_text(records: &[DiffRecord], color: bool) {
+    if records.is_empty() {
+        println!("No changes would be applied.");
+        return;
+    }
 
-        match (old_line, new_line) {
-            (Some(o), Some(n)) if o != n => {
-                println!("    - {o}");
-                println!("    + {n}");
-                showed_diff = true;
-            }
-            (Some(o), None) => {
-                println!("    - {o}");
-                showed_diff = true;
-            }
-            (None, Some(n)) => {
-                println!("    + {n}");
-                showed_diff = true;
-            }
-            _ => {}
+    println!("Changes that would be applied on next sync:\n");
+
+    for record in records {
+        let code = record.change.code();
+        let (old_label, new_label) = match record.change {
+            ChangeKind::Modified | ChangeKind::Drifted => (
+                format!("a/{}", record.target),
+                format!("b/{}", record.target),
+            ),
+            ChangeKind::Added => ("/dev/null".to_string(), format!("b/{}", record.target)),
+            ChangeKind::Removed => (format!("a/{}", record.target), "/dev/null".to_string()),
+            ChangeKind::Retargeted => (
+                format!(
+                    "a/{}",
+                    record.previous_target.as_deref().unwrap_or_default()
+                ),
+                format!("b/{}", record.target),
+            ),
+        };
+
+        match record.change {
+            ChangeKind::Modified => println!("{code} {} -> {}", record.source, record.target),
+            ChangeKind::Added => println!("{code} {} -> {} (new)", record.source, record.target),
+            ChangeKind::Removed => println!("{code} {} (would be removed)", record.target),
+            ChangeKind::Retargeted => println!(
+                "{code} {}: {} -> {}",
+                record.source,
+                record.previous_target.as_deref().unwrap_or_default(),
+                record.target
+            ),
+            ChangeKind::Drifted => println!(
+                "{code} {} -> {} (resolved file edited; fresh resolve vs. edits)",
+                record.source, record.target
+            ),
         }
-    }
 
-    if showed_diff {
+        if !record.hunks.is_empty() {
+            print!(
+                "{}",
+                unified::format_unified(&old_label, &new_label, &record.hunks, color)
+            );
+        }
         println!();
     }
 }
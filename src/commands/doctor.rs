@@ -0,0 +1,621 @@
+//! `towboat doctor` — validate configs and deployed state without writing anything.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use crate::commands::OutputFormat;
+use crate::config::manifest::SystemManifest;
+use crate::config::package::PackageConfig;
+use crate::deploy::collision::{self, PackageFiles};
+use crate::deploy::lock::LockFile;
+use crate::deploy::scripts;
+use crate::deploy::state::StateDir;
+use crate::deploy::symlink;
+use crate::discovery::walker::{self, DiscoveredFile};
+use crate::error::TowboatError;
+use crate::tags::{matcher, parser};
+use crate::template::engine;
+
+/// How serious a finding is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Sync will fail or deploy the wrong thing.
+    Error,
+    /// Likely a mistake, or state that sync will repair.
+    Warning,
+    /// Harmless leftovers.
+    Info,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        })
+    }
+}
+
+/// A single problem found by `towboat doctor`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    /// Short name of the check, e.g. `unknown-key` or `broken-symlink`.
+    pub check: &'static str,
+    pub message: String,
+    /// How to fix it.
+    pub hint: String,
+}
+
+#[derive(Serialize)]
+struct DoctorReport<'a> {
+    findings: &'a [Finding],
+}
+
+#[derive(Default)]
+pub(crate) struct Findings(pub(crate) Vec<Finding>);
+
+impl Findings {
+    pub(crate) fn push(
+        &mut self,
+        severity: Severity,
+        check: &'static str,
+        message: impl Into<String>,
+        hint: impl Into<String>,
+    ) {
+        self.0.push(Finding {
+            severity,
+            check,
+            message: message.into(),
+            hint: hint.into(),
+        });
+    }
+
+    pub(crate) fn push_config_error(&mut self, message: String) {
+        let hint = if message.contains("unknown field") {
+            "Remove the key or fix its spelling"
+        } else {
+            "Fix the config so it parses"
+        };
+        self.push(Severity::Error, "invalid-config", message, hint);
+    }
+}
+
+/// Run every check; fails if any finding is an error.
+pub fn run(stow_dir: &Path, target_dir: &Path, format: OutputFormat) -> Result<()> {
+    let findings = diagnose(stow_dir, target_dir)?;
+    report("doctor", &findings, format)
+}
+
+/// Print findings in the requested format; fails if any finding is an error.
+/// Shared by `doctor` and `lint`.
+pub(crate) fn report(command: &str, findings: &[Finding], format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Text => print_text(findings),
+        OutputFormat::Json => {
+            let json = serde_json::to_string_pretty(&DoctorReport { findings })?;
+            println!("{json}");
+        }
+        OutputFormat::Porcelain => {
+            for finding in findings {
+                println!(
+                    "{}\t{}\t{}",
+                    finding.severity, finding.check, finding.message
+                );
+            }
+        }
+    }
+
+    let errors = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .count();
+    if errors > 0 {
+        anyhow::bail!("towboat {command} found {errors} error(s)");
+    }
+    Ok(())
+}
+
+/// Collect findings, sorted by severity (stable within a severity).
+pub fn diagnose(stow_dir: &Path, target_dir: &Path) -> Result<Vec<Finding>> {
+    let manifest_path = stow_dir.join("towboat.toml");
+    let mut findings = Findings::default();
+
+    let manifest = match SystemManifest::load(&manifest_path) {
+        Ok(manifest) => Some(manifest),
+        Err(e @ TowboatError::InvalidConfig { .. }) => {
+            findings.push_config_error(e.to_string());
+            None
+        }
+        Err(e) => return Err(e).context("Failed to load towboat.toml"),
+    };
+    // Without a manifest, the default state dir is the best guess
+    let state = match &manifest {
+        Some(manifest) => StateDir::new(stow_dir, &manifest.system)?,
+        None => StateDir::for_stow(stow_dir)?,
+    };
+    let resolved_dir = state.resolved_dir();
+    let lock = LockFile::load(&state.lock_path()).unwrap_or_default();
+
+    match &manifest {
+        Some(manifest) => check_packages(
+            stow_dir,
+            target_dir,
+            manifest,
+            &resolved_dir,
+            &lock,
+            &mut findings,
+        ),
+        None => check_boat_tomls(stow_dir, &mut findings),
+    }
+    check_lock(stow_dir, target_dir, &resolved_dir, &lock, &mut findings);
+    check_orphans(&resolved_dir, &lock, &mut findings);
+
+    let mut findings = findings.0;
+    findings.sort_by_key(|f| f.severity);
+    Ok(findings)
+}
+
+/// Every check that needs the parsed manifest: package configs, sources,
+/// targets, scripts and collisions.
+fn check_packages(
+    stow_dir: &Path,
+    target_dir: &Path,
+    manifest: &SystemManifest,
+    resolved_dir: &Path,
+    lock: &LockFile,
+    findings: &mut Findings,
+) {
+    let active_tags = manifest.active_tags();
+    let variables = known_variables(manifest);
+    check_manifest_exprs(manifest, findings);
+
+    let mut names: Vec<&String> = manifest.packages.keys().collect();
+    names.sort();
+    let mut package_files = Vec::new();
+
+    for name in names {
+        let entry = &manifest.packages[name];
+        let pkg_dir = stow_dir.join(name);
+        if !pkg_dir.is_dir() {
+            findings.push(
+                Severity::Error,
+                "missing-package",
+                format!("Package '{name}' has no directory at {}", pkg_dir.display()),
+                format!("Create {name}/ or remove it from [packages]"),
+            );
+            continue;
+        }
+
+        let config = match crate::config::resolve_package_config(name, &pkg_dir, entry) {
+            Ok(config) => config,
+            Err(e) => {
+                findings.push_config_error(format!("{e:#}"));
+                continue;
+            }
+        };
+
+        if !check_package_configs(name, &pkg_dir, &config, findings) {
+            continue;
+        }
+
+        // Every source, whatever tags it needs, must only use defined variables
+        match walker::source_files(&pkg_dir, &config) {
+            Ok(sources) => check_sources(name, &pkg_dir, &sources, &variables, findings),
+            Err(e) => findings.push(
+                Severity::Error,
+                "discovery",
+                format!("Package '{name}': {e}"),
+                "Fix the ignore rules or nested boat.toml named in the error",
+            ),
+        }
+
+        if !entry.tags.iter().all(|t| active_tags.contains(t)) {
+            continue;
+        }
+        let files = match walker::discover_package(&pkg_dir, &config, &active_tags) {
+            Ok(files) => files,
+            Err(e) => {
+                findings.push(
+                    Severity::Error,
+                    "discovery",
+                    format!("Package '{name}': {e}"),
+                    "Fix the [targets] entry or file variants named in the error",
+                );
+                continue;
+            }
+        };
+
+        check_untracked_targets(name, &files, target_dir, resolved_dir, lock, findings);
+
+        package_files.push(PackageFiles {
+            package: name.clone(),
+            priority: entry.priority,
+            files,
+        });
+    }
+
+    check_scripts(stow_dir, manifest, &variables, findings);
+    check_collisions(&package_files, findings);
+}
+
+/// With an unparseable manifest the packages are unknown, so check every
+/// `boat.toml` under the stow dir instead.
+fn check_boat_tomls(stow_dir: &Path, findings: &mut Findings) {
+    let hidden = |entry: &walkdir::DirEntry| {
+        entry.depth() > 0 && entry.file_name().to_string_lossy().starts_with('.')
+    };
+    for entry in WalkDir::new(stow_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| !hidden(e))
+        .filter_map(|e| e.ok())
+    {
+        if entry.file_name() == "boat.toml"
+            && let Err(e) = PackageConfig::load(entry.path())
+        {
+            findings.push_config_error(e.to_string());
+        }
+    }
+}
+
+/// `[variables]` plus every profile's variables: a source may only deploy on a
+/// machine whose profile defines what it uses.
+fn known_variables(manifest: &SystemManifest) -> HashSet<String> {
+    manifest
+        .variables
+        .keys()
+        .chain(manifest.profiles.values().flat_map(|p| p.variables.keys()))
+        .cloned()
+        .collect()
+}
+
+fn check_expr(expr: &str, origin: impl FnOnce() -> String, findings: &mut Findings) {
+    if expr.is_empty() {
+        return;
+    }
+    if let Err(e) = matcher::parse(expr) {
+        findings.push(
+            Severity::Error,
+            "tag-expr",
+            format!("{}: `{expr}`: {e}", origin()),
+            "Use tag names with `&`, `|`, `!` and parentheses",
+        );
+    }
+}
+
+fn check_manifest_exprs(manifest: &SystemManifest, findings: &mut Findings) {
+    for (name, entry) in &manifest.packages {
+        let hooks = &entry.hooks;
+        for (kind, commands) in [
+            ("pre_sync", &hooks.pre_sync),
+            ("post_sync", &hooks.post_sync),
+            ("post_change", &hooks.post_change),
+            ("on_unlink", &hooks.on_unlink),
+        ] {
+            for hook in commands {
+                check_expr(
+                    &hook.tags_expr(),
+                    || format!("towboat.toml: {kind} hook of '{name}'"),
+                    findings,
+                );
+            }
+        }
+    }
+    for script in &manifest.scripts {
+        check_expr(
+            &script.tags.to_expr_string(),
+            || format!("towboat.toml: script {}", script.path),
+            findings,
+        );
+    }
+}
+
+/// Check that every nested `boat.toml` parses and that the tag expressions of
+/// all of the package's configs do. Returns `false` if a nested config is invalid,
+/// in which case discovery would fail on it too.
+fn check_package_configs(
+    name: &str,
+    pkg_dir: &Path,
+    config: &PackageConfig,
+    findings: &mut Findings,
+) -> bool {
+    let mut configs = vec![(format!("package '{name}'"), config.clone())];
+    let mut valid = true;
+
+    for entry in WalkDir::new(pkg_dir).into_iter().filter_map(|e| e.ok()) {
+        if entry.depth() < 2 || entry.file_name() != "boat.toml" {
+            continue;
+        }
+        match PackageConfig::load(entry.path()) {
+            Ok(nested) => configs.push((entry.path().display().to_string(), nested)),
+            Err(e) => {
+                findings.push_config_error(e.to_string());
+                valid = false;
+            }
+        }
+    }
+
+    for (origin, config) in &configs {
+        let mut keys: Vec<&String> = config.targets.keys().collect();
+        keys.sort();
+        for key in keys {
+            check_expr(
+                &config.targets[key].tags.to_expr_string(),
+                || format!("{origin}: [targets] \"{key}\""),
+                findings,
+            );
+        }
+        if let Some(default) = &config.default
+            && default.include_all
+        {
+            check_expr(
+                &default.default_tag,
+                || format!("{origin}: [default] default_tag"),
+                findings,
+            );
+        }
+    }
+
+    valid
+}
+
+/// Check every source of a package, including variants and in-file sections
+/// for other tags: tag sections must parse and every template variable must be
+/// defined.
+fn check_sources(
+    name: &str,
+    pkg_dir: &Path,
+    sources: &[PathBuf],
+    variables: &HashSet<String>,
+    findings: &mut Findings,
+) {
+    for path in sources {
+        let relative = path.strip_prefix(pkg_dir).unwrap_or(path);
+        let origin = format!("{name}/{}", relative.display());
+        check_template_source(path, &origin, variables, findings);
+    }
+}
+
+fn check_template_source(
+    path: &Path,
+    origin: &str,
+    variables: &HashSet<String>,
+    findings: &mut Findings,
+) {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        findings.push(
+            Severity::Error,
+            "unreadable-source",
+            format!("{origin}: not readable as UTF-8 text"),
+            "Fix permissions or ignore the file",
+        );
+        return;
+    };
+    if let Err(e) = parser::process_tags(&content, &HashSet::new()) {
+        findings.push(
+            Severity::Error,
+            "tag-section",
+            format!("{origin}: {e}"),
+            "Fix the in-file tag section markers",
+        );
+        return;
+    }
+    // Sections for every tag, not just the active ones
+    for var in engine::referenced_variables(&content) {
+        if !variables.contains(&var) {
+            findings.push(
+                Severity::Error,
+                "undefined-variable",
+                format!("{origin}: `${{{{ {var} }}}}` is not defined"),
+                format!("Add `{var} = \"...\"` to [variables] or escape it as `\\${{{{`"),
+            );
+        }
+    }
+}
+
+fn check_scripts(
+    stow_dir: &Path,
+    manifest: &SystemManifest,
+    variables: &HashSet<String>,
+    findings: &mut Findings,
+) {
+    let Ok(collected) = scripts::collect(stow_dir, &manifest.scripts) else {
+        return;
+    };
+    for script in collected {
+        let path = stow_dir.join(&script.path);
+        if !path.is_file() {
+            findings.push(
+                Severity::Error,
+                "missing-script",
+                format!("Script {} does not exist", script.path),
+                "Fix the path in [[scripts]] or remove the entry",
+            );
+            continue;
+        }
+        check_template_source(&path, &script.path, variables, findings);
+    }
+}
+
+/// Deployed files whose target is occupied by something sync doesn't own.
+fn check_untracked_targets(
+    name: &str,
+    files: &[DiscoveredFile],
+    target_dir: &Path,
+    resolved_dir: &Path,
+    lock: &LockFile,
+    findings: &mut Findings,
+) {
+    for file in files {
+        let target = file.target_path.to_string_lossy();
+        let link_path = target_dir.join(&file.target_path);
+        let resolved_path = resolved_dir.join(name).join(&file.relative_path);
+        if lock.find_by_target(&target).is_none()
+            && (link_path.exists() || link_path.is_symlink())
+            && !symlink::symlink_matches(&link_path, &resolved_path)
+        {
+            findings.push(
+                Severity::Warning,
+                "foreign-target",
+                format!(
+                    "{} exists but is not managed by towboat; sync of {name}/{} will refuse to replace it",
+                    link_path.display(),
+                    file.relative_path.display()
+                ),
+                "Move the existing file away, or run `towboat sync --force`",
+            );
+        }
+    }
+}
+
+fn check_collisions(package_files: &[PackageFiles], findings: &mut Findings) {
+    for collision in collision::detect(package_files).collisions {
+        let claims: Vec<String> = collision.claims.iter().map(|c| c.to_string()).collect();
+        findings.push(
+            Severity::Error,
+            "collision",
+            format!(
+                "Target {} is claimed by {}",
+                collision.target.display(),
+                claims.join(" and ")
+            ),
+            "Remove or retarget one of the files, or set `priority` on a package",
+        );
+    }
+}
+
+fn check_lock(
+    stow_dir: &Path,
+    target_dir: &Path,
+    resolved_dir: &Path,
+    lock: &LockFile,
+    findings: &mut Findings,
+) {
+    for entry in lock.entries() {
+        if !stow_dir.join(&entry.source).exists() {
+            findings.push(
+                Severity::Warning,
+                "missing-source",
+                format!("Lock entry {} points to a missing source", entry.source),
+                "Run `towboat sync` to remove the stale entry and its symlink",
+            );
+        }
+
+        let link_path = target_dir.join(&entry.target);
+        let resolved_path = resolved_dir.join(&entry.source);
+        if let Some(old) = symlink::old_state_dir(&link_path, &resolved_path, &entry.source) {
+            findings.push(
+                Severity::Error,
+                "moved-state-dir",
+                format!(
+                    "{} points into {}, not this stow dir's state dir",
+                    link_path.display(),
+                    old.display()
+                ),
+                "Run `towboat relocate` to re-point every link at the current state dir",
+            );
+        } else if symlink::is_broken_symlink(&link_path) {
+            findings.push(
+                Severity::Error,
+                "broken-symlink",
+                format!("{} is a broken symlink", link_path.display()),
+                "Run `towboat sync` to recreate it",
+            );
+        } else if link_path.is_symlink() {
+            if !symlink::symlink_matches(&link_path, &resolved_path) {
+                let points_to = std::fs::read_link(&link_path).unwrap_or_default();
+                findings.push(
+                    Severity::Warning,
+                    "foreign-symlink",
+                    format!(
+                        "{} points to {} instead of {}",
+                        link_path.display(),
+                        points_to.display(),
+                        resolved_path.display()
+                    ),
+                    "Run `towboat sync --force` to point it back, or remove the lock entry's source",
+                );
+            }
+        } else if link_path.exists() {
+            findings.push(
+                Severity::Warning,
+                "foreign-target",
+                format!(
+                    "{} was replaced by a regular file or directory",
+                    link_path.display()
+                ),
+                "Copy your edits into the source, then run `towboat sync --force`",
+            );
+        } else {
+            findings.push(
+                Severity::Warning,
+                "missing-symlink",
+                format!("{} is missing", link_path.display()),
+                "Run `towboat sync` to recreate it",
+            );
+        }
+    }
+}
+
+/// Files in the resolved dir that no lock entry refers to.
+fn check_orphans(resolved_dir: &Path, lock: &LockFile, findings: &mut Findings) {
+    if !resolved_dir.is_dir() {
+        return;
+    }
+    let tracked: HashSet<PathBuf> = lock
+        .entries()
+        .iter()
+        .map(|e| PathBuf::from(&e.source))
+        .collect();
+    for entry in WalkDir::new(resolved_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+    {
+        let Ok(relative) = entry.path().strip_prefix(resolved_dir) else {
+            continue;
+        };
+        if !tracked.contains(relative) {
+            findings.push(
+                Severity::Info,
+                "orphaned-resolved",
+                format!(
+                    "{} is not referenced by the lock file",
+                    entry.path().display()
+                ),
+                "Delete it; it is left over from an earlier sync",
+            );
+        }
+    }
+}
+
+fn print_text(findings: &[Finding]) {
+    if findings.is_empty() {
+        println!("No problems found.");
+        return;
+    }
+
+    for finding in findings {
+        println!(
+            "{}[{}]: {}",
+            finding.severity, finding.check, finding.message
+        );
+        println!("  hint: {}", finding.hint);
+    }
+
+    let count = |severity| findings.iter().filter(|f| f.severity == severity).count();
+    println!(
+        "\n{} error(s), {} warning(s), {} info",
+        count(Severity::Error),
+        count(Severity::Warning),
+        count(Severity::Info)
+    );
+}
@@ -0,0 +1,316 @@
+//! `towboat explain` — show why a file is or isn't deployed.
+//!
+//! Accepts a source path inside a package or a target path (deployed or not) and
+//! walks through every decision: package tags, the config in effect, ignore rules,
+//! filename tags, the `[targets]` rules and the in-file sections.
+
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::config::manifest::{PackageEntry, SystemManifest};
+use crate::config::overrides::Overrides;
+use crate::deploy::lock::LockFile;
+use crate::deploy::state::StateDir;
+use crate::discovery::variant::Variant;
+use crate::discovery::walker::{self, FileExplanation};
+use crate::tags::{matcher, parser};
+
+pub fn run(stow_dir: &Path, target_dir: &Path, path: &Path, overrides: &Overrides) -> Result<()> {
+    let manifest_path = stow_dir.join("towboat.toml");
+    let mut manifest =
+        SystemManifest::load(&manifest_path).context("Failed to load towboat.toml")?;
+    overrides.apply(&mut manifest)?;
+    let active_tags = manifest.active_tags();
+
+    let sources = match locate_source(stow_dir, path) {
+        Some(source) => vec![source],
+        None => {
+            let sources = sources_for_target(stow_dir, target_dir, &manifest, path)?;
+            if sources.is_empty() {
+                anyhow::bail!(
+                    "{} is neither a file inside the stow directory {} nor a target any package file deploys to",
+                    path.display(),
+                    stow_dir.display()
+                );
+            }
+            sources
+        }
+    };
+
+    for (i, source) in sources.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        explain_source(stow_dir, target_dir, &manifest, &active_tags, source)?;
+    }
+    Ok(())
+}
+
+fn explain_source(
+    stow_dir: &Path,
+    target_dir: &Path,
+    manifest: &SystemManifest,
+    active_tags: &HashSet<String>,
+    source_path: &Path,
+) -> Result<()> {
+    let relative = source_path
+        .strip_prefix(stow_dir)
+        .context("path is not inside the stow directory")?;
+    let pkg_name = package_of(relative)
+        .with_context(|| format!("{} is not a file inside a package", relative.display()))?;
+    let Some(pkg_entry) = manifest.packages.get(&pkg_name) else {
+        anyhow::bail!("Package '{pkg_name}' not found in towboat.toml");
+    };
+
+    let pkg_dir = stow_dir.join(&pkg_name);
+    let config = crate::config::resolve_package_config(&pkg_name, &pkg_dir, pkg_entry)?;
+    let explanation = walker::explain_file(&pkg_dir, &config, active_tags, source_path)?;
+
+    println!("File: {}", relative.display());
+    println!("Package: {pkg_name}");
+
+    let missing_tags: Vec<&String> = pkg_entry
+        .tags
+        .iter()
+        .filter(|t| !active_tags.contains(*t))
+        .collect();
+    if pkg_entry.tags.is_empty() {
+        println!("Package tags: none required");
+    } else if missing_tags.is_empty() {
+        println!("Package tags: {} (passed)", pkg_entry.tags.join(", "));
+    } else {
+        let missing: Vec<&str> = missing_tags.iter().map(|t| t.as_str()).collect();
+        println!(
+            "Package tags: {} (failed: {} not active)",
+            pkg_entry.tags.join(", "),
+            missing.join(", ")
+        );
+    }
+
+    println!(
+        "Config: {}",
+        describe_config(stow_dir, &pkg_name, &pkg_dir, pkg_entry, &explanation)
+    );
+    if explanation.ignored {
+        println!("Ignored by ignore rules");
+    }
+    if let Some(variant) = &explanation.variant {
+        let state = if variant.matches(active_tags) {
+            "matched"
+        } else {
+            "not matched"
+        };
+        println!("Filename tags: {} ({state})", variant.describe());
+    }
+
+    print_rules(&explanation, active_tags)?;
+    print_sections(source_path, active_tags);
+
+    match &explanation.target_path {
+        _ if !missing_tags.is_empty() => {
+            println!("Result: not deployed (package tags not met)")
+        }
+        Some(target) => {
+            // Another variant of the same file may be more specific
+            let discovered = walker::discover_package(&pkg_dir, &config, active_tags)?;
+            match discovered
+                .iter()
+                .find(|f| &f.target_path == target && f.source_path != source_path)
+            {
+                Some(winner) => println!(
+                    "Result: not deployed ({} is a more specific variant)",
+                    winner.relative_path.display()
+                ),
+                None => println!("Result: deployed to {}", target_dir.join(target).display()),
+            }
+        }
+        None => println!("Result: not deployed"),
+    }
+
+    Ok(())
+}
+
+/// Which config applies to the file: inline, `boat.toml`, nested `boat.toml` or none.
+fn describe_config(
+    stow_dir: &Path,
+    pkg_name: &str,
+    pkg_dir: &Path,
+    pkg_entry: &PackageEntry,
+    explanation: &FileExplanation,
+) -> String {
+    if explanation.config_root != pkg_dir {
+        let nested = explanation.config_root.join("boat.toml");
+        format!(
+            "{} (nested)",
+            nested.strip_prefix(stow_dir).unwrap_or(&nested).display()
+        )
+    } else if pkg_entry.has_inline_config() {
+        format!("inline [packages.{pkg_name}] in towboat.toml")
+    } else if pkg_dir.join("boat.toml").exists() {
+        format!("{pkg_name}/boat.toml")
+    } else {
+        "none (all files included, tagged `default`)".to_string()
+    }
+}
+
+fn print_rules(explanation: &FileExplanation, active_tags: &HashSet<String>) -> Result<()> {
+    if explanation.candidates.is_empty() {
+        println!("Rules: none (no [targets] entry and [default] include_all is off)");
+        return Ok(());
+    }
+
+    println!("Rules (highest precedence first):");
+    for (i, candidate) in explanation.candidates.iter().enumerate() {
+        let note = match explanation.decision {
+            Some((d, matched)) if d == i => {
+                if matched {
+                    "  <- decides: matched"
+                } else {
+                    "  <- decides: not matched"
+                }
+            }
+            Some((d, _)) if i > d => "  (shadowed)",
+            _ if candidate.expr.is_empty() => "  (no tags, falls through)",
+            _ => "",
+        };
+        let expr = if candidate.expr.is_empty() {
+            "-".to_string()
+        } else {
+            candidate.expr.clone()
+        };
+        println!("  {}: {expr}{note}", candidate.rule);
+
+        if explanation.decision.is_some_and(|(d, _)| d == i) {
+            let expr = matcher::parse(&candidate.expr)?;
+            for line in expr.trace(active_tags).to_string().lines() {
+                println!("      {line}");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// In-file tag sections and whether each is kept with the active tags.
+fn print_sections(source_path: &Path, active_tags: &HashSet<String>) {
+    let Ok(content) = std::fs::read_to_string(source_path) else {
+        return;
+    };
+    let sections = parser::section_exprs(&content);
+    if sections.is_empty() {
+        return;
+    }
+
+    println!("Sections:");
+    for (line, expr) in sections {
+        let state = match matcher::parse(&expr) {
+            Ok(parsed) if parsed.evaluate(active_tags) => "kept".to_string(),
+            Ok(_) => "dropped".to_string(),
+            Err(e) => format!("invalid ({e})"),
+        };
+        println!("  line {line}: {expr}  {state}");
+    }
+}
+
+/// Package name of a stow-relative path (its first component), if it is inside one.
+fn package_of(relative: &Path) -> Option<String> {
+    match relative.components().next() {
+        Some(Component::Normal(name)) if relative.components().count() > 1 => {
+            Some(name.to_string_lossy().into_owned())
+        }
+        _ => None,
+    }
+}
+
+/// Resolve `path` to an absolute source path: as given if it exists (absolute or
+/// relative to the current directory), otherwise relative to the stow directory.
+///
+/// A deployed symlink resolves into the state dir's `resolved/`, which maps back
+/// to its source.
+pub(crate) fn locate_source(stow_dir: &Path, path: &Path) -> Option<PathBuf> {
+    let real = |dir: &Path| dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let stow = real(stow_dir);
+    let resolved_dir = StateDir::for_stow(stow_dir)
+        .ok()
+        .map(|state| real(&state.resolved_dir()));
+
+    for candidate in [path.to_path_buf(), stow_dir.join(path)] {
+        if !candidate.is_file() {
+            continue;
+        }
+        let Ok(canonical) = candidate.canonicalize() else {
+            continue;
+        };
+        let rel = resolved_dir
+            .as_ref()
+            .and_then(|dir| canonical.strip_prefix(dir).ok())
+            .or_else(|| canonical.strip_prefix(&stow).ok());
+        if let Some(rel) = rel
+            && stow_dir.join(rel).is_file()
+        {
+            return Some(stow_dir.join(rel));
+        }
+    }
+    None
+}
+
+/// Source files that deploy, or would deploy with other tags, to `path` in the
+/// target directory.
+fn sources_for_target(
+    stow_dir: &Path,
+    target_dir: &Path,
+    manifest: &SystemManifest,
+    path: &Path,
+) -> Result<Vec<PathBuf>> {
+    let target = if path.is_absolute() {
+        match path.strip_prefix(target_dir) {
+            Ok(rel) => rel.to_path_buf(),
+            Err(_) => return Ok(Vec::new()),
+        }
+    } else {
+        path.to_path_buf()
+    };
+
+    let lock =
+        LockFile::load(&StateDir::new(stow_dir, &manifest.system)?.lock_path()).unwrap_or_default();
+    if let Some(entry) = lock.find_by_target(&target.to_string_lossy()) {
+        let source = stow_dir.join(&entry.source);
+        if source.is_file() {
+            return Ok(vec![source]);
+        }
+    }
+
+    // Not deployed: every file whose target would be `target` under some tags.
+    let mut sources = Vec::new();
+    let mut names: Vec<&String> = manifest.packages.keys().collect();
+    names.sort();
+    for name in names {
+        let pkg_dir = stow_dir.join(name);
+        if !pkg_dir.is_dir() {
+            continue;
+        }
+        let config =
+            crate::config::resolve_package_config(name, &pkg_dir, &manifest.packages[name])?;
+        let remapped: HashSet<&str> = config
+            .targets
+            .iter()
+            .filter(|(_, t)| t.target.as_deref() == Some(&*target.to_string_lossy()))
+            .map(|(key, _)| key.as_str())
+            .collect();
+
+        for file in walker::source_files(&pkg_dir, &config)? {
+            let Ok(relative) = file.strip_prefix(&pkg_dir) else {
+                continue;
+            };
+            let stripped = match Variant::from_path(&file) {
+                Some(variant) => variant.strip(relative),
+                None => relative.to_path_buf(),
+            };
+            if stripped == target || remapped.contains(&*stripped.to_string_lossy()) {
+                sources.push(file);
+            }
+        }
+    }
+    Ok(sources)
+}
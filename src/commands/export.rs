@@ -0,0 +1,285 @@
+//! `towboat export` — resolve packages into a standalone tree of real files.
+//!
+//! Export goes through the same plan and resolver as `sync`, so package tags,
+//! collisions and priorities apply, but it never reads or writes the state
+//! dir and never touches the target directory. The result is shaped like the
+//! target (`.vimrc`, `.config/...`) and can be copied to a machine that
+//! doesn't run towboat, e.g. a container image or a remote server.
+
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::commands::plan;
+use crate::config::manifest::SystemManifest;
+use crate::config::overrides::Overrides;
+use crate::resolve::resolver::resolve_discovered;
+
+/// Where the exported tree is written.
+#[derive(Debug, Clone)]
+pub enum ExportDest {
+    /// A new or empty directory.
+    Dir(PathBuf),
+    /// A tar archive, overwritten if it exists.
+    Tar(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    pub dest: ExportDest,
+    pub overrides: Overrides,
+}
+
+/// One resolved file, keyed by its path relative to the target directory.
+struct ExportedFile {
+    target: PathBuf,
+    content: String,
+    executable: bool,
+    mtime: u64,
+}
+
+pub fn run(stow_dir: &Path, package_filter: Option<&str>, options: &ExportOptions) -> Result<()> {
+    let manifest_path = stow_dir.join("towboat.toml");
+    let mut manifest =
+        SystemManifest::load(&manifest_path).context("Failed to load towboat.toml")?;
+    options.overrides.apply(&mut manifest)?;
+    let active_tags = manifest.active_tags();
+
+    let plan = plan::plan(stow_dir, &manifest, &active_tags, package_filter)?;
+
+    // Resolve everything before writing, so a bad template leaves no partial tree.
+    let mut files = Vec::new();
+    let mut packages = 0;
+    let mut errors = Vec::new();
+    for pkg in &plan.packages {
+        let discovered = pkg.files();
+        if !discovered.is_empty() {
+            packages += 1;
+        }
+        for file in discovered {
+            match resolve_discovered(pkg.name, file, &active_tags, &manifest.variables) {
+                Ok(resolved) => {
+                    let metadata = std::fs::metadata(&file.source_path)?;
+                    files.push(ExportedFile {
+                        target: resolved.target_relative,
+                        content: resolved.content,
+                        executable: is_executable(&metadata),
+                        mtime: metadata
+                            .modified()
+                            .ok()
+                            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                            .map_or(0, |d| d.as_secs()),
+                    });
+                }
+                Err(e) => errors.push(format!("{}: {e}", file.source_path.display())),
+            }
+        }
+    }
+    if !errors.is_empty() {
+        anyhow::bail!("Failed to resolve:\n  {}", errors.join("\n  "));
+    }
+    files.sort_by(|a, b| a.target.cmp(&b.target));
+
+    let dest = match &options.dest {
+        ExportDest::Dir(dir) => {
+            write_dir(dir, &files)?;
+            dir
+        }
+        ExportDest::Tar(path) => {
+            write_tar(path, &files)?;
+            path
+        }
+    };
+
+    println!(
+        "Exported {} file(s) from {} package(s) to {}",
+        files.len(),
+        packages,
+        dest.display()
+    );
+    Ok(())
+}
+
+fn write_dir(dir: &Path, files: &[ExportedFile]) -> Result<()> {
+    if dir.exists() {
+        let mut entries = std::fs::read_dir(dir)
+            .with_context(|| format!("{} is not a directory", dir.display()))?;
+        if entries.next().is_some() {
+            anyhow::bail!(
+                "{} is not empty; export into a new or empty directory",
+                dir.display()
+            );
+        }
+    }
+    std::fs::create_dir_all(dir)?;
+
+    for file in files {
+        let path = dir.join(&file.target);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, &file.content)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        #[cfg(unix)]
+        if file.executable {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+        }
+    }
+    Ok(())
+}
+
+fn write_tar(path: &Path, files: &[ExportedFile]) -> Result<()> {
+    let out = File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut builder = tar::Builder::new(out);
+
+    // Parent directories first, so extracting creates them with sane modes.
+    let dirs: BTreeSet<&Path> = files
+        .iter()
+        .flat_map(|f| f.target.ancestors().skip(1))
+        .filter(|d| !d.as_os_str().is_empty())
+        .collect();
+    for dir in dirs {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_mode(0o755);
+        header.set_size(0);
+        builder.append_data(&mut header, dir, std::io::empty())?;
+    }
+
+    for file in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(if file.executable { 0o755 } else { 0o644 });
+        header.set_size(file.content.len() as u64);
+        header.set_mtime(file.mtime);
+        builder.append_data(&mut header, &file.target, file.content.as_bytes())?;
+    }
+    builder.into_inner()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &std::fs::Metadata) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn setup() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("towboat.toml"),
+            "[system]\nstate_dir = \"state\"\ntags = [\"linux\"]\n\n[packages.bash]\n\n[packages.work]\ntags = [\"work\"]\n",
+        )
+        .unwrap();
+        fs::create_dir_all(dir.path().join("bash/.config/bash")).unwrap();
+        fs::write(
+            dir.path().join("bash/.bashrc"),
+            "# {linux-\nexport OS=linux\n# -linux}\n# {macos-\nexport OS=macos\n# -macos}\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("bash/.config/bash/aliases"), "alias l=ls\n").unwrap();
+        fs::write(
+            dir.path().join("bash/boat.toml"),
+            "[targets]\n\".bashrc\" = { tags = \"linux\" }\n\".config/bash/aliases\" = { tags = \"linux\" }\n",
+        )
+        .unwrap();
+        fs::create_dir_all(dir.path().join("work")).unwrap();
+        fs::write(dir.path().join("work/.workrc"), "work\n").unwrap();
+        fs::write(
+            dir.path().join("work/boat.toml"),
+            "[targets]\n\".workrc\" = { tags = \"linux\" }\n",
+        )
+        .unwrap();
+        dir
+    }
+
+    fn options(dest: ExportDest) -> ExportOptions {
+        ExportOptions {
+            dest,
+            overrides: Overrides::default(),
+        }
+    }
+
+    #[test]
+    fn export_writes_real_files_without_state() {
+        let stow = setup();
+        let out = TempDir::new().unwrap();
+        let dest = out.path().join("tree");
+
+        run(stow.path(), None, &options(ExportDest::Dir(dest.clone()))).unwrap();
+
+        let bashrc = dest.join(".bashrc");
+        assert!(!bashrc.symlink_metadata().unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&bashrc).unwrap(), "export OS=linux\n");
+        assert_eq!(
+            fs::read_to_string(dest.join(".config/bash/aliases")).unwrap(),
+            "alias l=ls\n"
+        );
+        assert!(!dest.join(".workrc").exists());
+        assert!(!stow.path().join("state").exists());
+    }
+
+    #[test]
+    fn export_refuses_non_empty_dir() {
+        let stow = setup();
+        let out = TempDir::new().unwrap();
+        fs::write(out.path().join("keep"), "").unwrap();
+
+        let err = run(
+            stow.path(),
+            None,
+            &options(ExportDest::Dir(out.path().to_path_buf())),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("not empty"));
+    }
+
+    #[test]
+    fn export_tar_contains_target_paths() {
+        let stow = setup();
+        let out = TempDir::new().unwrap();
+        let archive = out.path().join("dots.tar");
+
+        run(
+            stow.path(),
+            None,
+            &options(ExportDest::Tar(archive.clone())),
+        )
+        .unwrap();
+
+        let mut entries = Vec::new();
+        let mut tar = tar::Archive::new(File::open(&archive).unwrap());
+        for entry in tar.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().display().to_string();
+            let mut content = String::new();
+            std::io::Read::read_to_string(&mut entry, &mut content).unwrap();
+            entries.push((path, content));
+        }
+        assert_eq!(
+            entries,
+            vec![
+                (".config".to_string(), String::new()),
+                (".config/bash".to_string(), String::new()),
+                (".bashrc".to_string(), "export OS=linux\n".to_string()),
+                (
+                    ".config/bash/aliases".to_string(),
+                    "alias l=ls\n".to_string()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,39 @@
+//! `towboat generations` — list recorded sync generations.
+
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::Local;
+
+use crate::deploy::generations::Generations;
+use crate::deploy::state::StateDir;
+
+pub fn run(stow_dir: &Path) -> Result<()> {
+    let store = Generations::new(StateDir::for_stow(stow_dir)?.root());
+    let generations = store.list()?;
+    if generations.is_empty() {
+        println!("No generations recorded yet; each sync that changes the resolved tree adds one.");
+        return Ok(());
+    }
+
+    let current = store.current();
+    println!("   GEN  CREATED              FILES  CHANGED");
+    for generation in &generations {
+        let marker = if Some(generation.number) == current {
+            '*'
+        } else {
+            ' '
+        };
+        println!(
+            "{marker} {:>4}  {}  {:>5}  {:>7}",
+            generation.number,
+            generation
+                .created
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S"),
+            generation.files.len(),
+            generation.changed,
+        );
+    }
+    Ok(())
+}
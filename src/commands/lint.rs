@@ -0,0 +1,324 @@
+//! `towboat lint` — check tag references against the declared tag vocabulary.
+//!
+//! Every tag expression in the manifest, package configs, filename suffixes and
+//! in-file sections is checked for unknown tags (typos like `lnux`), for being
+//! always true or always false, and for matching none of the declared profiles.
+
+use std::collections::{BTreeSet, HashSet};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use walkdir::WalkDir;
+
+use crate::commands::OutputFormat;
+use crate::commands::doctor::{self, Finding, Findings, Severity};
+use crate::config::manifest::SystemManifest;
+use crate::config::package::PackageConfig;
+use crate::discovery::variant::Variant;
+use crate::discovery::walker;
+use crate::tags::{matcher, parser};
+
+/// A tag expression found somewhere in the repo.
+struct TagRef {
+    /// Where it was found, e.g. `bash/.bashrc:12: section`.
+    origin: String,
+    /// The expression as written.
+    text: String,
+}
+
+/// Lint the repo and print the findings; fails if any finding is an error.
+pub fn run(stow_dir: &Path, format: OutputFormat) -> Result<()> {
+    let findings = lint(stow_dir)?;
+    doctor::report("lint", &findings, format)
+}
+
+/// Collect lint findings, sorted by severity.
+pub fn lint(stow_dir: &Path) -> Result<Vec<Finding>> {
+    let manifest = SystemManifest::load(&stow_dir.join("towboat.toml"))
+        .context("Failed to load towboat.toml")?;
+    let mut findings = Findings::default();
+    let refs = collect_refs(stow_dir, &manifest, &mut findings);
+
+    let vocabulary = manifest.tag_vocabulary();
+    if vocabulary.is_none() {
+        findings.push(
+            Severity::Info,
+            "no-vocabulary",
+            "No tag vocabulary is declared, so unknown tags are not checked",
+            "Add `known_tags = [...]` to [system], or describe your machines as [profiles.<name>]",
+        );
+    }
+
+    let mut profiles: Vec<(String, HashSet<String>)> =
+        vec![("this machine".to_string(), manifest.active_tags())];
+    let mut names: Vec<&String> = manifest.profiles.keys().collect();
+    names.sort();
+    for name in names {
+        let tags = manifest.profiles[name].tags.iter().cloned().collect();
+        profiles.push((name.clone(), tags));
+    }
+    let profiles = if manifest.profiles.is_empty() {
+        None
+    } else {
+        Some(profiles)
+    };
+
+    for tag_ref in &refs {
+        check_ref(
+            tag_ref,
+            vocabulary.as_ref(),
+            profiles.as_deref(),
+            &mut findings,
+        );
+    }
+
+    let mut findings = findings.0;
+    findings.sort_by_key(|f| f.severity);
+    Ok(findings)
+}
+
+fn check_ref(
+    tag_ref: &TagRef,
+    vocabulary: Option<&BTreeSet<String>>,
+    profiles: Option<&[(String, HashSet<String>)]>,
+    findings: &mut Findings,
+) {
+    let TagRef { origin, text } = tag_ref;
+    let expr = match matcher::parse(text) {
+        Ok(expr) => expr,
+        Err(e) => {
+            findings.push(
+                Severity::Error,
+                "tag-expr",
+                format!("{origin}: `{text}`: {e}"),
+                "Use tag names with `&`, `|`, `!` and parentheses",
+            );
+            return;
+        }
+    };
+
+    if let Some(vocabulary) = vocabulary {
+        for tag in expr
+            .variables()
+            .into_iter()
+            .filter(|t| !vocabulary.contains(*t))
+        {
+            let hint = match closest(tag, vocabulary) {
+                Some(known) => {
+                    format!("Did you mean `{known}`? Otherwise add `{tag}` to [system] known_tags")
+                }
+                None => format!("Add `{tag}` to [system] known_tags or fix its spelling"),
+            };
+            findings.push(
+                Severity::Error,
+                "unknown-tag",
+                format!("{origin}: unknown tag `{tag}` in `{text}`"),
+                hint,
+            );
+        }
+    }
+
+    if expr.is_always_false() {
+        findings.push(
+            Severity::Error,
+            "always-false",
+            format!("{origin}: `{text}` can never match"),
+            "Fix the expression; as written the content is never deployed",
+        );
+        return;
+    }
+    if expr.is_always_true() {
+        findings.push(
+            Severity::Warning,
+            "always-true",
+            format!("{origin}: `{text}` always matches"),
+            "Drop the condition, or fix the expression if it should be selective",
+        );
+    }
+
+    if let Some(profiles) = profiles
+        && !profiles.iter().any(|(_, active)| expr.evaluate(active))
+    {
+        let names: Vec<&str> = profiles.iter().map(|(name, _)| name.as_str()).collect();
+        findings.push(
+            Severity::Warning,
+            "unreachable",
+            format!(
+                "{origin}: `{text}` matches none of the profiles ({})",
+                names.join(", ")
+            ),
+            "Declare a profile that uses it, or remove the dead config",
+        );
+    }
+}
+
+/// Every tag expression in the manifest, package configs, file names and sections.
+fn collect_refs(
+    stow_dir: &Path,
+    manifest: &SystemManifest,
+    findings: &mut Findings,
+) -> Vec<TagRef> {
+    let mut refs = Vec::new();
+    let mut push = |origin: String, text: String| {
+        if !text.trim().is_empty() {
+            refs.push(TagRef { origin, text });
+        }
+    };
+
+    for tag in &manifest.system.tags {
+        push("towboat.toml: [system] tags".to_string(), tag.clone());
+    }
+    let mut profile_names: Vec<&String> = manifest.profiles.keys().collect();
+    profile_names.sort();
+    for name in profile_names {
+        for tag in &manifest.profiles[name].tags {
+            push(format!("towboat.toml: [profiles.{name}] tags"), tag.clone());
+        }
+    }
+    for script in &manifest.scripts {
+        push(
+            format!("towboat.toml: script {}", script.path),
+            script.tags.to_expr_string(),
+        );
+    }
+
+    let mut names: Vec<&String> = manifest.packages.keys().collect();
+    names.sort();
+    for name in names {
+        let entry = &manifest.packages[name];
+        let pkg_dir = stow_dir.join(name);
+
+        push(
+            format!("towboat.toml: package '{name}' tags"),
+            entry.tags.join(" & "),
+        );
+        let hooks = &entry.hooks;
+        for (kind, commands) in [
+            ("pre_sync", &hooks.pre_sync),
+            ("post_sync", &hooks.post_sync),
+            ("post_change", &hooks.post_change),
+            ("on_unlink", &hooks.on_unlink),
+        ] {
+            for hook in commands {
+                push(
+                    format!("towboat.toml: {kind} hook of '{name}'"),
+                    hook.tags_expr(),
+                );
+            }
+        }
+
+        if !pkg_dir.is_dir() {
+            continue;
+        }
+        let config = match crate::config::resolve_package_config(name, &pkg_dir, entry) {
+            Ok(config) => config,
+            Err(e) => {
+                findings.push_config_error(format!("{e:#}"));
+                continue;
+            }
+        };
+
+        let mut configs = Vec::new();
+        if entry.has_inline_config() {
+            configs.push((format!("towboat.toml: [packages.{name}]"), config.clone()));
+        } else if pkg_dir.join("boat.toml").exists() {
+            configs.push((format!("{name}/boat.toml"), config.clone()));
+        }
+        for nested in WalkDir::new(&pkg_dir)
+            .min_depth(2)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name() == "boat.toml")
+        {
+            match PackageConfig::load(nested.path()) {
+                Ok(nested_config) => {
+                    let relative = nested
+                        .path()
+                        .strip_prefix(stow_dir)
+                        .unwrap_or(nested.path());
+                    configs.push((relative.display().to_string(), nested_config));
+                }
+                Err(e) => findings.push_config_error(e.to_string()),
+            }
+        }
+        for (origin, config) in &configs {
+            let mut keys: Vec<&String> = config.targets.keys().collect();
+            keys.sort();
+            for key in keys {
+                push(
+                    format!("{origin}: [targets] \"{key}\""),
+                    config.targets[key].tags.to_expr_string(),
+                );
+            }
+            if let Some(default) = &config.default
+                && default.include_all
+            {
+                push(
+                    format!("{origin}: [default] default_tag"),
+                    default.default_tag.clone(),
+                );
+            }
+        }
+
+        let files = match walker::source_files(&pkg_dir, &config) {
+            Ok(files) => files,
+            Err(e) => {
+                findings.push(
+                    Severity::Error,
+                    "discovery",
+                    format!("Package '{name}': {e}"),
+                    "Fix the config or ignore patterns named in the error",
+                );
+                continue;
+            }
+        };
+        for file in files {
+            let relative = file
+                .strip_prefix(stow_dir)
+                .unwrap_or(&file)
+                .display()
+                .to_string();
+            if let Some(variant) = Variant::from_path(&file) {
+                push(
+                    format!("{relative}: filename tags"),
+                    variant.tags.join(" & "),
+                );
+            }
+            let Ok(content) = std::fs::read_to_string(&file) else {
+                continue;
+            };
+            for (line, expr) in parser::section_exprs(&content) {
+                push(format!("{relative}:{line}: section"), expr);
+            }
+        }
+    }
+
+    refs
+}
+
+/// The vocabulary entry closest to `tag`, if it is a likely typo.
+fn closest<'a>(tag: &str, vocabulary: &'a BTreeSet<String>) -> Option<&'a str> {
+    vocabulary
+        .iter()
+        .map(|known| (strsim::damerau_levenshtein(tag, known), known))
+        .filter(|(distance, known)| *distance <= (known.len() / 3).max(1))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, known)| known.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn closest_suggests_typos_only() {
+        let vocabulary: BTreeSet<String> = ["linux", "macos", "work"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(closest("lnux", &vocabulary), Some("linux"));
+        assert_eq!(closest("mcaos", &vocabulary), Some("macos"));
+        assert_eq!(closest("server", &vocabulary), None);
+    }
+}
@@ -0,0 +1,202 @@
+//! `towboat log` — show what past syncs and rollbacks did, newest first.
+
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+use crate::deploy::history::{History, HistoryRecord};
+use crate::deploy::state::StateDir;
+
+#[derive(Debug, Clone, Default)]
+pub struct LogOptions {
+    /// Only show file changes of this package.
+    pub package: Option<String>,
+    /// Only show records at or after this time (see [`parse_since`]).
+    pub since: Option<String>,
+}
+
+pub fn run(stow_dir: &Path, options: &LogOptions) -> Result<()> {
+    let since = options
+        .since
+        .as_deref()
+        .map(|s| parse_since(s, Utc::now()))
+        .transpose()?;
+    let records = History::new(StateDir::for_stow(stow_dir)?.root()).read()?;
+    if records.is_empty() {
+        println!("No history recorded yet.");
+        return Ok(());
+    }
+
+    let mut shown = 0;
+    for record in records.iter().rev() {
+        if since.is_some_and(|since| record.time < since) {
+            continue;
+        }
+        let Some(record) = filter(record, options.package.as_deref()) else {
+            continue;
+        };
+        print_record(&record);
+        shown += 1;
+    }
+    if shown == 0 {
+        println!("No matching history.");
+    }
+    Ok(())
+}
+
+/// Narrow `record` to the file changes of `package`; `None` if it has none.
+fn filter(record: &HistoryRecord, package: Option<&str>) -> Option<HistoryRecord> {
+    let Some(package) = package else {
+        return Some(record.clone());
+    };
+    let files: Vec<_> = record
+        .files
+        .iter()
+        .filter(|f| f.package == package)
+        .cloned()
+        .collect();
+    if files.is_empty() {
+        return None;
+    }
+    Some(HistoryRecord {
+        files,
+        errors: Vec::new(),
+        ..record.clone()
+    })
+}
+
+fn print_record(record: &HistoryRecord) {
+    let mut header = format!(
+        "{}  {}",
+        record
+            .time
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S"),
+        record.action.as_str()
+    );
+    match (&record.user, &record.host) {
+        (Some(user), Some(host)) => header.push_str(&format!("  {user}@{host}")),
+        (Some(name), None) | (None, Some(name)) => header.push_str(&format!("  {name}")),
+        (None, None) => {}
+    }
+    if let Some(profile) = &record.profile {
+        header.push_str(&format!("  profile {profile}"));
+    }
+    if !record.tags.is_empty() {
+        header.push_str(&format!("  tags {}", record.tags.join(",")));
+    }
+    if let Some(package) = &record.package {
+        header.push_str(&format!("  package {package}"));
+    }
+    if let Some(generation) = record.generation {
+        header.push_str(&format!("  generation {generation}"));
+    }
+    println!("{header}");
+
+    for file in &record.files {
+        println!(
+            "    {:<8} {} ({})",
+            file.change.as_str(),
+            file.target,
+            file.package
+        );
+    }
+    for error in &record.errors {
+        println!("    {:<8} {error}", "error");
+    }
+    if record.files.is_empty() && record.errors.is_empty() {
+        println!("    no changes");
+    }
+}
+
+/// Parse `--since`: an age (`30m`, `12h`, `3d`, `2w`), a local date
+/// (`2026-10-01`) or date and time (`2026-10-01 14:30`), or RFC 3339.
+pub fn parse_since(value: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    let value = value.trim();
+    let invalid = || {
+        anyhow::anyhow!(
+            "invalid --since {value:?}: expected an age like 3d or 12h, a date like 2026-10-01, \
+             or a date and time like \"2026-10-01 14:30\""
+        )
+    };
+
+    if let Some(unit) = value.chars().last().filter(char::is_ascii_alphabetic)
+        && let Ok(amount) = value[..value.len() - 1].trim().parse::<i64>()
+    {
+        let age = match unit {
+            'm' => Duration::minutes(amount),
+            'h' => Duration::hours(amount),
+            'd' => Duration::days(amount),
+            'w' => Duration::weeks(amount),
+            _ => return Err(invalid()),
+        };
+        return Ok(now - age);
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    let local = if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        date.and_hms_opt(0, 0, 0)
+    } else {
+        ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+    };
+    local
+        .and_then(|time| Local.from_local_datetime(&time).earliest())
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deploy::history::{Action, Change, FileChange};
+
+    #[test]
+    fn since_accepts_ages_dates_and_rfc3339() {
+        let now = Utc.with_ymd_and_hms(2026, 10, 18, 12, 0, 0).unwrap();
+        assert_eq!(parse_since("3d", now).unwrap(), now - Duration::days(3));
+        assert_eq!(
+            parse_since("90m", now).unwrap(),
+            now - Duration::minutes(90)
+        );
+        assert_eq!(
+            parse_since("2026-10-01T08:00:00Z", now).unwrap(),
+            Utc.with_ymd_and_hms(2026, 10, 1, 8, 0, 0).unwrap()
+        );
+        let midnight = Local
+            .with_ymd_and_hms(2026, 10, 1, 0, 0, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(parse_since("2026-10-01", now).unwrap(), midnight);
+        assert_eq!(parse_since("2026-10-01 00:00", now).unwrap(), midnight);
+
+        for bad in ["yesterday", "3y", "2026-13-01", ""] {
+            assert!(parse_since(bad, now).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn package_filter_keeps_only_that_packages_files() {
+        let mut record = HistoryRecord::new(Action::Sync, None);
+        record
+            .errors
+            .push("bash/.bashrc: undefined variable".to_string());
+        for (package, target) in [("git", ".gitconfig"), ("bash", ".bashrc")] {
+            record.files.push(FileChange {
+                package: package.to_string(),
+                target: target.to_string(),
+                change: Change::Updated,
+            });
+        }
+
+        let git = filter(&record, Some("git")).unwrap();
+        assert_eq!(git.files.len(), 1);
+        assert_eq!(git.files[0].target, ".gitconfig");
+        assert!(git.errors.is_empty());
+        assert!(filter(&record, Some("vim")).is_none());
+        assert_eq!(filter(&record, None).unwrap(), record);
+    }
+}
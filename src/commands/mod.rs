@@ -1,4 +1,52 @@
 pub mod diff;
+pub mod doctor;
+pub mod explain;
+pub mod export;
+pub mod generations;
 pub mod init;
+pub mod lint;
+pub mod log;
+pub(crate) mod plan;
+pub mod relocate;
+pub mod render;
+pub mod rollback;
 pub mod status;
 pub mod sync;
+pub mod watch;
+
+/// Output format for commands that report per-file records (`status`, `diff`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text.
+    #[default]
+    Text,
+    /// A single JSON document with one record per file.
+    Json,
+    /// One tab-separated line per file, stable for scripts and prompts.
+    Porcelain,
+}
+
+/// When to emit ANSI colors in human-readable output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Color when stdout is a terminal and `NO_COLOR` is unset.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Resolve this choice against the current stdout.
+    pub fn enabled(self) -> bool {
+        use std::io::IsTerminal;
+
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal()
+            }
+        }
+    }
+}
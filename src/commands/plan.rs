@@ -0,0 +1,187 @@
+//! Discovery across all packages, shared by commands that preview or apply a sync.
+
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use rayon::prelude::*;
+
+use crate::config::manifest::{PackageEntry, SystemManifest};
+use crate::deploy::collision::{self, CollisionReport, PackageFiles};
+use crate::deploy::lock::LockFile;
+use crate::deploy::symlink;
+use crate::discovery::walker::{self, DiscoveredFile};
+
+/// What a sync would do with one package.
+pub(crate) struct PackagePlan<'a> {
+    pub name: &'a str,
+    pub entry: &'a PackageEntry,
+    pub dir: PathBuf,
+    pub state: PlanState,
+}
+
+pub(crate) enum PlanState {
+    /// The package directory does not exist.
+    Missing,
+    /// The package's `tags` requirement is not met; its files are removed.
+    TagsUnmet,
+    /// Files to deploy, with higher-priority overrides already removed.
+    Ready { files: Vec<DiscoveredFile> },
+}
+
+impl PackagePlan<'_> {
+    pub fn files(&self) -> &[DiscoveredFile] {
+        match &self.state {
+            PlanState::Ready { files } => files,
+            _ => &[],
+        }
+    }
+}
+
+/// Plans for the selected packages, sorted by name, plus every target path that
+/// any manifest package will deploy.
+pub(crate) struct Plan<'a> {
+    pub packages: Vec<PackagePlan<'a>>,
+    pub claimed_targets: HashSet<PathBuf>,
+    pub collisions: CollisionReport,
+}
+
+impl Plan<'_> {
+    /// State dirs other than `resolved_dir`'s that targets untracked in `lock`
+    /// still link into: what a moved stow dir's targets look like.
+    pub fn old_state_dirs(
+        &self,
+        target_dir: &Path,
+        resolved_dir: &Path,
+        lock: &LockFile,
+    ) -> BTreeSet<PathBuf> {
+        let mut dirs = BTreeSet::new();
+        for pkg in &self.packages {
+            for file in pkg.files() {
+                let source = format!("{}/{}", pkg.name, file.relative_path.display());
+                if lock.find(pkg.name, &source).is_none() {
+                    dirs.extend(symlink::old_state_dir(
+                        &target_dir.join(&file.target_path),
+                        &resolved_dir.join(&source),
+                        &source,
+                    ));
+                }
+            }
+        }
+        dirs
+    }
+}
+
+/// Discover files for every package in the manifest and check them for target
+/// collisions before anything is written.
+///
+/// All packages are discovered so that a filtered sync still detects collisions
+/// with the others, so a package that fails discovery fails every plan.
+pub(crate) fn plan<'a>(
+    stow_dir: &Path,
+    manifest: &'a SystemManifest,
+    active_tags: &HashSet<String>,
+    package_filter: Option<&str>,
+) -> Result<Plan<'a>> {
+    if let Some(name) = package_filter
+        && !manifest.packages.contains_key(name)
+    {
+        anyhow::bail!("Package '{name}' not found in towboat.toml");
+    }
+
+    let mut names: Vec<&String> = manifest.packages.keys().collect();
+    names.sort();
+
+    // Packages are discovered in parallel; results keep name order, so the
+    // first failing package (by name) is the one reported.
+    let discovered: Vec<Result<(bool, PackagePlan<'a>)>> = names
+        .par_iter()
+        .map(|name| {
+            let name: &'a str = name.as_str();
+            let entry = &manifest.packages[name];
+            let selected = package_filter.is_none_or(|f| f == name);
+            let dir = stow_dir.join(name);
+
+            let state = if !dir.exists() {
+                PlanState::Missing
+            } else if !entry.tags.is_empty() && !entry.tags.iter().all(|t| active_tags.contains(t))
+            {
+                PlanState::TagsUnmet
+            } else {
+                let discovered = crate::config::resolve_package_config(name, &dir, entry)
+                    .and_then(|config| Ok(walker::discover_package(&dir, &config, active_tags)?));
+                match discovered {
+                    Ok(files) => PlanState::Ready { files },
+                    Err(e) if selected => return Err(e),
+                    // Its targets are unknown, so a filtered sync could link over them
+                    Err(e) => {
+                        return Err(e.context(format!(
+                            "Package '{name}' can't be discovered, so targets can't be checked for collisions with it"
+                        )));
+                    }
+                }
+            };
+
+            Ok((
+                selected,
+                PackagePlan {
+                    name,
+                    entry,
+                    dir,
+                    state,
+                },
+            ))
+        })
+        .collect();
+    let all = discovered.into_iter().collect::<Result<Vec<_>>>()?;
+
+    let package_files: Vec<PackageFiles> = all
+        .iter()
+        .map(|(_, p)| PackageFiles {
+            package: p.name.to_string(),
+            priority: p.entry.priority,
+            files: p.files().to_vec(),
+        })
+        .collect();
+    let collisions = collision::detect(&package_files);
+
+    if !collisions.collisions.is_empty() {
+        anyhow::bail!("{}", format_collisions(&collisions));
+    }
+
+    let mut claimed_targets = HashSet::new();
+    let mut packages = Vec::new();
+    for (selected, mut pkg) in all {
+        if let PlanState::Ready { files } = &mut pkg.state {
+            files.retain(|f| !collisions.is_overridden(pkg.name, &f.relative_path));
+            claimed_targets.extend(files.iter().map(|f| f.target_path.clone()));
+        }
+        if selected {
+            packages.push(pkg);
+        }
+    }
+
+    Ok(Plan {
+        packages,
+        claimed_targets,
+        collisions,
+    })
+}
+
+fn format_collisions(report: &CollisionReport) -> String {
+    let mut out = String::new();
+    for collision in &report.collisions {
+        let _ = writeln!(out, "Target collision: {}", collision.target.display());
+        for claim in &collision.claims {
+            let _ = writeln!(out, "  {claim}");
+        }
+    }
+    let _ = write!(
+        out,
+        "{} target collision(s) detected; nothing was written. \
+         Remove or retarget one of the files, or set `priority` on a package to pick a winner.",
+        report.collisions.len()
+    );
+    out
+}
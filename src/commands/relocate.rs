@@ -0,0 +1,178 @@
+//! `towboat relocate` (alias `repair`) — re-point every lock-tracked link at
+//! this stow dir's state dir.
+//!
+//! After the state dir moves — with the stow dir, through a changed
+//! `[system] state_dir`, or by migrating a legacy `.towboat/` — links still
+//! lead to `<old>/resolved/...`. Relocate rewrites them without re-resolving
+//! anything, and also recreates missing links and converts links to the
+//! configured `relative_links` style.
+//!
+//! The default state dir is named after the stow dir's path, so after moving
+//! the stow dir, `--from <old path>` brings its state along. `status` and
+//! `sync` spot links leading into such a state dir and suggest it.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+
+use crate::config::manifest::SystemManifest;
+use crate::deploy::history::{Action, Change, FileChange, History, HistoryRecord};
+use crate::deploy::lock::LockFile;
+use crate::deploy::state::{self, StateDir};
+use crate::deploy::symlink::{self, LinkStyle};
+
+#[derive(Debug, Clone, Default)]
+pub struct RelocateOptions {
+    /// Show what would be re-pointed without changing anything.
+    pub dry_run: bool,
+    /// The stow dir's previous location, whose default state dir to take over.
+    pub from: Option<PathBuf>,
+}
+
+pub fn run(stow_dir: &Path, target_dir: &Path, options: &RelocateOptions) -> Result<()> {
+    let manifest = SystemManifest::load(&stow_dir.join("towboat.toml"))
+        .context("Failed to load towboat.toml")?;
+    let style = LinkStyle::of(&manifest.system);
+    let mut state = StateDir::new(stow_dir, &manifest.system)?;
+    if let Some(from) = &options.from {
+        let old = state::default_root(from)?;
+        if !old.is_dir() {
+            bail!(
+                "No state recorded for {} (looked in {})",
+                from.display(),
+                old.display()
+            );
+        }
+        if old != state.destination() {
+            if state.destination().exists() {
+                bail!(
+                    "{} already holds state; remove it to take over {}",
+                    state.destination().display(),
+                    old.display()
+                );
+            }
+            state.adopt(old);
+        }
+    }
+
+    let lock = LockFile::load(&state.lock_path())?;
+    if options.dry_run {
+        if state.root() != state.destination() {
+            println!(
+                "Would move {} to {}",
+                state.root().display(),
+                state.destination().display()
+            );
+        }
+    } else if let Some(from) = state
+        .migrate()
+        .context("Failed to move the state directory")?
+    {
+        println!(
+            "Moved {} to {}",
+            from.display(),
+            state.destination().display()
+        );
+    }
+    if !options.dry_run {
+        state
+            .record_stow_dir(stow_dir)
+            .context("Failed to record the stow dir")?;
+    }
+    // Links go to the destination; until a dry run moves nothing, files are still here
+    let resolved_dir = state.destination().join("resolved");
+    let present_dir = state.resolved_dir();
+
+    let mut files = Vec::new();
+    let mut old_dirs: BTreeSet<PathBuf> = BTreeSet::new();
+    let mut unresolved = Vec::new();
+    let mut skipped = Vec::new();
+    for entry in lock.entries() {
+        let link_path = target_dir.join(&entry.target);
+        let resolved_path = resolved_dir.join(&entry.source);
+        if symlink::link_is_current(&link_path, &resolved_path, style) {
+            continue;
+        }
+        if !present_dir.join(&entry.source).exists() {
+            unresolved.push(entry.target.clone());
+            continue;
+        }
+
+        // Missing, into an old state dir, or here in the other style; a dangling
+        // link leading anywhere else isn't ours to replace
+        let old = symlink::old_state_dir(&link_path, &resolved_path, &entry.source);
+        let ours = link_path.symlink_metadata().is_err()
+            || old.is_some()
+            || symlink::symlink_matches(&link_path, &resolved_path);
+        if !ours {
+            skipped.push(entry.target.clone());
+            continue;
+        }
+        old_dirs.extend(old);
+
+        if options.dry_run {
+            println!("Would re-point {}", link_path.display());
+        } else {
+            symlink::create_link(&resolved_path, &link_path, true, style)?;
+        }
+        files.push(FileChange {
+            package: entry.package.clone(),
+            target: entry.target.clone(),
+            change: Change::Updated,
+        });
+    }
+
+    if !options.dry_run && !files.is_empty() {
+        let mut record = HistoryRecord::new(Action::Relocate, Some(&manifest));
+        record.files = files.clone();
+        History::new(state.root())
+            .append(&record)
+            .context("Failed to record history")?;
+    }
+
+    let prefix = if options.dry_run {
+        "Would re-point"
+    } else {
+        "Re-pointed"
+    };
+    if files.is_empty() {
+        println!(
+            "All {} link(s) already point into {}",
+            lock.entries().len() - unresolved.len() - skipped.len(),
+            state.destination().display()
+        );
+    } else {
+        println!(
+            "{prefix} {} link(s) into {}",
+            files.len(),
+            state.destination().display()
+        );
+    }
+    for old in &old_dirs {
+        println!("  previously {}", old.display());
+    }
+    for target in &unresolved {
+        eprintln!("{target}: resolved file missing; run `towboat sync`");
+    }
+    for target in &skipped {
+        eprintln!("Skipped {target}: target exists and isn't a towboat link");
+    }
+    Ok(())
+}
+
+/// What to suggest when targets still link into `old_state`, another state dir
+/// than this stow dir's, as they do right after the stow dir moved.
+pub(crate) fn suggestion(old_state: &Path) -> String {
+    match state::recorded_stow_dir(old_state) {
+        Some(old) => format!(
+            "links lead into the state of {}; if this stow dir moved from there, run `towboat relocate --from {}`",
+            old.display(),
+            old.display()
+        ),
+        None => format!(
+            "links lead into {}; if this stow dir moved, run `towboat relocate --from <old dir>`",
+            old_state.display()
+        ),
+    }
+}
@@ -0,0 +1,31 @@
+//! `towboat render` — print a file's resolved content without touching the state dir.
+
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::commands::explain;
+use crate::config::manifest::SystemManifest;
+use crate::config::overrides::Overrides;
+use crate::resolve::resolver::resolve_file;
+
+pub fn run(stow_dir: &Path, path: &Path, overrides: &Overrides) -> Result<()> {
+    let manifest_path = stow_dir.join("towboat.toml");
+    let mut manifest =
+        SystemManifest::load(&manifest_path).context("Failed to load towboat.toml")?;
+    overrides.apply(&mut manifest)?;
+
+    let Some(source) = explain::locate_source(stow_dir, path) else {
+        anyhow::bail!(
+            "{} is not a file inside the stow directory {}",
+            path.display(),
+            stow_dir.display()
+        );
+    };
+    let (content, _had_tags) = resolve_file(&source, &manifest.active_tags(), &manifest.variables)
+        .with_context(|| format!("Failed to resolve {}", source.display()))?;
+
+    std::io::stdout().write_all(content.as_bytes())?;
+    Ok(())
+}
@@ -0,0 +1,164 @@
+//! `towboat rollback` — restore the resolved tree, symlinks and lock of an
+//! earlier generation.
+//!
+//! Sources are left alone, so the next `towboat sync` re-deploys whatever they
+//! currently say; roll back to get a working shell, then fix the source.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::config::manifest::SystemManifest;
+use crate::deploy::generations::Generations;
+use crate::deploy::history::{Action, Change, FileChange, History, HistoryRecord};
+use crate::deploy::lock::{LockEntry, LockFile};
+use crate::deploy::state::StateDir;
+use crate::deploy::symlink::{self, LinkStyle};
+
+/// Roll back to `generation`, or to the one before the current generation.
+pub fn run(stow_dir: &Path, target_dir: &Path, generation: Option<u32>) -> Result<()> {
+    // Rolling back must work even when the manifest is what broke
+    let manifest = SystemManifest::load(&stow_dir.join("towboat.toml")).ok();
+    let style = manifest
+        .as_ref()
+        .map_or(LinkStyle::default(), |m| LinkStyle::of(&m.system));
+    let mut state = match &manifest {
+        Some(manifest) => StateDir::new(stow_dir, &manifest.system)?,
+        None => StateDir::for_stow(stow_dir)?,
+    };
+    if let Some(from) = state
+        .migrate()
+        .context("Failed to move .towboat/ to the state directory")?
+    {
+        println!("Moved {} to {}", from.display(), state.root().display());
+    }
+    let resolved_dir = state.resolved_dir();
+    let lock_path = state.lock_path();
+    let store = Generations::new(state.root());
+
+    let number = match generation {
+        Some(number) => number,
+        None => {
+            let current = store.current().unwrap_or(u32::MAX);
+            store
+                .list()?
+                .iter()
+                .map(|g| g.number)
+                .filter(|&n| n < current)
+                .max()
+                .context("No earlier generation to roll back to (see `towboat generations`)")?
+        }
+    };
+    let target = store.load(number)?;
+    let mut restored_lock = store.load_lock(number)?;
+    let lock = LockFile::load(&lock_path).unwrap_or_default();
+
+    // Files whose resolved copy was missing when the generation was taken have
+    // no contents to restore; keeping their entries would claim a stale hash
+    let mut unrecorded = Vec::new();
+    restored_lock.retain(|entry| {
+        let recorded = target.files.contains_key(&entry.source);
+        if !recorded {
+            unrecorded.push(entry.target.clone());
+        }
+        recorded
+    });
+
+    let mut files = Vec::new();
+    let mut change = |entry: &LockEntry, change| {
+        files.push(FileChange {
+            package: entry.package.clone(),
+            target: entry.target.clone(),
+            change,
+        });
+    };
+
+    // Unlink files the generation doesn't have
+    let mut removed = 0;
+    for entry in lock.entries() {
+        if restored_lock.find(&entry.package, &entry.source).is_some() {
+            continue;
+        }
+        let link_path = target_dir.join(&entry.target);
+        let resolved_path = resolved_dir.join(&entry.source);
+        if symlink::symlink_matches(&link_path, &resolved_path) {
+            symlink::remove_symlink(&link_path)?;
+        }
+        if resolved_path.exists() {
+            std::fs::remove_file(&resolved_path)?;
+        }
+        removed += 1;
+        change(entry, Change::Removed);
+    }
+
+    // Restore resolved contents and re-point links
+    let mut restored = 0;
+    let mut skipped = Vec::new();
+    for entry in restored_lock.entries() {
+        let hash = &target.files[&entry.source];
+        let resolved_path = resolved_dir.join(&entry.source);
+        let content = store.read_object(hash)?;
+        let rewrite =
+            std::fs::read_to_string(&resolved_path).ok().as_deref() != Some(content.as_str());
+        if rewrite {
+            if let Some(parent) = resolved_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&resolved_path, content)?;
+            restored += 1;
+        }
+
+        let link_path = target_dir.join(&entry.target);
+        if symlink::symlink_matches(&link_path, &resolved_path) {
+            if rewrite {
+                change(entry, Change::Updated);
+            }
+            continue;
+        }
+        let ours = lock.find_by_target(&entry.target).is_some()
+            || std::fs::read_link(&link_path).is_ok_and(|t| t.starts_with(&resolved_dir));
+        if link_path.symlink_metadata().is_ok() && !ours {
+            skipped.push(entry.target.clone());
+            continue;
+        }
+        symlink::create_link(&resolved_path, &link_path, true, style)?;
+        let kind = if lock.find(&entry.package, &entry.source).is_some() {
+            Change::Updated
+        } else {
+            Change::Added
+        };
+        change(entry, kind);
+    }
+
+    // Scripts ran against the machine, not the tree; their records stay current
+    restored_lock.scripts = lock.scripts.clone();
+    restored_lock.save(&lock_path)?;
+    store.set_current(number)?;
+
+    let mut record = HistoryRecord::new(Action::Rollback, manifest.as_ref());
+    record.generation = Some(number);
+    record.files = files;
+    record.errors = skipped
+        .iter()
+        .map(|target| format!("{target}: target exists and isn't managed by towboat"))
+        .chain(
+            unrecorded
+                .iter()
+                .map(|target| format!("{target}: no contents recorded in generation {number}")),
+        )
+        .collect();
+    History::new(state.root())
+        .append(&record)
+        .context("Failed to record history")?;
+
+    println!("Rolled back to generation {number} ({restored} file(s) restored, {removed} removed)");
+    for target in &skipped {
+        eprintln!("Skipped {target}: target exists and isn't managed by towboat");
+    }
+    for target in &unrecorded {
+        eprintln!(
+            "Skipped {target}: no contents recorded in generation {number}; run `towboat sync`"
+        );
+    }
+    Ok(())
+}
@@ -1,68 +1,370 @@
 //! `towboat status` — show per-file state.
+//!
+//! Tracked files come from the lock; discovery adds files the next sync would
+//! deploy (`new`) and lock entries it would remove (`stale`).
 
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::path::Path;
 
 use anyhow::{Context, Result};
+use serde::Serialize;
 
+use crate::commands::OutputFormat;
+use crate::commands::{plan, relocate};
 use crate::config::manifest::SystemManifest;
-use crate::deploy::lock::{FileState, LockFile};
+use crate::config::scripts::ScriptMode;
+use crate::deploy::lock::{FileStat, FileState, LockEntry, LockFile};
+use crate::deploy::scripts;
+use crate::deploy::state::StateDir;
 use crate::deploy::symlink;
-use crate::resolve::resolver::compute_hash;
+use crate::resolve::Inputs;
+use crate::resolve::resolver::{self, compute_hash};
 
-pub fn run(stow_dir: &Path, target_dir: &Path, package_filter: Option<&str>) -> Result<()> {
+/// Per-file status record, as emitted by `--format json` and `--format porcelain`.
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusRecord {
+    pub package: String,
+    /// Source path relative to the stow directory.
+    pub source: String,
+    /// Target path relative to the target directory.
+    pub target: String,
+    pub state: FileState,
+    /// Source hash recorded in the lock at last sync (`None` for files never synced).
+    pub locked_source_hash: Option<String>,
+    /// Resolved hash recorded in the lock at last sync (`None` for files never synced).
+    pub locked_resolved_hash: Option<String>,
+    /// Current source hash (`None` if the source is missing or unreadable).
+    pub source_hash: Option<String>,
+    /// Current resolved hash (`None` if the resolved file is missing or unreadable).
+    pub resolved_hash: Option<String>,
+    /// Tag and variable changes that make the file need re-resolving.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub reasons: Vec<String>,
+}
+
+/// A run-once / run-on-change script that would run on the next sync.
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingScript {
+    /// Script path relative to the stow directory.
+    pub path: String,
+    pub mode: ScriptMode,
+    /// Why the script won't resolve; the next sync fails on it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Options for `towboat status`.
+#[derive(Debug, Clone, Default)]
+pub struct StatusOptions {
+    pub format: OutputFormat,
+    /// Print nothing; only the returned [`Health`] matters.
+    pub quiet: bool,
+    /// Hash every file instead of trusting unchanged size, mtime and inode.
+    pub paranoid: bool,
+}
+
+/// Overall health for `status --check`, ordered from best to worst.
+///
+/// The worst state of any file decides the exit code. Codes start at 10 so
+/// they never collide with `1` (towboat failed) or `2` (usage error).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Health {
+    /// Everything is up to date.
+    Clean,
+    /// A sync would change something: new, stale or source-changed files, or pending scripts.
+    Pending,
+    /// A resolved file was edited by hand.
+    Drifted,
+    /// Both sides changed, or a target is occupied by a foreign file.
+    Conflict,
+    /// A symlink is missing or dangling.
+    Broken,
+}
+
+impl Health {
+    pub fn of(state: &FileState) -> Health {
+        match state {
+            FileState::UpToDate => Health::Clean,
+            FileState::SourceChanged | FileState::Stale | FileState::New => Health::Pending,
+            FileState::Drifted => Health::Drifted,
+            FileState::Conflict | FileState::Foreign => Health::Conflict,
+            FileState::Broken | FileState::Moved => Health::Broken,
+        }
+    }
+
+    /// Process exit code for `status --check`.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Health::Clean => 0,
+            Health::Pending => 10,
+            Health::Drifted => 11,
+            Health::Conflict => 12,
+            Health::Broken => 13,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct StatusReport<'a> {
+    files: &'a [StatusRecord],
+    /// Number of files in each state, keyed like `state`.
+    summary: BTreeMap<&'static str, usize>,
+    pending_scripts: &'a [PendingScript],
+}
+
+pub fn run(
+    stow_dir: &Path,
+    target_dir: &Path,
+    package_filter: Option<&str>,
+    options: &StatusOptions,
+) -> Result<Health> {
     let manifest_path = stow_dir.join("towboat.toml");
     let manifest = SystemManifest::load(&manifest_path).context("Failed to load towboat.toml")?;
 
-    let towboat_dir = stow_dir.join(".towboat");
-    let resolved_dir = towboat_dir.join("resolved");
-    let lock_path = towboat_dir.join("towboat.lock");
-    let lock = LockFile::load(&lock_path).unwrap_or_default();
+    let state = StateDir::new(stow_dir, &manifest.system)?;
+    let resolved_dir = state.resolved_dir();
+    let lock = LockFile::load(&state.lock_path()).unwrap_or_default();
+
+    // Scripts only run on full syncs, so only report them without a package filter
+    let pending_scripts = if package_filter.is_none() {
+        pending_scripts(stow_dir, &manifest, &lock)
+    } else {
+        Vec::new()
+    };
+
+    let active_tags = manifest.active_tags();
+    let plan = plan::plan(stow_dir, &manifest, &active_tags, package_filter)?;
+    let packages: Vec<&str> = plan.packages.iter().map(|p| p.name).collect();
+
+    let inspector = Inspector {
+        lock: &lock,
+        active_tags: &active_tags,
+        variables: &manifest.variables,
+        paranoid: options.paranoid,
+    };
+    let mut records = Vec::new();
+    let mut old_state_dirs = BTreeSet::new();
+    for pkg in &plan.packages {
+        let pkg_name = pkg.name;
+        let mut deployed: HashSet<String> = HashSet::new();
+
+        for file in pkg.files() {
+            let source = format!("{pkg_name}/{}", file.relative_path.display());
+            let resolved_path = resolved_dir.join(pkg_name).join(&file.relative_path);
+            let link_path = target_dir.join(&file.target_path);
+
+            let record = match lock.find(pkg_name, &source) {
+                Some(entry) => {
+                    inspector.inspect(entry, &file.source_path, &resolved_path, &link_path)
+                }
+                None => {
+                    let target = file.target_path.to_string_lossy();
+                    // Targets deployed from another variant or package are ours to replace
+                    let foreign = link_path.symlink_metadata().is_ok()
+                        && !symlink::symlink_matches(&link_path, &resolved_path)
+                        && lock.find_by_target(&target).is_none();
+                    let old = symlink::old_state_dir(&link_path, &resolved_path, &source);
+                    let state = if let Some(old) = old {
+                        // Deployed from a state dir this stow dir may have moved away from
+                        old_state_dirs.insert(old);
+                        FileState::Moved
+                    } else if foreign {
+                        FileState::Foreign
+                    } else {
+                        FileState::New
+                    };
+                    untracked_record(pkg_name, source.clone(), &file.target_path, state)
+                }
+            };
+            deployed.insert(source);
+            records.push(record);
+        }
 
-    if lock.files.is_empty() {
-        println!("No files tracked. Run `towboat sync` first.");
-        return Ok(());
+        // Lock entries the next sync would remove: the source is gone, the
+        // package's tags are unmet, or its config no longer matches the file
+        for entry in lock.entries_for_package(pkg_name) {
+            if !deployed.contains(&entry.source) {
+                records.push(stale_record(entry));
+            }
+        }
     }
 
-    let packages: Vec<&str> = if let Some(name) = package_filter {
-        if !manifest.packages.contains_key(name) {
-            anyhow::bail!("Package '{name}' not found in towboat.toml");
+    // Packages in lock but not in manifest
+    let mut removed = Vec::new();
+    if package_filter.is_none() {
+        for entry in lock.entries() {
+            if !manifest.packages.contains_key(&entry.package) {
+                removed.push(stale_record(entry));
+            }
+        }
+    }
+
+    let health = records
+        .iter()
+        .chain(&removed)
+        .map(|r| Health::of(&r.state))
+        .chain((!pending_scripts.is_empty()).then_some(Health::Pending))
+        .max()
+        .unwrap_or(Health::Clean);
+    if options.quiet {
+        return Ok(health);
+    }
+
+    let summary = summarize(records.iter().chain(&removed));
+    match options.format {
+        OutputFormat::Text => {
+            print_text(&packages, &records, &removed);
+            print_summary(&summary);
+            print_pending_scripts(&pending_scripts);
+            for old in &old_state_dirs {
+                println!("Note: {}", relocate::suggestion(old));
+            }
+        }
+        OutputFormat::Json => {
+            records.extend(removed);
+            let json = serde_json::to_string_pretty(&StatusReport {
+                files: &records,
+                summary,
+                pending_scripts: &pending_scripts,
+            })?;
+            println!("{json}");
+        }
+        OutputFormat::Porcelain => {
+            for record in records.iter().chain(&removed) {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    record.state.as_str(),
+                    record.package,
+                    record.source,
+                    record.target
+                );
+            }
+        }
+    }
+
+    Ok(health)
+}
+
+fn untracked_record(
+    package: &str,
+    source: String,
+    target: &Path,
+    state: FileState,
+) -> StatusRecord {
+    StatusRecord {
+        package: package.to_string(),
+        source,
+        target: target.to_string_lossy().into_owned(),
+        state,
+        locked_source_hash: None,
+        locked_resolved_hash: None,
+        source_hash: None,
+        resolved_hash: None,
+        reasons: Vec::new(),
+    }
+}
+
+fn stale_record(entry: &LockEntry) -> StatusRecord {
+    StatusRecord {
+        package: entry.package.clone(),
+        source: entry.source.clone(),
+        target: entry.target.clone(),
+        state: FileState::Stale,
+        locked_source_hash: Some(entry.source_hash.clone()),
+        locked_resolved_hash: Some(entry.resolved_hash.clone()),
+        source_hash: None,
+        resolved_hash: None,
+        reasons: Vec::new(),
+    }
+}
+
+fn summarize<'a>(records: impl Iterator<Item = &'a StatusRecord>) -> BTreeMap<&'static str, usize> {
+    let mut summary = BTreeMap::new();
+    for record in records {
+        *summary.entry(record.state.as_str()).or_default() += 1;
+    }
+    summary
+}
+
+fn pending_scripts(
+    stow_dir: &Path,
+    manifest: &SystemManifest,
+    lock: &LockFile,
+) -> Vec<PendingScript> {
+    let active_tags = manifest.active_tags();
+    let collected = match scripts::collect(stow_dir, &manifest.scripts) {
+        Ok(collected) => collected,
+        Err(e) => {
+            eprintln!("Warning: cannot list scripts: {e}");
+            return Vec::new();
         }
-        vec![name]
-    } else {
-        manifest.packages.keys().map(|s| s.as_str()).collect()
     };
+    let mut pending = Vec::new();
+    for script in &collected {
+        // A script that won't resolve is reported rather than failing the status
+        let error = match scripts::resolve_one(stow_dir, script, &active_tags, &manifest.variables)
+        {
+            Ok(Some(resolved)) if resolved.is_pending(lock) => None,
+            Ok(_) => continue,
+            Err(e) => Some(e.to_string()),
+        };
+        pending.push(PendingScript {
+            path: script.path.clone(),
+            mode: script.mode,
+            error,
+        });
+    }
+    pending
+}
 
+fn print_pending_scripts(pending: &[PendingScript]) {
+    if pending.is_empty() {
+        return;
+    }
+    println!("Pending scripts:");
+    for script in pending {
+        match &script.error {
+            Some(error) => println!(
+                "  {} ({}): won't resolve: {error}",
+                script.path,
+                script.mode.as_str()
+            ),
+            None => println!("  {} ({})", script.path, script.mode.as_str()),
+        }
+    }
+    println!();
+}
+
+fn print_summary(summary: &BTreeMap<&'static str, usize>) {
+    if summary.is_empty() {
+        return;
+    }
+    let counts: Vec<String> = FileState::ALL
+        .iter()
+        .filter_map(|state| {
+            summary
+                .get(state.as_str())
+                .map(|n| format!("{n} {}", state.label()))
+        })
+        .collect();
+    println!("{}", counts.join(", "));
+    println!();
+}
+
+fn print_text(packages: &[&str], records: &[StatusRecord], removed: &[StatusRecord]) {
     let mut any_output = false;
 
-    for pkg_name in &packages {
-        let entries = lock.entries_for_package(pkg_name);
-        if entries.is_empty() {
+    for pkg_name in packages {
+        let mut pkg_records = records.iter().filter(|r| r.package == *pkg_name).peekable();
+        if pkg_records.peek().is_none() {
             continue;
         }
 
         println!("Package: {pkg_name}");
         any_output = true;
 
-        for entry in entries {
-            let source_path = stow_dir.join(&entry.source);
-            let resolved_path = resolved_dir.join(
-                entry
-                    .source
-                    .strip_prefix(&format!("{pkg_name}/"))
-                    .map(|s| format!("{pkg_name}/{s}"))
-                    .unwrap_or_else(|| entry.source.clone()),
-            );
-            let link_path = target_dir.join(&entry.target);
-
-            let state = determine_state(
-                &source_path,
-                &resolved_path,
-                &link_path,
-                &entry.source_hash,
-                &entry.resolved_hash,
-            );
-
-            let icon = match &state {
+        for record in pkg_records {
+            let icon = match &record.state {
                 FileState::UpToDate => "  ",
                 FileState::SourceChanged => "M ",
                 FileState::Drifted => " D",
@@ -70,56 +372,148 @@ pub fn run(stow_dir: &Path, target_dir: &Path, package_filter: Option<&str>) ->
                 FileState::Broken => "! ",
                 FileState::Stale => "S ",
                 FileState::New => "N ",
+                FileState::Foreign => "F ",
+                FileState::Moved => "R ",
             };
 
-            let label = match &state {
+            let label = match &record.state {
                 FileState::UpToDate => "up to date",
                 FileState::SourceChanged => "source changed",
                 FileState::Drifted => "drifted (resolved file edited)",
                 FileState::Conflict => "CONFLICT (both changed)",
                 FileState::Broken => "broken symlink",
-                FileState::Stale => "stale (removed from config)",
+                FileState::Stale => "stale (no longer deployed)",
                 FileState::New => "new",
+                FileState::Foreign => "foreign (target exists and isn't ours)",
+                FileState::Moved => "moved (points into an old state dir; run `towboat relocate`)",
             };
 
-            println!("  {icon} {}: {label}", entry.target);
+            if record.reasons.is_empty() {
+                println!("  {icon} {}: {label}", record.target);
+            } else {
+                println!(
+                    "  {icon} {}: {label} ({})",
+                    record.target,
+                    record.reasons.join(", ")
+                );
+            }
         }
         println!();
     }
 
-    // Check for packages in lock but not in manifest
-    let manifest_packages: std::collections::HashSet<&str> = packages.into_iter().collect();
-    let lock_packages: std::collections::HashSet<String> =
-        lock.files.iter().map(|e| e.package.clone()).collect();
+    let mut removed_packages: Vec<&str> = removed.iter().map(|r| r.package.as_str()).collect();
+    removed_packages.sort_unstable();
+    removed_packages.dedup();
 
-    for pkg in &lock_packages {
-        if !manifest_packages.contains(pkg.as_str()) && package_filter.is_none() {
-            println!("Package: {pkg} (removed from manifest)");
-            for entry in lock.entries_for_package(pkg) {
-                println!("  S  {}: stale", entry.target);
-            }
-            println!();
-            any_output = true;
+    for pkg in removed_packages {
+        println!("Package: {pkg} (removed from manifest)");
+        for record in removed.iter().filter(|r| r.package == pkg) {
+            println!("  S  {}: stale", record.target);
         }
+        println!();
+        any_output = true;
     }
 
     if !any_output {
-        println!("No tracked files for the specified package(s).");
+        println!("No files to deploy for the specified package(s).");
+    }
+}
+
+/// Works out the state of tracked files against the current manifest.
+struct Inspector<'a> {
+    lock: &'a LockFile,
+    active_tags: &'a HashSet<String>,
+    variables: &'a HashMap<String, String>,
+    /// Hash every file instead of trusting unchanged stats.
+    paranoid: bool,
+}
+
+impl Inspector<'_> {
+    fn inspect(
+        &self,
+        entry: &LockEntry,
+        source_path: &Path,
+        resolved_path: &Path,
+        link_path: &Path,
+    ) -> StatusRecord {
+        let record = |state, source_hash, resolved_hash, reasons| StatusRecord {
+            package: entry.package.clone(),
+            source: entry.source.clone(),
+            target: entry.target.clone(),
+            state,
+            locked_source_hash: Some(entry.source_hash.clone()),
+            locked_resolved_hash: Some(entry.resolved_hash.clone()),
+            source_hash,
+            resolved_hash,
+            reasons,
+        };
+
+        // Fast path: neither file was touched and no recorded tag or variable changed
+        if !self.paranoid
+            && self.lock.stats_unchanged(
+                entry,
+                FileStat::of(source_path),
+                FileStat::of(resolved_path),
+            )
+            && entry
+                .inputs
+                .as_ref()
+                .is_some_and(|inputs| inputs.holds(self.active_tags, self.variables))
+        {
+            let state =
+                link_state(link_path, resolved_path, &entry.source).unwrap_or(FileState::UpToDate);
+            return record(
+                state,
+                Some(entry.source_hash.clone()),
+                Some(entry.resolved_hash.clone()),
+                Vec::new(),
+            );
+        }
+
+        let source = std::fs::read_to_string(source_path).ok();
+        let source_hash = source.as_deref().map(compute_hash);
+        let inputs = source
+            .as_deref()
+            .and_then(|content| resolver::inputs(content, self.active_tags, self.variables).ok());
+        let resolved_hash = hash_file(resolved_path);
+
+        let state = determine_state(
+            source_path,
+            resolved_path,
+            link_path,
+            source_hash.as_deref(),
+            resolved_hash.as_deref(),
+            inputs.as_ref(),
+            entry,
+        );
+        let reasons = match (&state, &inputs) {
+            (FileState::SourceChanged | FileState::Conflict, Some(inputs)) => {
+                entry.input_changes(inputs)
+            }
+            _ => Vec::new(),
+        };
+
+        record(state, source_hash, resolved_hash, reasons)
     }
+}
 
-    Ok(())
+fn hash_file(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path)
+        .ok()
+        .map(|content| compute_hash(&content))
 }
 
 fn determine_state(
     source_path: &Path,
     resolved_path: &Path,
     link_path: &Path,
-    locked_source_hash: &str,
-    locked_resolved_hash: &str,
+    current_source_hash: Option<&str>,
+    current_resolved_hash: Option<&str>,
+    current_inputs: Option<&Inputs>,
+    entry: &LockEntry,
 ) -> FileState {
-    // Check for broken symlink first
-    if symlink::is_broken_symlink(link_path) {
-        return FileState::Broken;
+    if let Some(state) = link_state(link_path, resolved_path, &entry.source) {
+        return state;
     }
 
     // Check if source still exists
@@ -127,24 +521,26 @@ fn determine_state(
         return FileState::Stale;
     }
 
-    // Get current hashes
-    let current_source_hash = match std::fs::read_to_string(source_path) {
-        Ok(content) => compute_hash(&content),
-        Err(_) => return FileState::Broken,
-    };
-
-    let current_resolved_hash = match std::fs::read_to_string(resolved_path) {
-        Ok(content) => compute_hash(&content),
-        Err(_) => return FileState::Broken,
-    };
-
-    let source_changed = current_source_hash != locked_source_hash;
-    let resolved_changed = current_resolved_hash != locked_resolved_hash;
+    match (current_source_hash, current_resolved_hash) {
+        (Some(source_hash), Some(resolved_hash)) => match current_inputs {
+            Some(inputs) => entry.state_with_inputs(source_hash, resolved_hash, inputs),
+            None => entry.state(source_hash, resolved_hash),
+        },
+        _ => FileState::Broken,
+    }
+}
 
-    match (source_changed, resolved_changed) {
-        (false, false) => FileState::UpToDate,
-        (true, false) => FileState::SourceChanged,
-        (false, true) => FileState::Drifted,
-        (true, true) => FileState::Conflict,
+/// `Moved`, `Broken` or `Foreign` if the target isn't the expected symlink.
+fn link_state(link_path: &Path, resolved_path: &Path, source: &str) -> Option<FileState> {
+    // A link into a moved stow dir needs `relocate`, a missing or dangling one a
+    // re-sync; anything else in its place belongs to someone else
+    if symlink::old_state_dir(link_path, resolved_path, source).is_some() {
+        Some(FileState::Moved)
+    } else if symlink::is_broken_symlink(link_path) || link_path.symlink_metadata().is_err() {
+        Some(FileState::Broken)
+    } else if !symlink::symlink_matches(link_path, resolved_path) {
+        Some(FileState::Foreign)
+    } else {
+        None
     }
 }
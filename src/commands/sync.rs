@@ -1,152 +1,344 @@
 //! `towboat sync` — resolve packages, create/update symlinks, update lock file.
 
-use std::collections::HashSet;
-use std::path::Path;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
 use chrono::Utc;
+use rayon::prelude::*;
 
+use crate::commands::plan::{self, PlanState};
+use crate::commands::relocate;
+use crate::config::hooks::HooksConfig;
 use crate::config::manifest::SystemManifest;
-use crate::deploy::lock::{FileState, LockEntry, LockFile};
-use crate::deploy::symlink;
-use crate::discovery::walker;
-use crate::resolve::resolver::{compute_hash, resolve_file};
+use crate::config::overrides::Overrides;
+use crate::deploy::generations::{self, Generation, Generations};
+use crate::deploy::history::{Action, Change, FileChange, History, HistoryRecord};
+use crate::deploy::hooks::{self, HookContext, HookKind, HookResult};
+use crate::deploy::lock::{FileStat, FileState, LockEntry, LockFile, ScriptEntry};
+use crate::deploy::scripts::{self, Script};
+use crate::deploy::state::StateDir;
+use crate::deploy::symlink::{self, LinkStyle};
+use crate::discovery::walker::DiscoveredFile;
+use crate::resolve::Inputs;
+use crate::resolve::resolver::{self, compute_hash};
+
+/// Options for `towboat sync`.
+#[derive(Debug, Clone, Default)]
+pub struct SyncOptions {
+    /// Show what would be done without making changes.
+    pub dry_run: bool,
+    /// Overwrite existing files and resolve conflicts.
+    pub force: bool,
+    /// Skip package hooks.
+    pub no_hooks: bool,
+    /// Skip run-once / run-on-change scripts.
+    pub no_scripts: bool,
+    /// Simulated tags and variables; only allowed with `dry_run`.
+    pub overrides: Overrides,
+    /// Worker threads for discovery and resolution (0 = one per CPU).
+    pub jobs: usize,
+    /// Hash every file, even when its size, mtime and inode are unchanged.
+    pub paranoid: bool,
+    /// Don't record a generation for this sync.
+    pub no_generation: bool,
+}
 
 pub fn run(
     stow_dir: &Path,
     target_dir: &Path,
     package_filter: Option<&str>,
-    dry_run: bool,
-    force: bool,
+    options: &SyncOptions,
 ) -> Result<()> {
+    let report = execute(stow_dir, target_dir, package_filter, options)?;
+    print_summary(&report, options.dry_run);
+
+    if !report.conflicts.is_empty() && !options.force {
+        anyhow::bail!(
+            "{} conflict(s) detected. Use --force to overwrite.",
+            report.conflicts.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// What one sync did, for callers that report it their own way (`towboat watch`).
+#[derive(Default)]
+pub(crate) struct SyncReport {
+    pub(crate) stats: SyncStats,
+    pub(crate) conflicts: Vec<String>,
+    pub(crate) hooks: Vec<HookResult>,
+    pub(crate) scripts: Vec<(Script, Option<String>)>,
+    /// Files added, updated, removed or left alone because they drifted.
+    pub(crate) files: Vec<FileChange>,
+    /// Files that failed to resolve and packages whose pre_sync hook failed.
+    pub(crate) errors: Vec<String>,
+    /// The generation recorded for this sync, if the resolved tree changed.
+    pub(crate) generation: Option<Generation>,
+    /// A legacy `.towboat/` moved to the state directory (from, to).
+    pub(crate) migrated: Option<(PathBuf, PathBuf)>,
+}
+
+/// Run a sync without printing its summary or failing on conflicts.
+pub(crate) fn execute(
+    stow_dir: &Path,
+    target_dir: &Path,
+    package_filter: Option<&str>,
+    options: &SyncOptions,
+) -> Result<SyncReport> {
+    let SyncOptions {
+        dry_run,
+        force,
+        no_hooks,
+        no_scripts,
+        ref overrides,
+        jobs,
+        paranoid,
+        no_generation,
+    } = *options;
+    if !dry_run && !overrides.is_empty() {
+        anyhow::bail!("--tags, --set and --as-host only simulate a sync; add --dry-run");
+    }
+
     let manifest_path = stow_dir.join("towboat.toml");
-    let manifest = SystemManifest::load(&manifest_path)
+    let mut manifest = SystemManifest::load(&manifest_path)
         .context("Failed to load towboat.toml — run `towboat init` to create one")?;
+    overrides.apply(&mut manifest)?;
 
     let active_tags = manifest.active_tags();
-    let towboat_dir = stow_dir.join(".towboat");
-    let resolved_dir = towboat_dir.join("resolved");
-    let lock_path = towboat_dir.join("towboat.lock");
+    let link_style = LinkStyle::of(&manifest.system);
+    let mut state = StateDir::new(stow_dir, &manifest.system)?;
+    let migrated = if dry_run {
+        None
+    } else {
+        state
+            .migrate()
+            .context("Failed to move .towboat/ to the state directory")?
+            .map(|from| (from, state.root().to_path_buf()))
+    };
+    let resolved_dir = state.resolved_dir();
+    let lock_path = state.lock_path();
 
     let mut lock = LockFile::load(&lock_path).unwrap_or_default();
     let mut stats = SyncStats::default();
     let mut conflicts = Vec::new();
+    let mut hook_results = Vec::new();
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build()
+        .context("Failed to start worker threads")?;
+
+    // Discover everything and validate targets before any write
+    let plan = pool.install(|| plan::plan(stow_dir, &manifest, &active_tags, package_filter))?;
+    stats.overridden = plan
+        .collisions
+        .overrides
+        .iter()
+        .filter(|o| plan.packages.iter().any(|p| p.name == o.loser.package))
+        .count();
+
+    // Scripts belong to the whole repo, so they only run when syncing all
+    // packages. They run last, but resolve first: a bad one fails the sync
+    // before anything is written
+    let scripts = if package_filter.is_none() && !no_scripts {
+        let collected = scripts::collect(stow_dir, &manifest.scripts)?;
+        scripts::resolve(stow_dir, &collected, &active_tags, &manifest.variables)
+            .context("Failed to resolve scripts")?
+    } else {
+        Vec::new()
+    };
+
+    // Right after the stow dir moved, its targets still link into the old state
+    // dir; replacing them would leave that state behind
+    if !force
+        && let Some(old) = plan
+            .old_state_dirs(target_dir, &resolved_dir, &lock)
+            .first()
+    {
+        anyhow::bail!(
+            "Not syncing: {}, or pass --force to replace them",
+            relocate::suggestion(old)
+        );
+    }
 
-    let packages: Vec<(&str, &crate::config::manifest::PackageEntry)> =
-        if let Some(name) = package_filter {
-            match manifest.packages.get(name) {
-                Some(entry) => vec![(name, entry)],
-                None => anyhow::bail!("Package '{name}' not found in towboat.toml"),
+    for pkg in &plan.packages {
+        let pkg_name = pkg.name;
+        let mut hooks = PackageHooks {
+            context: HookContext {
+                package: pkg_name,
+                package_dir: &pkg.dir,
+                stow_dir,
+                target_dir,
+                changed: &[],
+                unlinked: &[],
+            },
+            config: &pkg.entry.hooks,
+            active_tags: &active_tags,
+            enabled: !no_hooks,
+            dry_run,
+            results: &mut hook_results,
+        };
+        let mut changed = Vec::new();
+        let mut unlinked = Vec::new();
+
+        let discovered = match &pkg.state {
+            PlanState::Missing => {
+                eprintln!(
+                    "Warning: package directory not found: {}",
+                    pkg.dir.display()
+                );
+                continue;
             }
-        } else {
-            manifest
-                .packages
-                .iter()
-                .map(|(k, v)| (k.as_str(), v))
-                .collect()
+            PlanState::TagsUnmet => {
+                // Package-level tag requirements not met
+                cleanup_package(
+                    &mut lock,
+                    pkg_name,
+                    target_dir,
+                    &plan.claimed_targets,
+                    &mut stats,
+                    &mut unlinked,
+                    dry_run,
+                )?;
+                hooks.context.unlinked = &unlinked;
+                if !unlinked.is_empty() {
+                    hooks.run(HookKind::OnUnlink);
+                }
+                files.extend(removed(pkg_name, target_dir, &unlinked));
+                continue;
+            }
+            PlanState::Ready { files } => files,
         };
 
-    for (pkg_name, pkg_entry) in &packages {
-        let pkg_dir = stow_dir.join(pkg_name);
-        if !pkg_dir.exists() {
-            eprintln!(
-                "Warning: package directory not found: {}",
-                pkg_dir.display()
-            );
-            continue;
-        }
-
-        let config = crate::config::resolve_package_config(pkg_name, &pkg_dir, pkg_entry)?;
-
-        // Check package-level tag requirements
-        if !pkg_entry.tags.is_empty() && !pkg_entry.tags.iter().all(|t| active_tags.contains(t)) {
-            cleanup_package(&mut lock, pkg_name, target_dir, &mut stats, dry_run)?;
+        if !hooks.run(HookKind::PreSync) {
+            eprintln!("Skipping package '{pkg_name}': pre_sync hook failed");
+            stats.errors += 1;
+            errors.push(format!("{pkg_name}: pre_sync hook failed"));
             continue;
         }
-
-        // Discover files (without resolving yet)
-        let discovered = walker::discover_package(&pkg_dir, &config, &active_tags)?;
         let mut seen_sources: HashSet<String> = HashSet::new();
 
-        for file in &discovered {
+        // Read, hash and resolve in parallel; symlinks and the lock are updated
+        // below in discovery order, so output doesn't depend on the pool size
+        let preparer = Preparer {
+            lock: &lock,
+            active_tags: &active_tags,
+            variables: &manifest.variables,
+            force,
+            paranoid,
+        };
+        let prepared: Vec<Result<Prepared>> = pool.install(|| {
+            discovered
+                .par_iter()
+                .map(|file| {
+                    let source_relative = format!("{pkg_name}/{}", file.relative_path.display());
+                    preparer.prepare(
+                        file,
+                        lock.find(pkg_name, &source_relative),
+                        &resolved_dir.join(pkg_name).join(&file.relative_path),
+                    )
+                })
+                .collect()
+        });
+
+        for (file, prepared) in discovered.iter().zip(prepared) {
             let source_relative = format!("{pkg_name}/{}", file.relative_path.display());
             seen_sources.insert(source_relative.clone());
 
             let resolved_path = resolved_dir.join(pkg_name).join(&file.relative_path);
             let link_path = target_dir.join(&file.target_path);
 
-            // Compute current source hash
-            let source_content = std::fs::read_to_string(&file.source_path).with_context(|| {
-                format!("Failed to read source: {}", file.source_path.display())
-            })?;
-            let source_hash = compute_hash(&source_content);
-
-            // Check drift BEFORE resolving (using the old resolved file on disk)
-            if let Some(lock_entry) = lock.find(pkg_name, &source_relative) {
-                let old_resolved_hash = if resolved_path.exists() {
-                    let content = std::fs::read_to_string(&resolved_path)?;
-                    compute_hash(&content)
-                } else {
-                    String::new()
-                };
-
-                let state = lock_entry.state(&source_hash, &old_resolved_hash);
-                match state {
-                    FileState::UpToDate => {
-                        // Just ensure symlink is correct
-                        if !symlink::symlink_matches(&link_path, &resolved_path) {
-                            if dry_run {
-                                println!(
-                                    "Would fix symlink: {} -> {}",
-                                    link_path.display(),
-                                    resolved_path.display()
-                                );
-                            } else {
-                                symlink::create_symlink(&resolved_path, &link_path, force)?;
-                            }
-                            stats.symlinks_created += 1;
-                        }
-                        continue;
-                    }
-                    FileState::Conflict if !force => {
-                        conflicts.push(format!(
-                            "{source_relative}: source and resolved both changed since last sync"
-                        ));
-                        continue;
+            let (source_hash, source_stat, inputs, resolved) = match prepared? {
+                Prepared::Unchanged(FileState::UpToDate, verified) => {
+                    if let Some((source_stat, resolved_stat)) = verified
+                        && !dry_run
+                    {
+                        lock.record_stats(pkg_name, &source_relative, source_stat, resolved_stat);
                     }
-                    FileState::Drifted if !force => {
-                        // Source hasn't changed — preserve user's edits
-                        continue;
-                    }
-                    _ => {
-                        // SourceChanged, force, or new — proceed to resolve
+                    // Just ensure symlink is correct
+                    if !symlink::link_is_current(&link_path, &resolved_path, link_style) {
+                        if dry_run {
+                            println!(
+                                "Would fix symlink: {} -> {}",
+                                link_path.display(),
+                                resolved_path.display()
+                            );
+                        } else {
+                            // Ours if it already leads here in the other style, or into
+                            // a state dir this one was moved from
+                            let ours = symlink::symlink_matches(&link_path, &resolved_path)
+                                || symlink::old_state_dir(
+                                    &link_path,
+                                    &resolved_path,
+                                    &source_relative,
+                                )
+                                .is_some();
+                            symlink::create_link(
+                                &resolved_path,
+                                &link_path,
+                                force || ours,
+                                link_style,
+                            )?;
+                        }
+                        stats.symlinks_created += 1;
+                        files.push(file_change(pkg_name, file, Change::Updated));
+                        changed.push(link_path);
                     }
+                    continue;
                 }
-            }
-
-            // Now resolve (tags + templates)
-            let (resolved_content, _had_tags) =
-                match resolve_file(&file.source_path, &active_tags, &manifest.variables) {
-                    Ok(result) => result,
-                    Err(e) => {
-                        eprintln!("Error: {}: {e}", file.relative_path.display());
-                        stats.errors += 1;
-                        continue;
-                    }
-                };
+                Prepared::Unchanged(FileState::Conflict, _) => {
+                    conflicts.push(format!(
+                        "{source_relative}: source and resolved both changed since last sync"
+                    ));
+                    continue;
+                }
+                Prepared::Unchanged(..) => {
+                    // Drifted, source hasn't changed — preserve user's edits
+                    files.push(file_change(pkg_name, file, Change::Drifted));
+                    continue;
+                }
+                Prepared::Resolve {
+                    source_hash,
+                    source_stat,
+                    inputs,
+                    resolved,
+                } => (source_hash, source_stat, inputs, resolved),
+            };
+            let resolved_content = match resolved {
+                Ok(content) => content,
+                Err(e) => {
+                    eprintln!("Error: {}: {e}", file.relative_path.display());
+                    stats.errors += 1;
+                    errors.push(format!("{source_relative}: {e}"));
+                    continue;
+                }
+            };
 
             let resolved_hash = compute_hash(&resolved_content);
+            let previous_hash = lock
+                .find(pkg_name, &source_relative)
+                .map(|e| e.resolved_hash.clone());
+            let link_was_correct = symlink::link_is_current(&link_path, &resolved_path, link_style);
 
             // Write resolved file
-            if let Some(parent) = resolved_path.parent() {
-                std::fs::create_dir_all(parent)?;
+            if !dry_run {
+                if let Some(parent) = resolved_path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&resolved_path, &resolved_content)?;
             }
-            std::fs::write(&resolved_path, &resolved_content)?;
             stats.resolved += 1;
 
             // Create/update symlink
-            // Always force-overwrite our own symlinks (file is tracked in lock or new)
+            // Always force-overwrite our own symlinks (file is tracked in lock or new,
+            // or the target was deployed from another variant or package)
+            let target_str = file.target_path.to_string_lossy();
             let is_our_symlink = lock.find(pkg_name, &source_relative).is_some()
+                || lock.find_by_target(&target_str).is_some()
                 || symlink::symlink_matches(&link_path, &resolved_path);
             let effective_force = force || is_our_symlink;
 
@@ -157,9 +349,18 @@ pub fn run(
                     resolved_path.display()
                 );
             } else {
-                symlink::create_symlink(&resolved_path, &link_path, effective_force)?;
+                symlink::create_link(&resolved_path, &link_path, effective_force, link_style)?;
             }
             stats.symlinks_created += 1;
+            if !link_was_correct || previous_hash.as_deref() != Some(resolved_hash.as_str()) {
+                let change = if previous_hash.is_none() {
+                    Change::Added
+                } else {
+                    Change::Updated
+                };
+                files.push(file_change(pkg_name, file, change));
+                changed.push(link_path);
+            }
 
             // Update lock entry
             if !dry_run {
@@ -170,51 +371,240 @@ pub fn run(
                     resolved_hash,
                     target: file.target_path.to_string_lossy().to_string(),
                     tags_matched: vec![file.matched_expr.clone()],
+                    inputs: Some(inputs),
+                    source_stat,
+                    resolved_stat: FileStat::of(&resolved_path),
                 });
             }
         }
 
         // Clean up stale entries for this package
         if !dry_run {
-            cleanup_stale_entries(
+            unlinked.extend(cleanup_stale_entries(
                 &mut lock,
                 pkg_name,
                 &seen_sources,
                 target_dir,
                 &resolved_dir,
+                &plan.claimed_targets,
                 &mut stats,
-            )?;
+            )?);
+        }
+
+        hooks.context.changed = &changed;
+        hooks.context.unlinked = &unlinked;
+        if !unlinked.is_empty() {
+            hooks.run(HookKind::OnUnlink);
         }
+        hooks.run(HookKind::PostSync);
+        if !changed.is_empty() || !unlinked.is_empty() {
+            hooks.run(HookKind::PostChange);
+        }
+        files.extend(removed(pkg_name, target_dir, &unlinked));
+    }
+
+    // Run pending scripts once the packages are in place
+    let mut script_results = Vec::new();
+    for script in scripts {
+        if !script.is_pending(&lock) {
+            continue;
+        }
+        if dry_run {
+            println!(
+                "Would run script: {} ({})",
+                script.script.path,
+                script.script.mode.as_str()
+            );
+            continue;
+        }
+        let result = scripts::run(&script, &state.scripts_dir(), stow_dir, target_dir);
+        if result.is_ok() {
+            lock.upsert_script(ScriptEntry {
+                path: script.script.path.clone(),
+                mode: script.script.mode,
+                resolved_hash: script.hash.clone(),
+                last_run: Utc::now(),
+            });
+        }
+        script_results.push((script.script, result.err()));
     }
 
     // Clean up packages removed from manifest (only when syncing all)
     if package_filter.is_none() && !dry_run {
-        cleanup_removed_packages(&manifest, &mut lock, target_dir, &mut stats)?;
+        files.extend(cleanup_removed_packages(
+            &manifest,
+            &mut lock,
+            target_dir,
+            &plan.claimed_targets,
+            &mut stats,
+        )?);
     }
 
-    // Save lock file
+    // Save lock file, then snapshot the tree it describes
+    let mut generation = None;
     if !dry_run {
         lock.last_sync = Some(Utc::now());
         lock.save(&lock_path)?;
+        state
+            .record_stow_dir(stow_dir)
+            .context("Failed to record the stow dir")?;
+
+        let keep = manifest
+            .system
+            .keep_generations
+            .unwrap_or(generations::DEFAULT_KEEP);
+        if keep > 0 && !no_generation {
+            generation = Generations::new(state.root())
+                .snapshot(&resolved_dir, &lock, keep)
+                .context("Failed to record generation")?;
+        }
+
+        let mut record = HistoryRecord::new(Action::Sync, Some(&manifest));
+        record.package = package_filter.map(str::to_string);
+        record.generation = generation.as_ref().map(|g| g.number);
+        record.files = files.clone();
+        record.errors = errors.clone();
+        record
+            .errors
+            .extend(conflicts.iter().map(|c| format!("conflict: {c}")));
+        record.errors.extend(hook_results.iter().filter_map(|r| {
+            Some(format!(
+                "{} {} hook failed: {}",
+                r.package,
+                r.kind,
+                r.error.as_ref()?
+            ))
+        }));
+        History::new(state.root())
+            .append(&record)
+            .context("Failed to record history")?;
     }
 
-    print_summary(&stats, &conflicts, dry_run);
+    Ok(SyncReport {
+        stats,
+        conflicts,
+        hooks: hook_results,
+        scripts: script_results,
+        files,
+        errors,
+        generation,
+        migrated,
+    })
+}
 
-    if !conflicts.is_empty() && !force {
-        anyhow::bail!(
-            "{} conflict(s) detected. Use --force to overwrite.",
-            conflicts.len()
-        );
-    }
+/// What to do with one discovered file, worked out before anything is written.
+enum Prepared {
+    /// Leave the file alone: up to date, or drifted / conflicting without `--force`.
+    /// Up-to-date files that had to be hashed carry their fresh source and resolved
+    /// stats, so the next run can skip them.
+    Unchanged(FileState, Option<(FileStat, FileStat)>),
+    /// (Re-)deploy the file with freshly resolved content, or report why it failed.
+    Resolve {
+        source_hash: String,
+        source_stat: Option<FileStat>,
+        inputs: Inputs,
+        resolved: crate::error::Result<String>,
+    },
+}
 
-    Ok(())
+/// Everything [`Preparer::prepare`] needs besides the file itself.
+struct Preparer<'a> {
+    lock: &'a LockFile,
+    active_tags: &'a HashSet<String>,
+    variables: &'a HashMap<String, String>,
+    force: bool,
+    paranoid: bool,
+}
+
+impl Preparer<'_> {
+    /// Read, hash and, if needed, resolve one file. Only reads from disk, so it
+    /// can run on any worker thread.
+    fn prepare(
+        &self,
+        file: &DiscoveredFile,
+        lock_entry: Option<&LockEntry>,
+        resolved_path: &Path,
+    ) -> Result<Prepared> {
+        let Preparer {
+            lock,
+            active_tags,
+            variables,
+            force,
+            paranoid,
+        } = *self;
+
+        // Stat before reading, so a write racing with the read shows up next time
+        let source_stat = FileStat::of(&file.source_path);
+        let resolved_stat = FileStat::of(resolved_path);
+
+        // Fast path: neither file was touched and no recorded tag or variable changed
+        if let Some(lock_entry) = lock_entry
+            && !paranoid
+            && lock.stats_unchanged(lock_entry, source_stat, resolved_stat)
+            && lock_entry
+                .inputs
+                .as_ref()
+                .is_some_and(|inputs| inputs.holds(active_tags, variables))
+        {
+            return Ok(Prepared::Unchanged(FileState::UpToDate, None));
+        }
+
+        let source_content = std::fs::read_to_string(&file.source_path)
+            .with_context(|| format!("Failed to read source: {}", file.source_path.display()))?;
+        let source_hash = compute_hash(&source_content);
+        let inputs = match resolver::inputs(&source_content, active_tags, variables) {
+            Ok(inputs) => inputs,
+            Err(e) => {
+                return Ok(Prepared::Resolve {
+                    source_hash,
+                    source_stat,
+                    inputs: Inputs::default(),
+                    resolved: Err(e),
+                });
+            }
+        };
+
+        // Check drift BEFORE resolving (using the old resolved file on disk)
+        if let Some(lock_entry) = lock_entry {
+            let old_resolved_hash = if resolved_path.exists() {
+                let content = std::fs::read_to_string(resolved_path)?;
+                compute_hash(&content)
+            } else {
+                String::new()
+            };
+
+            let state = lock_entry.state_with_inputs(&source_hash, &old_resolved_hash, &inputs);
+            match state {
+                FileState::UpToDate => {
+                    return Ok(Prepared::Unchanged(state, source_stat.zip(resolved_stat)));
+                }
+                FileState::Conflict | FileState::Drifted if !force => {
+                    return Ok(Prepared::Unchanged(state, None));
+                }
+                // SourceChanged, force, or new — proceed to resolve
+                _ => {}
+            }
+        }
+
+        // Now resolve (tags + templates)
+        let resolved =
+            resolver::resolve_content(&source_content, active_tags, variables).map(|(c, _)| c);
+        Ok(Prepared::Resolve {
+            source_hash,
+            source_stat,
+            inputs,
+            resolved,
+        })
+    }
 }
 
 fn cleanup_package(
     lock: &mut LockFile,
     pkg_name: &str,
     target_dir: &Path,
+    claimed_targets: &HashSet<PathBuf>,
     stats: &mut SyncStats,
+    unlinked: &mut Vec<PathBuf>,
     dry_run: bool,
 ) -> Result<()> {
     if dry_run {
@@ -222,11 +612,12 @@ fn cleanup_package(
     }
     let stale = lock.entries_for_package(pkg_name);
     for entry in &stale {
-        let link_path = target_dir.join(&entry.target);
-        if link_path.is_symlink() || link_path.exists() {
-            symlink::remove_symlink(&link_path)?;
-            stats.stale_removed += 1;
-        }
+        unlinked.extend(remove_stale_link(
+            target_dir,
+            &entry.target,
+            claimed_targets,
+            stats,
+        )?);
     }
     lock.remove_package(pkg_name);
     Ok(())
@@ -238,8 +629,10 @@ fn cleanup_stale_entries(
     seen_sources: &HashSet<String>,
     target_dir: &Path,
     resolved_dir: &Path,
+    claimed_targets: &HashSet<PathBuf>,
     stats: &mut SyncStats,
-) -> Result<()> {
+) -> Result<Vec<PathBuf>> {
+    let mut unlinked = Vec::new();
     let stale_entries: Vec<LockEntry> = lock
         .entries_for_package(pkg_name)
         .into_iter()
@@ -248,11 +641,12 @@ fn cleanup_stale_entries(
         .collect();
 
     for entry in &stale_entries {
-        let link_path = target_dir.join(&entry.target);
-        if link_path.is_symlink() || link_path.exists() {
-            symlink::remove_symlink(&link_path)?;
-            stats.stale_removed += 1;
-        }
+        unlinked.extend(remove_stale_link(
+            target_dir,
+            &entry.target,
+            claimed_targets,
+            stats,
+        )?);
         let resolved_file = resolved_dir.join(pkg_name).join(
             entry
                 .source
@@ -264,47 +658,152 @@ fn cleanup_stale_entries(
         }
     }
 
-    lock.files
-        .retain(|e| e.package != pkg_name || seen_sources.contains(&e.source));
-    Ok(())
+    lock.retain(|e| e.package != pkg_name || seen_sources.contains(&e.source));
+    Ok(unlinked)
 }
 
 fn cleanup_removed_packages(
     manifest: &SystemManifest,
     lock: &mut LockFile,
     target_dir: &Path,
+    claimed_targets: &HashSet<PathBuf>,
     stats: &mut SyncStats,
-) -> Result<()> {
+) -> Result<Vec<FileChange>> {
     let manifest_packages: HashSet<&str> = manifest.packages.keys().map(|s| s.as_str()).collect();
-    let lock_packages: HashSet<String> = lock.files.iter().map(|e| e.package.clone()).collect();
+    let lock_packages: BTreeSet<String> =
+        lock.entries().iter().map(|e| e.package.clone()).collect();
 
+    let mut files = Vec::new();
     for pkg in &lock_packages {
         if !manifest_packages.contains(pkg.as_str()) {
             let stale = lock.entries_for_package(pkg);
+            let mut unlinked = Vec::new();
             for entry in &stale {
-                let link_path = target_dir.join(&entry.target);
-                if link_path.is_symlink() || link_path.exists() {
-                    symlink::remove_symlink(&link_path)?;
-                    stats.stale_removed += 1;
-                }
+                unlinked.extend(remove_stale_link(
+                    target_dir,
+                    &entry.target,
+                    claimed_targets,
+                    stats,
+                )?);
             }
+            files.extend(removed(pkg, target_dir, &unlinked));
             lock.remove_package(pkg);
         }
     }
-    Ok(())
+    Ok(files)
+}
+
+fn file_change(package: &str, file: &DiscoveredFile, change: Change) -> FileChange {
+    FileChange {
+        package: package.to_string(),
+        target: file.target_path.to_string_lossy().to_string(),
+        change,
+    }
+}
+
+/// History entries for links removed from `target_dir`.
+fn removed<'a>(
+    package: &'a str,
+    target_dir: &'a Path,
+    links: &'a [PathBuf],
+) -> impl Iterator<Item = FileChange> + 'a {
+    links.iter().map(move |link| FileChange {
+        package: package.to_string(),
+        target: link
+            .strip_prefix(target_dir)
+            .unwrap_or(link)
+            .to_string_lossy()
+            .to_string(),
+        change: Change::Removed,
+    })
+}
+
+/// Remove the symlink of an entry that is no longer deployed, unless another file
+/// (a different variant, or a higher-priority package) now owns the target.
+/// Returns the removed link path.
+fn remove_stale_link(
+    target_dir: &Path,
+    target: &str,
+    claimed_targets: &HashSet<PathBuf>,
+    stats: &mut SyncStats,
+) -> Result<Option<PathBuf>> {
+    if claimed_targets.contains(Path::new(target)) {
+        return Ok(None);
+    }
+    let link_path = target_dir.join(target);
+    if link_path.is_symlink() || link_path.exists() {
+        symlink::remove_symlink(&link_path)?;
+        stats.stale_removed += 1;
+        return Ok(Some(link_path));
+    }
+    Ok(None)
+}
+
+/// Hooks of the package being synced, collecting results for the summary.
+struct PackageHooks<'a> {
+    context: HookContext<'a>,
+    config: &'a HooksConfig,
+    active_tags: &'a HashSet<String>,
+    enabled: bool,
+    dry_run: bool,
+    results: &'a mut Vec<HookResult>,
+}
+
+impl PackageHooks<'_> {
+    /// Run (or in dry-run mode, list) the hooks of `kind`. Returns `false` if any failed.
+    fn run(&mut self, kind: HookKind) -> bool {
+        if !self.enabled {
+            return true;
+        }
+        if self.dry_run {
+            return match hooks::selected(self.config, kind, self.active_tags) {
+                Ok(selected) => {
+                    for hook in selected {
+                        println!(
+                            "Would run {kind} hook for {}: {}",
+                            self.context.package,
+                            hook.command()
+                        );
+                    }
+                    true
+                }
+                Err(e) => {
+                    eprintln!("Error: {} {kind} hooks: {e}", self.context.package);
+                    false
+                }
+            };
+        }
+        let results = hooks::run_hooks(self.config, kind, &self.context, self.active_tags);
+        let ok = results.iter().all(HookResult::succeeded);
+        self.results.extend(results);
+        ok
+    }
 }
 
 #[derive(Default)]
-struct SyncStats {
-    resolved: usize,
-    symlinks_created: usize,
-    stale_removed: usize,
-    errors: usize,
+pub(crate) struct SyncStats {
+    pub(crate) resolved: usize,
+    pub(crate) symlinks_created: usize,
+    pub(crate) stale_removed: usize,
+    pub(crate) overridden: usize,
+    pub(crate) errors: usize,
 }
 
-fn print_summary(stats: &SyncStats, conflicts: &[String], dry_run: bool) {
+fn print_summary(report: &SyncReport, dry_run: bool) {
+    let SyncReport {
+        stats,
+        conflicts,
+        hooks,
+        scripts,
+        generation,
+        migrated,
+        ..
+    } = report;
     let prefix = if dry_run { "Would: " } else { "" };
 
+    if let Some((from, to)) = migrated {
+        println!("Moved {} to {}", from.display(), to.display());
+    }
     if stats.resolved > 0 {
         println!("{prefix}{} file(s) resolved", stats.resolved);
     }
@@ -317,6 +816,42 @@ fn print_summary(stats: &SyncStats, conflicts: &[String], dry_run: bool) {
     if stats.stale_removed > 0 {
         println!("{prefix}{} stale entry(ies) removed", stats.stale_removed);
     }
+    if stats.overridden > 0 {
+        println!(
+            "{} file(s) skipped: target owned by a higher-priority package",
+            stats.overridden
+        );
+    }
+    if !hooks.is_empty() {
+        println!("Hooks:");
+        for result in hooks {
+            let status = match &result.error {
+                None => "ok".to_string(),
+                Some(error) => format!("FAILED ({error})"),
+            };
+            println!(
+                "  {} {}: {} — {status}",
+                result.package, result.kind, result.command
+            );
+        }
+    }
+    let failed_hooks = hooks.iter().filter(|r| !r.succeeded()).count();
+    if failed_hooks > 0 {
+        eprintln!("{failed_hooks} hook(s) failed");
+    }
+    if !scripts.is_empty() {
+        println!("Scripts:");
+        for (script, error) in scripts {
+            let status = match error {
+                None => "ok".to_string(),
+                Some(error) => format!("FAILED ({error}), will retry on next sync"),
+            };
+            println!("  {} ({}) — {status}", script.path, script.mode.as_str());
+        }
+    }
+    if let Some(generation) = generation {
+        println!("Generation {} recorded", generation.number);
+    }
     if stats.errors > 0 {
         eprintln!("{} error(s) occurred", stats.errors);
     }
@@ -328,6 +863,8 @@ fn print_summary(stats: &SyncStats, conflicts: &[String], dry_run: bool) {
         && stats.stale_removed == 0
         && conflicts.is_empty()
         && stats.errors == 0
+        && hooks.is_empty()
+        && scripts.is_empty()
     {
         println!("Everything up to date.");
     }
@@ -0,0 +1,399 @@
+//! `towboat watch` — re-sync packages as their sources change.
+//!
+//! Watches the stow directory and the state dir's resolved files with
+//! inotify (or the platform equivalent),
+//! batches events until the tree has been quiet for the debounce interval,
+//! then re-syncs only the packages that were touched. A change to
+//! `towboat.toml` re-syncs everything, since tags, variables and the package
+//! list may all have moved; so does one to the stow root's ignore files.
+//!
+//! Edits to resolved files — typically made through a deployed symlink — are
+//! never overwritten: sync already preserves drifted files, and watch reports
+//! them as they appear by comparing against the lock, without running a sync.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::commands::sync::{self, SyncOptions, SyncReport};
+use crate::config::manifest::{PackageEntry, SystemManifest};
+use crate::deploy::history::Change;
+use crate::deploy::lock::LockFile;
+use crate::deploy::state::StateDir;
+use crate::discovery::ignore::{BOATIGNORE, STOW_LOCAL_IGNORE};
+use crate::resolve::resolver::compute_hash;
+
+/// Options for `towboat watch`.
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    /// How long the tree must be quiet before a batch of events is handled.
+    pub debounce: Duration,
+    /// Skip package hooks.
+    pub no_hooks: bool,
+    /// Worker threads for discovery and resolution (0 = one per CPU).
+    pub jobs: usize,
+}
+
+/// What a batch of filesystem events touched.
+#[derive(Debug, Default, PartialEq)]
+struct Changes {
+    /// `towboat.toml` changed.
+    manifest: bool,
+    /// Packages whose sources or `boat.toml` changed.
+    sources: BTreeSet<String>,
+    /// Packages whose resolved files changed.
+    resolved: BTreeSet<String>,
+}
+
+pub fn run(stow_dir: &Path, target_dir: &Path, options: &WatchOptions) -> Result<()> {
+    // inotify reports absolute paths; match them against the same form
+    let stow_dir = stow_dir
+        .canonicalize()
+        .with_context(|| format!("Stow directory not found: {}", stow_dir.display()))?;
+    let mut watch = Session {
+        stow_dir: &stow_dir,
+        target_dir,
+        sync_options: SyncOptions {
+            no_hooks: options.no_hooks,
+            // Scripts belong to a full `towboat sync`, not to edit-and-save loops
+            no_scripts: true,
+            jobs: options.jobs,
+            ..Default::default()
+        },
+        drift: BTreeMap::new(),
+    };
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).context("Failed to start file watcher")?;
+    watcher
+        .watch(&stow_dir, RecursiveMode::Recursive)
+        .with_context(|| format!("Failed to watch {}", stow_dir.display()))?;
+
+    // Start from a synced tree, so later batches only report what they changed.
+    // This also moves a legacy `.towboat/` before its new location is watched.
+    watch.sync(None);
+    // Later syncs follow every save; recording each would push the rollback
+    // points worth keeping out of `keep_generations`
+    watch.sync_options.no_generation = true;
+    let resolved_dir = StateDir::for_stow(&stow_dir)?.resolved_dir();
+    std::fs::create_dir_all(&resolved_dir)
+        .with_context(|| format!("Failed to create {}", resolved_dir.display()))?;
+    let resolved_dir = resolved_dir.canonicalize()?;
+    if !resolved_dir.starts_with(&stow_dir) {
+        watcher
+            .watch(&resolved_dir, RecursiveMode::Recursive)
+            .with_context(|| format!("Failed to watch {}", resolved_dir.display()))?;
+    }
+    println!(
+        "Watching {} for changes (Ctrl-C to stop)",
+        stow_dir.display()
+    );
+
+    while let Some(paths) = next_batch(&rx, options.debounce) {
+        let manifest = match SystemManifest::load(&stow_dir.join("towboat.toml")) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                log("towboat.toml", &format!("error: {e}"));
+                continue;
+            }
+        };
+        let changes = classify(&stow_dir, &resolved_dir, &manifest.packages, &paths);
+        if changes.manifest {
+            watch.sync(None);
+            continue;
+        }
+        for package in &changes.sources {
+            watch.sync(Some(package));
+        }
+        for package in changes.resolved.difference(&changes.sources) {
+            watch.check_drift(package);
+        }
+    }
+    Ok(())
+}
+
+/// State carried between batches.
+struct Session<'a> {
+    stow_dir: &'a Path,
+    target_dir: &'a Path,
+    sync_options: SyncOptions,
+    /// Drifted links already reported, per package, so each is reported once.
+    drift: BTreeMap<String, BTreeSet<PathBuf>>,
+}
+
+impl Session<'_> {
+    /// Re-sync one package (or all) and log what changed.
+    fn sync(&mut self, package: Option<&str>) {
+        let scope = package.unwrap_or("all packages");
+        match sync::execute(self.stow_dir, self.target_dir, package, &self.sync_options) {
+            Ok(report) => {
+                let lines = self.describe(&report);
+                if lines.is_empty() {
+                    log(scope, "up to date");
+                } else {
+                    log(scope, "synced");
+                    for line in lines {
+                        println!("  {line}");
+                    }
+                }
+            }
+            Err(e) => log(scope, &format!("error: {e:#}")),
+        }
+
+        let packages = match package {
+            Some(package) => vec![package.to_string()],
+            None => {
+                let lock = self.lock();
+                let names: BTreeSet<&str> =
+                    lock.entries().iter().map(|e| e.package.as_str()).collect();
+                // Forget packages that no longer have anything deployed
+                self.drift.retain(|name, _| names.contains(name.as_str()));
+                names.into_iter().map(str::to_string).collect()
+            }
+        };
+        for package in packages {
+            self.check_drift(&package);
+        }
+    }
+
+    /// Report resolved files of `package` that were edited since they were deployed.
+    fn check_drift(&mut self, package: &str) {
+        let Ok(state) = StateDir::for_stow(self.stow_dir) else {
+            return;
+        };
+        let lock = LockFile::load(&state.lock_path()).unwrap_or_default();
+        let resolved_dir = state.resolved_dir();
+        let drifted: BTreeSet<PathBuf> = lock
+            .entries_for_package(package)
+            .into_iter()
+            .filter(|entry| {
+                // A missing resolved file is broken, not drifted; `status` reports it
+                std::fs::read_to_string(resolved_dir.join(&entry.source))
+                    .is_ok_and(|content| compute_hash(&content) != entry.resolved_hash)
+            })
+            .map(|entry| PathBuf::from(&entry.target))
+            .collect();
+
+        let known = self.drift.entry(package.to_string()).or_default();
+        for target in drifted.difference(known) {
+            log(
+                package,
+                &format!(
+                    "{} drifted: edited in place, kept (see `towboat diff --drifted`)",
+                    target.display()
+                ),
+            );
+        }
+        *known = drifted;
+    }
+
+    /// One line per changed file, hook run, conflict and error.
+    fn describe(&self, report: &SyncReport) -> Vec<String> {
+        let mut lines = Vec::new();
+        // Drift is reported once, by `check_drift`
+        for file in report.files.iter().filter(|f| f.change != Change::Drifted) {
+            lines.push(format!("{} {}", file.change.as_str(), file.target));
+        }
+        for hook in &report.hooks {
+            let status = match &hook.error {
+                None => "ok".to_string(),
+                Some(error) => format!("FAILED ({error})"),
+            };
+            lines.push(format!(
+                "hook {} {}: {} — {status}",
+                hook.package, hook.kind, hook.command
+            ));
+        }
+        for conflict in &report.conflicts {
+            lines.push(format!("conflict: {conflict}"));
+        }
+        if let Some(generation) = &report.generation {
+            lines.push(format!("generation {} recorded", generation.number));
+        }
+        for error in &report.errors {
+            lines.push(format!("error: {error}"));
+        }
+        lines
+    }
+
+    fn lock(&self) -> LockFile {
+        StateDir::for_stow(self.stow_dir)
+            .ok()
+            .and_then(|state| LockFile::load(&state.lock_path()).ok())
+            .unwrap_or_default()
+    }
+}
+
+fn log(scope: &str, message: &str) {
+    println!("[{}] {scope}: {message}", Local::now().format("%H:%M:%S"));
+}
+
+/// Block for the next event, then collect paths until nothing has arrived
+/// for `debounce`. Returns `None` once the watcher has gone away.
+fn next_batch(
+    rx: &Receiver<notify::Result<notify::Event>>,
+    debounce: Duration,
+) -> Option<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    let mut event = rx.recv().ok()?;
+    loop {
+        match event {
+            // Reads (including our own) don't change anything
+            Ok(event) if matches!(event.kind, EventKind::Access(_)) => {}
+            Ok(event) => paths.extend(event.paths),
+            Err(e) => eprintln!("Warning: file watcher: {e}"),
+        }
+        event = match rx.recv_timeout(debounce) {
+            Ok(event) => event,
+            Err(RecvTimeoutError::Timeout) if paths.is_empty() => rx.recv().ok()?,
+            Err(RecvTimeoutError::Timeout) => return Some(paths),
+            Err(RecvTimeoutError::Disconnected) => return Some(paths),
+        };
+    }
+}
+
+/// Map changed paths to the packages they belong to. Paths outside any
+/// configured package (`.git/`, the lock, scripts) are ignored.
+fn classify(
+    stow_dir: &Path,
+    resolved_dir: &Path,
+    packages: &HashMap<String, PackageEntry>,
+    paths: &[PathBuf],
+) -> Changes {
+    let names = |path: &Path| -> Vec<String> {
+        path.components()
+            .filter_map(|c| match c {
+                Component::Normal(name) => name.to_str().map(str::to_string),
+                _ => None,
+            })
+            .collect()
+    };
+    let mut changes = Changes::default();
+    for path in paths {
+        // The resolved dir may sit inside the stow dir (`state_dir = ".towboat"`)
+        if let Ok(relative) = path.strip_prefix(resolved_dir) {
+            if let Some(package) = names(relative).into_iter().next()
+                && packages.contains_key(&package)
+            {
+                changes.resolved.insert(package);
+            }
+            continue;
+        }
+        let Ok(relative) = path.strip_prefix(stow_dir) else {
+            continue;
+        };
+        let parts = names(relative);
+        let mut components = parts.iter().map(String::as_str);
+        match (components.next(), components.next()) {
+            // Root ignore files apply to every package, like the manifest
+            (Some("towboat.toml" | BOATIGNORE | STOW_LOCAL_IGNORE), None) => {
+                changes.manifest = true;
+            }
+            // The package directory itself, anything under it, and its boat.toml
+            (Some(package), _) if packages.contains_key(package) => {
+                changes.sources.insert(package.to_string());
+            }
+            _ => {}
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packages(names: &[&str]) -> HashMap<String, PackageEntry> {
+        names
+            .iter()
+            .map(|name| (name.to_string(), PackageEntry::default()))
+            .collect()
+    }
+
+    #[test]
+    fn classify_maps_paths_to_packages() {
+        let stow = Path::new("/dots");
+        let paths = [
+            "/dots/bash/.bashrc",
+            "/dots/vim/boat.toml",
+            "/state/resolved/git/.gitconfig",
+            "/state/towboat.lock",
+            "/dots/.git/index",
+            "/dots/unlisted/file",
+            "/elsewhere/bash/.bashrc",
+        ]
+        .map(PathBuf::from);
+
+        let changes = classify(
+            stow,
+            Path::new("/state/resolved"),
+            &packages(&["bash", "vim", "git"]),
+            &paths,
+        );
+        assert_eq!(
+            changes,
+            Changes {
+                manifest: false,
+                sources: BTreeSet::from(["bash".to_string(), "vim".to_string()]),
+                resolved: BTreeSet::from(["git".to_string()]),
+            }
+        );
+    }
+
+    #[test]
+    fn classify_flags_manifest_changes() {
+        let stow = Path::new("/dots");
+        let changes = classify(
+            stow,
+            Path::new("/dots/.towboat/resolved"),
+            &packages(&["bash"]),
+            &[
+                PathBuf::from("/dots/towboat.toml"),
+                PathBuf::from("/dots/.towboat/resolved/bash/.bashrc"),
+            ],
+        );
+        assert!(changes.manifest);
+        assert!(changes.sources.is_empty());
+        assert_eq!(changes.resolved, BTreeSet::from(["bash".to_string()]));
+
+        // Root ignore files apply to every package; a package's own only to it
+        for (path, manifest) in [
+            ("/dots/.boatignore", true),
+            ("/dots/.stow-local-ignore", true),
+            ("/dots/bash/.boatignore", false),
+        ] {
+            let changes = classify(
+                stow,
+                Path::new("/dots/.towboat/resolved"),
+                &packages(&["bash"]),
+                &[PathBuf::from(path)],
+            );
+            assert_eq!(changes.manifest, manifest, "{path}");
+        }
+    }
+
+    #[test]
+    fn next_batch_waits_for_quiet() {
+        let (tx, rx) = mpsc::channel();
+        let event =
+            |path: &str| Ok(notify::Event::new(EventKind::Any).add_path(PathBuf::from(path)));
+        tx.send(event("/a")).unwrap();
+        tx.send(event("/b")).unwrap();
+        tx.send(Ok(notify::Event::new(EventKind::Access(
+            notify::event::AccessKind::Any,
+        ))
+        .add_path(PathBuf::from("/read"))))
+            .unwrap();
+
+        let batch = next_batch(&rx, Duration::from_millis(20)).unwrap();
+        assert_eq!(batch, vec![PathBuf::from("/a"), PathBuf::from("/b")]);
+
+        drop(tx);
+        assert!(next_batch(&rx, Duration::from_millis(20)).is_none());
+    }
+}
@@ -0,0 +1,207 @@
+//! Parsing for per-package hooks (`[packages.<name>.hooks]` in `towboat.toml`).
+//!
+//! Each hook point takes a command string, a `{ run, tags }` table, or a list
+//! of either:
+//!
+//! ```toml
+//! [packages.fonts.hooks]
+//! post_change = "fc-cache -f"
+//! post_sync = [{ run = "systemctl --user daemon-reload", tags = "linux" }]
+//! ```
+
+use std::fmt;
+
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::config::package::TagsSpec;
+
+/// Commands to run around a package's sync.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct HooksConfig {
+    /// Before the package's files are synced. A failure skips the package.
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub pre_sync: Vec<HookCommand>,
+
+    /// After the package's files are synced.
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub post_sync: Vec<HookCommand>,
+
+    /// After the package's files are synced, only if any target changed.
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub post_change: Vec<HookCommand>,
+
+    /// After symlinks of the package were removed.
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub on_unlink: Vec<HookCommand>,
+}
+
+impl HooksConfig {
+    pub fn is_empty(&self) -> bool {
+        self.pre_sync.is_empty()
+            && self.post_sync.is_empty()
+            && self.post_change.is_empty()
+            && self.on_unlink.is_empty()
+    }
+}
+
+/// A single hook command, optionally filtered by a tag expression.
+///
+/// Deserialized by hand rather than as an untagged enum, so a typo inside a
+/// hook table reports the unknown key instead of "did not match any variant".
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum HookCommand {
+    /// `"fc-cache -f"` — always runs.
+    Command(String),
+    /// `{ run = "fc-cache -f", tags = "linux" }` — runs when the tags match.
+    Filtered {
+        run: String,
+        #[serde(default)]
+        tags: TagsSpec,
+    },
+}
+
+impl HookCommand {
+    /// Shell command line.
+    pub fn command(&self) -> &str {
+        match self {
+            HookCommand::Command(run) | HookCommand::Filtered { run, .. } => run,
+        }
+    }
+
+    /// Tag expression filter (empty means "always").
+    pub fn tags_expr(&self) -> String {
+        match self {
+            HookCommand::Command(_) => String::new(),
+            HookCommand::Filtered { tags, .. } => tags.to_expr_string(),
+        }
+    }
+}
+
+/// The table form, `{ run, tags }`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FilteredHook {
+    run: String,
+    #[serde(default)]
+    tags: TagsSpec,
+}
+
+impl From<FilteredHook> for HookCommand {
+    fn from(hook: FilteredHook) -> Self {
+        HookCommand::Filtered {
+            run: hook.run,
+            tags: hook.tags,
+        }
+    }
+}
+
+/// Accepts a command string or a `{ run, tags }` table; with `many`, also a
+/// list of either.
+struct HookVisitor {
+    many: bool,
+}
+
+impl<'de> Visitor<'de> for HookVisitor {
+    type Value = Vec<HookCommand>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.many {
+            f.write_str("a command string, a { run, tags } table, or a list of them")
+        } else {
+            f.write_str("a command string or a { run, tags } table")
+        }
+    }
+
+    fn visit_str<E: de::Error>(self, run: &str) -> std::result::Result<Self::Value, E> {
+        Ok(vec![HookCommand::Command(run.to_string())])
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> std::result::Result<Self::Value, A::Error> {
+        let hook = FilteredHook::deserialize(de::value::MapAccessDeserializer::new(map))?;
+        Ok(vec![hook.into()])
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(
+        self,
+        mut seq: A,
+    ) -> std::result::Result<Self::Value, A::Error> {
+        if !self.many {
+            return Err(de::Error::invalid_type(de::Unexpected::Seq, &self));
+        }
+        let mut hooks = Vec::new();
+        while let Some(hook) = seq.next_element::<HookCommand>()? {
+            hooks.push(hook);
+        }
+        Ok(hooks)
+    }
+}
+
+impl<'de> Deserialize<'de> for HookCommand {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let mut hooks = deserializer.deserialize_any(HookVisitor { many: false })?;
+        Ok(hooks.remove(0))
+    }
+}
+
+fn one_or_many<'de, D>(deserializer: D) -> std::result::Result<Vec<HookCommand>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(HookVisitor { many: true })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_string_table_and_list_forms() {
+        let hooks: HooksConfig = toml::from_str(
+            r#"
+pre_sync = "echo pre"
+post_change = { run = "fc-cache -f", tags = "linux & !headless" }
+on_unlink = ["echo a", { run = "echo b", tags = ["macos", "linux"] }]
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(hooks.pre_sync.len(), 1);
+        assert_eq!(hooks.pre_sync[0].command(), "echo pre");
+        assert_eq!(hooks.pre_sync[0].tags_expr(), "");
+        assert_eq!(hooks.post_change[0].tags_expr(), "linux & !headless");
+        assert!(hooks.post_sync.is_empty());
+        assert_eq!(hooks.on_unlink.len(), 2);
+        assert_eq!(hooks.on_unlink[1].tags_expr(), "macos | linux");
+        assert!(!hooks.is_empty());
+    }
+
+    #[test]
+    fn unknown_key_in_hook_table_is_named_with_location() {
+        let err = crate::config::parse::from_toml::<HooksConfig>(
+            std::path::Path::new("towboat.toml"),
+            "post_sync = [\"echo a\", { run = \"echo b\", tag = \"linux\" }]\n",
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.starts_with("towboat.toml:1:"), "{err}");
+        assert!(err.contains("unknown field `tag`"), "{err}");
+        assert!(err.contains("did you mean `tags`?"), "{err}");
+
+        let err = toml::from_str::<HooksConfig>("pre_sync = 3\n")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("a command string, a { run, tags } table"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn empty_hooks() {
+        let hooks: HooksConfig = toml::from_str("").unwrap();
+        assert!(hooks.is_empty());
+    }
+}
@@ -4,17 +4,22 @@
 //! - Active system tags
 //! - Template variables
 //! - Which packages to deploy (with optional per-package tag overrides)
+//! - Scripts to run once or on change
+//! - Named profiles describing the machines the repo targets
 
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 
+use crate::config::hooks::HooksConfig;
 use crate::config::package::{DefaultConfig, PackageConfig, TargetConfig};
+use crate::config::scripts::ScriptConfig;
 use crate::error::{Result, TowboatError};
 
 /// Top-level `towboat.toml` manifest.
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct SystemManifest {
     #[serde(default)]
     pub system: SystemConfig,
@@ -24,14 +29,55 @@ pub struct SystemManifest {
 
     #[serde(default)]
     pub packages: HashMap<String, PackageEntry>,
+
+    /// Run-once / run-on-change scripts (`[[scripts]]`).
+    #[serde(default)]
+    pub scripts: Vec<ScriptConfig>,
+
+    /// Named machine profiles (`[profiles.<name>]`), used by `towboat lint` and `--as-host`.
+    #[serde(default)]
+    pub profiles: HashMap<String, ProfileConfig>,
 }
 
 /// `[system]` section of the manifest.
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct SystemConfig {
     /// Active build tags for this system (e.g. `["macos", "laptop", "work"]`).
     #[serde(default)]
     pub tags: Vec<String>,
+
+    /// Every tag the repo uses; `towboat lint` flags references to anything else.
+    #[serde(default)]
+    pub known_tags: Vec<String>,
+
+    /// How many sync generations to keep for `towboat rollback` (default 10, 0 disables them).
+    #[serde(default)]
+    pub keep_generations: Option<usize>,
+
+    /// Store symlink targets relative to the link's directory instead of absolute.
+    #[serde(default)]
+    pub relative_links: bool,
+
+    /// Where resolved files, the lock, generations and history live. Relative
+    /// paths are relative to the stow dir; default `$XDG_STATE_HOME/towboat/<repo-id>`.
+    #[serde(default)]
+    pub state_dir: Option<PathBuf>,
+}
+
+/// A machine the repo is deployed to, e.g. `[profiles.work-laptop] tags = ["macos", "work"]`.
+///
+/// Used by `towboat lint`, and simulated with `--as-host <name>`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfig {
+    /// Active tags on that machine.
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// Variables that differ on that machine, overlaid on `[variables]`.
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
 }
 
 /// Entry in the `[packages]` table.
@@ -42,11 +88,21 @@ pub struct SystemConfig {
 /// Optionally embeds full `PackageConfig` fields inline, making `boat.toml` optional.
 /// If inline config is provided, no `boat.toml` should exist (error if both present).
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct PackageEntry {
     /// Additional tags required for this package (ANDed with system tags).
     #[serde(default)]
     pub tags: Vec<String>,
 
+    /// When two packages deploy the same target, the higher priority wins.
+    /// Equal priorities are a collision error.
+    #[serde(default)]
+    pub priority: i32,
+
+    /// Commands to run before/after syncing this package (`[packages.<name>.hooks]`).
+    #[serde(default)]
+    pub hooks: HooksConfig,
+
     // --- Inline PackageConfig fields (optional) ---
     /// Override target directory for this package (supports `~` expansion).
     #[serde(default)]
@@ -63,6 +119,11 @@ pub struct PackageEntry {
     /// Default behavior for unconfigured files.
     #[serde(default)]
     pub default: Option<DefaultConfig>,
+
+    /// Gitignore-style patterns for files to skip. Unlike the other inline fields,
+    /// these also apply on top of a package's `boat.toml`.
+    #[serde(default)]
+    pub ignore: Vec<String>,
 }
 
 impl PackageEntry {
@@ -84,6 +145,7 @@ impl PackageEntry {
             build_tags: self.build_tags.clone(),
             targets: self.targets.clone(),
             default: self.default.clone(),
+            ignore: self.ignore.clone(),
         })
     }
 }
@@ -94,8 +156,27 @@ impl SystemManifest {
         let content = std::fs::read_to_string(path)
             .map_err(|_| TowboatError::ManifestNotFound(path.to_path_buf()))?;
 
-        toml::from_str(&content)
-            .map_err(|e| TowboatError::ManifestNotFound(path.to_path_buf()).into_io(e))
+        crate::config::parse::from_toml(path, &content)
+    }
+
+    /// The declared tag vocabulary: `[system] known_tags` if set, otherwise the union
+    /// of the system tags and every profile's tags. `None` when neither `known_tags`
+    /// nor any profile is declared.
+    pub fn tag_vocabulary(&self) -> Option<BTreeSet<String>> {
+        if !self.system.known_tags.is_empty() {
+            return Some(self.system.known_tags.iter().cloned().collect());
+        }
+        if self.profiles.is_empty() {
+            return None;
+        }
+        Some(
+            self.system
+                .tags
+                .iter()
+                .chain(self.profiles.values().flat_map(|p| &p.tags))
+                .cloned()
+                .collect(),
+        )
     }
 
     /// Collect the full set of active tags as a `HashSet`.
@@ -104,13 +185,6 @@ impl SystemManifest {
     }
 }
 
-// Helper to convert TOML parse errors into IO errors with context.
-impl TowboatError {
-    fn into_io(self, toml_err: toml::de::Error) -> Self {
-        TowboatError::LockCorrupt(format!("failed to parse manifest: {toml_err}"))
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -267,6 +341,8 @@ target_dir = "~"
     fn to_package_config_conversion() {
         let entry = PackageEntry {
             tags: vec!["work".to_string()],
+            priority: 0,
+            hooks: HooksConfig::default(),
             target_dir: Some("~".to_string()),
             build_tags: Some(vec!["production".to_string()]),
             targets: {
@@ -284,6 +360,7 @@ target_dir = "~"
                 include_all: true,
                 default_tag: "default".to_string(),
             }),
+            ignore: vec!["*.swp".to_string()],
         };
 
         let config = entry.to_package_config().unwrap();
@@ -291,6 +368,43 @@ target_dir = "~"
         assert_eq!(config.build_tags, Some(vec!["production".to_string()]));
         assert_eq!(config.targets.len(), 1);
         assert!(config.default.unwrap().include_all);
+        assert_eq!(config.ignore, vec!["*.swp"]);
+    }
+
+    #[test]
+    fn ignore_alone_is_not_inline_config() {
+        let entry = PackageEntry {
+            ignore: vec!["README.md".to_string()],
+            ..Default::default()
+        };
+        assert!(!entry.has_inline_config());
+    }
+
+    #[test]
+    fn tag_vocabulary_prefers_known_tags() {
+        let manifest: SystemManifest = toml::from_str(
+            r#"
+[system]
+tags = ["linux"]
+
+[profiles.mac]
+tags = ["macos", "laptop"]
+"#,
+        )
+        .unwrap();
+        let vocabulary = manifest.tag_vocabulary().unwrap();
+        assert_eq!(
+            vocabulary.into_iter().collect::<Vec<_>>(),
+            vec!["laptop", "linux", "macos"]
+        );
+
+        let manifest: SystemManifest =
+            toml::from_str("[system]\ntags = [\"linux\"]\nknown_tags = [\"linux\", \"macos\"]\n")
+                .unwrap();
+        assert_eq!(manifest.tag_vocabulary().unwrap().len(), 2);
+
+        let manifest: SystemManifest = toml::from_str("[system]\ntags = [\"linux\"]\n").unwrap();
+        assert!(manifest.tag_vocabulary().is_none());
     }
 
     #[test]
@@ -1,7 +1,11 @@
+pub mod hooks;
 pub mod manifest;
+pub mod overrides;
 pub mod package;
+pub mod parse;
+pub mod scripts;
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
 
@@ -13,6 +17,9 @@ use anyhow::{Context, Result};
 /// | No             | Yes                 | Use `boat.toml` (backwards compat)|
 /// | No             | No                  | Default (`include_all: true`)    |
 /// | Yes            | Yes                 | **Error** — pick one             |
+///
+/// The entry's `ignore` patterns are not inline config: they always apply, on top of
+/// `boat.toml` or the default.
 pub fn resolve_package_config(
     pkg_name: &str,
     pkg_dir: &Path,
@@ -22,7 +29,7 @@ pub fn resolve_package_config(
     let has_boat_toml = boat_toml.exists();
     let has_inline = pkg_entry.has_inline_config();
 
-    match (has_inline, has_boat_toml) {
+    let mut config = match (has_inline, has_boat_toml) {
         (true, true) => {
             anyhow::bail!(
                 "Package '{pkg_name}' has both inline config in towboat.toml and a boat.toml file. \
@@ -31,17 +38,15 @@ pub fn resolve_package_config(
         }
         (true, false) => {
             // Safe to unwrap: has_inline_config() returned true
-            Ok(pkg_entry.to_package_config().unwrap())
-        }
-        (false, true) => {
-            package::PackageConfig::load(&boat_toml).with_context(|| {
-                format!(
-                    "Failed to load boat.toml for package '{pkg_name}' at {}",
-                    pkg_dir.display()
-                )
-            })
+            pkg_entry.to_package_config().unwrap()
         }
-        (false, false) => Ok(package::PackageConfig {
+        (false, true) => package::PackageConfig::load(&boat_toml).with_context(|| {
+            format!(
+                "Failed to load boat.toml for package '{pkg_name}' at {}",
+                pkg_dir.display()
+            )
+        })?,
+        (false, false) => package::PackageConfig {
             target_dir: None,
             build_tags: None,
             targets: std::collections::HashMap::new(),
@@ -49,6 +54,31 @@ pub fn resolve_package_config(
                 include_all: true,
                 default_tag: "default".to_string(),
             }),
-        }),
+            ignore: Vec::new(),
+        },
+    };
+
+    // `ignore` in the manifest entry layers on top of whichever config applies
+    if !has_inline {
+        config.ignore.extend(pkg_entry.ignore.iter().cloned());
+    }
+
+    Ok(config)
+}
+
+/// Expand a leading `~` to `$HOME`; other paths are returned unchanged.
+pub fn expand_tilde(path: &Path) -> PathBuf {
+    if path.to_string_lossy() == "~" {
+        match std::env::var("HOME") {
+            Ok(home) => PathBuf::from(home),
+            Err(_) => path.to_path_buf(),
+        }
+    } else if let Some(rest) = path.to_string_lossy().strip_prefix("~/") {
+        match std::env::var("HOME") {
+            Ok(home) => PathBuf::from(home).join(rest),
+            Err(_) => path.to_path_buf(),
+        }
+    } else {
+        path.to_path_buf()
     }
 }
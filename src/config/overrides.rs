@@ -0,0 +1,195 @@
+//! Command-line overrides that simulate another machine: `--as-host`, `--tags`
+//! and `--set`, applied on top of the loaded manifest.
+
+use crate::config::manifest::SystemManifest;
+use crate::error::{Result, TowboatError};
+
+/// One item of `--tags`: `+tag` adds, `-tag` removes, a bare `tag` selects it as
+/// part of a replacement tag set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagChange {
+    Add(String),
+    Remove(String),
+    Set(String),
+}
+
+impl TagChange {
+    /// Parse a single `--tags` item.
+    pub fn parse(item: &str) -> std::result::Result<Self, String> {
+        let item = item.trim();
+        let (change, tag): (fn(String) -> Self, &str) = match item.as_bytes().first() {
+            Some(b'+') => (TagChange::Add, &item[1..]),
+            Some(b'-') => (TagChange::Remove, &item[1..]),
+            _ => (TagChange::Set, item),
+        };
+        if tag.is_empty()
+            || !tag
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(format!("invalid tag {item:?}"));
+        }
+        Ok(change(tag.to_string()))
+    }
+}
+
+/// Parse a `--set var=value` assignment.
+pub fn parse_assignment(assignment: &str) -> std::result::Result<(String, String), String> {
+    match assignment.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.to_string()))
+        }
+        _ => Err(format!("expected VAR=VALUE, got {assignment:?}")),
+    }
+}
+
+/// Overrides for the active tags and variables of a manifest.
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    /// Simulate the machine described by `[profiles.<host>]`.
+    pub host: Option<String>,
+    /// Tag changes, applied after the host's tags.
+    pub tags: Vec<TagChange>,
+    /// Variable assignments, applied last.
+    pub variables: Vec<(String, String)>,
+}
+
+impl Overrides {
+    pub fn is_empty(&self) -> bool {
+        self.host.is_none() && self.tags.is_empty() && self.variables.is_empty()
+    }
+
+    /// Apply to `manifest`, in order: the host profile replaces `[system] tags` and
+    /// overlays its variables; bare `--tags` items replace the tag set and `+`/`-`
+    /// items edit it; `--set` assignments override variables.
+    pub fn apply(&self, manifest: &mut SystemManifest) -> Result<()> {
+        if let Some(host) = &self.host {
+            let profile = manifest
+                .profiles
+                .get(host)
+                .ok_or_else(|| TowboatError::ProfileNotFound(host.clone()))?
+                .clone();
+            manifest.system.tags = profile.tags;
+            manifest.variables.extend(profile.variables);
+        }
+
+        let replacement: Vec<String> = self
+            .tags
+            .iter()
+            .filter_map(|change| match change {
+                TagChange::Set(tag) => Some(tag.clone()),
+                _ => None,
+            })
+            .collect();
+        if !replacement.is_empty() {
+            manifest.system.tags = replacement;
+        }
+        for change in &self.tags {
+            match change {
+                TagChange::Add(tag) if !manifest.system.tags.contains(tag) => {
+                    manifest.system.tags.push(tag.clone());
+                }
+                TagChange::Remove(tag) => manifest.system.tags.retain(|t| t != tag),
+                _ => {}
+            }
+        }
+
+        for (name, value) in &self.variables {
+            manifest.variables.insert(name.clone(), value.clone());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest() -> SystemManifest {
+        toml::from_str(
+            r#"
+[system]
+tags = ["linux", "laptop"]
+
+[variables]
+email = "me@home"
+editor = "vim"
+
+[profiles.work-mac]
+tags = ["macos", "work"]
+variables = { email = "me@work" }
+"#,
+        )
+        .unwrap()
+    }
+
+    fn changes(items: &[&str]) -> Vec<TagChange> {
+        items.iter().map(|i| TagChange::parse(i).unwrap()).collect()
+    }
+
+    #[test]
+    fn parse_tag_changes() {
+        assert_eq!(
+            changes(&["+macos", "-linux", "server"]),
+            vec![
+                TagChange::Add("macos".into()),
+                TagChange::Remove("linux".into()),
+                TagChange::Set("server".into()),
+            ]
+        );
+        assert!(TagChange::parse("+").is_err());
+        assert!(TagChange::parse("a&b").is_err());
+        assert_eq!(
+            parse_assignment("email=a=b").unwrap(),
+            ("email".to_string(), "a=b".to_string())
+        );
+        assert!(parse_assignment("novalue").is_err());
+    }
+
+    #[test]
+    fn add_and_remove_edit_the_system_tags() {
+        let mut m = manifest();
+        Overrides {
+            tags: changes(&["+macos", "-linux", "+laptop"]),
+            ..Default::default()
+        }
+        .apply(&mut m)
+        .unwrap();
+        assert_eq!(m.system.tags, vec!["laptop", "macos"]);
+    }
+
+    #[test]
+    fn bare_tags_replace_the_set() {
+        let mut m = manifest();
+        Overrides {
+            tags: changes(&["server", "linux", "-linux", "+work"]),
+            ..Default::default()
+        }
+        .apply(&mut m)
+        .unwrap();
+        assert_eq!(m.system.tags, vec!["server", "work"]);
+    }
+
+    #[test]
+    fn host_overlay_then_tags_and_variables() {
+        let mut m = manifest();
+        Overrides {
+            host: Some("work-mac".into()),
+            tags: changes(&["+laptop"]),
+            variables: vec![("editor".into(), "nvim".into())],
+        }
+        .apply(&mut m)
+        .unwrap();
+        assert_eq!(m.system.tags, vec!["macos", "work", "laptop"]);
+        assert_eq!(m.variables["email"], "me@work");
+        assert_eq!(m.variables["editor"], "nvim");
+
+        let err = Overrides {
+            host: Some("nope".into()),
+            ..Default::default()
+        }
+        .apply(&mut manifest())
+        .unwrap_err();
+        assert!(matches!(err, TowboatError::ProfileNotFound(name) if name == "nope"));
+    }
+}
@@ -5,6 +5,7 @@
 //! - Default build tags for the package
 //! - A `[targets]` map of source paths → tag expressions + optional target remapping
 //! - Default behavior for unconfigured files
+//! - `ignore` patterns for files that should never be discovered
 
 use std::collections::HashMap;
 use std::path::Path;
@@ -14,7 +15,8 @@ use serde::{Deserialize, Serialize};
 use crate::error::{Result, TowboatError};
 
 /// Parsed `boat.toml` configuration.
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct PackageConfig {
     /// Override target directory for this package (supports `~` expansion).
     #[serde(default)]
@@ -31,10 +33,15 @@ pub struct PackageConfig {
     /// Default behavior for unconfigured files.
     #[serde(default)]
     pub default: Option<DefaultConfig>,
+
+    /// Gitignore-style patterns for files to skip, relative to this config's directory.
+    #[serde(default)]
+    pub ignore: Vec<String>,
 }
 
 /// Configuration for a single file or directory in `[targets]`.
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct TargetConfig {
     /// Optional remapped target path (relative to target directory).
     #[serde(default)]
@@ -89,6 +96,7 @@ impl TagsSpec {
 
 /// Default behavior for files not explicitly listed in `[targets]`.
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct DefaultConfig {
     /// If true, include all files not explicitly listed.
     #[serde(default)]
@@ -118,12 +126,7 @@ impl PackageConfig {
         let content = std::fs::read_to_string(path)
             .map_err(|_| TowboatError::PackageConfigNotFound(path.to_path_buf()))?;
 
-        toml::from_str(&content).map_err(|e| {
-            TowboatError::LockCorrupt(format!(
-                "failed to parse boat.toml at {}: {e}",
-                path.display()
-            ))
-        })
+        crate::config::parse::from_toml(path, &content)
     }
 }
 
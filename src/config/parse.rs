@@ -0,0 +1,110 @@
+//! Strict TOML parsing for `towboat.toml` and `boat.toml`.
+//!
+//! Config types deny unknown fields, so a typo like `tag = "linux"` is an error
+//! instead of silently doing nothing. Errors carry the line and column of the
+//! offending key and, for unknown fields, the closest known field name.
+
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+
+use crate::error::{Result, TowboatError};
+
+/// Parse `content` (read from `path`) into `T`, reporting errors as
+/// `TowboatError::InvalidConfig`.
+pub fn from_toml<T: DeserializeOwned>(path: &Path, content: &str) -> Result<T> {
+    toml::from_str(content).map_err(|e| {
+        let (line, column) = e
+            .span()
+            .map(|span| line_column(content, span.start))
+            .unwrap_or((1, 1));
+        let mut message = e.message().trim_end().to_string();
+        if let Some(suggestion) = suggest(&message) {
+            message.push_str(&format!("; did you mean `{suggestion}`?"));
+        }
+        TowboatError::InvalidConfig {
+            path: path.to_path_buf(),
+            line,
+            column,
+            message,
+        }
+    })
+}
+
+/// 1-based line and column of a byte offset.
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+/// For serde's "unknown field `x`, expected one of `a`, `b`" message, the
+/// expected field closest to `x`, if any is close enough to be a likely typo.
+fn suggest(message: &str) -> Option<String> {
+    let rest = message.strip_prefix("unknown field `")?;
+    let (unknown, expected) = rest.split_once('`')?;
+    let (_, expected) = expected.split_once("expected")?;
+
+    expected
+        .split('`')
+        .skip(1)
+        .step_by(2)
+        .map(|candidate| (strsim::damerau_levenshtein(unknown, candidate), candidate))
+        .filter(|(distance, candidate)| *distance <= (candidate.len() / 3).max(1) + 1)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::manifest::SystemManifest;
+    use crate::config::package::PackageConfig;
+
+    fn parse_err<T: DeserializeOwned + std::fmt::Debug>(content: &str) -> TowboatError {
+        from_toml::<T>(Path::new("boat.toml"), content).unwrap_err()
+    }
+
+    #[test]
+    fn unknown_field_points_at_key_with_suggestion() {
+        let err = parse_err::<PackageConfig>("[targets]\n\".bashrc\" = { tag = \"linux\" }\n");
+        let TowboatError::InvalidConfig {
+            line,
+            column,
+            message,
+            ..
+        } = &err
+        else {
+            panic!("unexpected error: {err}");
+        };
+        assert_eq!((*line, *column), (2, 15));
+        assert!(message.contains("unknown field `tag`"), "{message}");
+        assert!(message.ends_with("did you mean `tags`?"), "{message}");
+        assert!(err.to_string().starts_with("boat.toml:2:15: "));
+    }
+
+    #[test]
+    fn suggestions_in_nested_manifest_tables() {
+        let err = parse_err::<SystemManifest>(
+            "[system]\ntags = []\n\n[packages.bash]\ntaget_dir = \"~\"\n",
+        );
+        assert!(err.to_string().contains(":5:1: "), "{err}");
+        assert!(
+            err.to_string().contains("did you mean `target_dir`?"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn no_suggestion_for_unrelated_field() {
+        let err = parse_err::<PackageConfig>("completely_different = 1\n");
+        assert!(!err.to_string().contains("did you mean"), "{err}");
+    }
+
+    #[test]
+    fn syntax_errors_have_locations() {
+        let err = parse_err::<PackageConfig>("[targets]\n\".bashrc\" = {\n");
+        assert!(err.to_string().starts_with("boat.toml:2:"), "{err}");
+    }
+}
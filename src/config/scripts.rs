@@ -0,0 +1,134 @@
+//! Parsing for `[[scripts]]` entries in `towboat.toml`.
+//!
+//! ```toml
+//! [[scripts]]
+//! path = "scripts/install-plugins.sh"
+//! run = "once"
+//!
+//! [[scripts]]
+//! path = "scripts/packages.sh"
+//! run = "onchange"
+//! tags = "linux"
+//! ```
+
+use std::path::{Component, Path};
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::config::package::TagsSpec;
+
+/// When a script runs again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptMode {
+    /// Run on the first sync only.
+    Once,
+    /// Run whenever the resolved script content changes.
+    OnChange,
+}
+
+impl ScriptMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScriptMode::Once => "once",
+            ScriptMode::OnChange => "onchange",
+        }
+    }
+}
+
+/// A script declared in the manifest.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScriptConfig {
+    /// Script path relative to the stow directory. Absolute paths and `..`
+    /// are rejected: the resolved copy is written to the same path under the
+    /// state dir, so they would overwrite the source or escape it.
+    #[serde(deserialize_with = "relative_path")]
+    pub path: String,
+
+    /// `once` or `onchange`.
+    pub run: ScriptMode,
+
+    /// Tag expression filter; the script is skipped when it doesn't match.
+    #[serde(default)]
+    pub tags: TagsSpec,
+}
+
+fn relative_path<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let path = String::deserialize(deserializer)?;
+    let escapes = Path::new(&path).components().any(|c| {
+        matches!(
+            c,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_)
+        )
+    });
+    if escapes {
+        return Err(serde::de::Error::custom(format!(
+            "script `{path}` must be a path inside the stow directory (no leading `/` or `..`)"
+        )));
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct Wrapper {
+        scripts: Vec<ScriptConfig>,
+    }
+
+    #[test]
+    fn parse_scripts_table() {
+        let parsed: Wrapper = toml::from_str(
+            r#"
+[[scripts]]
+path = "scripts/a.sh"
+run = "once"
+
+[[scripts]]
+path = "scripts/b.sh"
+run = "onchange"
+tags = "linux & !headless"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(parsed.scripts[0].run, ScriptMode::Once);
+        assert_eq!(parsed.scripts[0].tags.to_expr_string(), "");
+        assert_eq!(parsed.scripts[1].run, ScriptMode::OnChange);
+        assert_eq!(parsed.scripts[1].tags.to_expr_string(), "linux & !headless");
+    }
+
+    #[test]
+    fn absolute_and_parent_paths_are_rejected() {
+        for path in [
+            "/home/me/dotfiles/scripts/a.sh",
+            "../a.sh",
+            "scripts/../../a.sh",
+        ] {
+            let err = crate::config::parse::from_toml::<Wrapper>(
+                Path::new("towboat.toml"),
+                &format!("[[scripts]]\npath = \"{path}\"\nrun = \"once\"\n"),
+            )
+            .unwrap_err()
+            .to_string();
+            assert!(
+                err.contains(&format!("script `{path}` must be a path")),
+                "{err}"
+            );
+            assert!(err.starts_with("towboat.toml:2:"), "{err}");
+        }
+        let ok: Wrapper =
+            toml::from_str("[[scripts]]\npath = \"./scripts/a.sh\"\nrun = \"once\"\n").unwrap();
+        assert_eq!(ok.scripts[0].path, "./scripts/a.sh");
+    }
+
+    #[test]
+    fn unknown_mode_is_error() {
+        let result: std::result::Result<Wrapper, _> =
+            toml::from_str("[[scripts]]\npath = \"a\"\nrun = \"always\"\n");
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,195 @@
+//! Target collision detection across packages.
+//!
+//! Two discovered files that resolve to the same target path would silently
+//! overwrite each other's symlink. Collisions are detected over all packages
+//! before anything is written; a package's `priority` decides intentional
+//! overrides, and equal priorities are an error.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::discovery::walker::{DiscoveredFile, MatchRule};
+
+/// Files discovered for one package, with the package's priority.
+#[derive(Debug, Clone)]
+pub struct PackageFiles {
+    pub package: String,
+    pub priority: i32,
+    pub files: Vec<DiscoveredFile>,
+}
+
+/// One file claiming a target path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Claim {
+    pub package: String,
+    /// Source path relative to the package directory.
+    pub source: PathBuf,
+    /// Rule that included the file.
+    pub rule: MatchRule,
+    pub priority: i32,
+}
+
+impl fmt::Display for Claim {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} ({}, priority {})",
+            self.package,
+            self.source.display(),
+            self.rule,
+            self.priority
+        )
+    }
+}
+
+/// Several files with the same, highest priority claiming one target.
+#[derive(Debug, Clone)]
+pub struct Collision {
+    pub target: PathBuf,
+    /// Every claim on the target, highest priority first.
+    pub claims: Vec<Claim>,
+}
+
+/// A lower-priority file that gives way to a higher-priority package.
+#[derive(Debug, Clone)]
+pub struct Override {
+    pub target: PathBuf,
+    pub winner: Claim,
+    pub loser: Claim,
+}
+
+/// Result of [`detect`].
+#[derive(Debug, Clone, Default)]
+pub struct CollisionReport {
+    pub collisions: Vec<Collision>,
+    pub overrides: Vec<Override>,
+}
+
+impl CollisionReport {
+    /// Whether `package`'s file at `source` loses to a higher-priority package.
+    pub fn is_overridden(&self, package: &str, source: &Path) -> bool {
+        self.overrides
+            .iter()
+            .any(|o| o.loser.package == package && o.loser.source == source)
+    }
+}
+
+/// Find every target claimed by more than one discovered file.
+///
+/// Collisions and overrides are sorted by target path.
+pub fn detect(packages: &[PackageFiles]) -> CollisionReport {
+    let mut by_target: HashMap<&Path, Vec<Claim>> = HashMap::new();
+    for pkg in packages {
+        for file in &pkg.files {
+            by_target.entry(&file.target_path).or_default().push(Claim {
+                package: pkg.package.clone(),
+                source: file.relative_path.clone(),
+                rule: file.rule.clone(),
+                priority: pkg.priority,
+            });
+        }
+    }
+
+    let mut report = CollisionReport::default();
+    let mut targets: Vec<_> = by_target.into_iter().filter(|(_, c)| c.len() > 1).collect();
+    targets.sort_by(|a, b| a.0.cmp(b.0));
+
+    for (target, mut claims) in targets {
+        claims.sort_by(|a, b| {
+            b.priority
+                .cmp(&a.priority)
+                .then_with(|| a.package.cmp(&b.package))
+                .then_with(|| a.source.cmp(&b.source))
+        });
+        if claims[0].priority == claims[1].priority {
+            report.collisions.push(Collision {
+                target: target.to_path_buf(),
+                claims,
+            });
+        } else {
+            for loser in &claims[1..] {
+                report.overrides.push(Override {
+                    target: target.to_path_buf(),
+                    winner: claims[0].clone(),
+                    loser: loser.clone(),
+                });
+            }
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(relative: &str, target: &str) -> DiscoveredFile {
+        DiscoveredFile {
+            source_path: PathBuf::from("/stow").join(relative),
+            relative_path: PathBuf::from(relative),
+            target_path: PathBuf::from(target),
+            matched_expr: "default".to_string(),
+            rule: MatchRule::Default,
+            variant: None,
+        }
+    }
+
+    fn package(name: &str, priority: i32, files: Vec<DiscoveredFile>) -> PackageFiles {
+        PackageFiles {
+            package: name.to_string(),
+            priority,
+            files,
+        }
+    }
+
+    #[test]
+    fn distinct_targets_do_not_collide() {
+        let report = detect(&[
+            package("a", 0, vec![file(".bashrc", ".bashrc")]),
+            package("b", 0, vec![file(".vimrc", ".vimrc")]),
+        ]);
+        assert!(report.collisions.is_empty());
+        assert!(report.overrides.is_empty());
+    }
+
+    #[test]
+    fn equal_priority_collides() {
+        let report = detect(&[
+            package("b", 0, vec![file(".gitconfig", ".gitconfig")]),
+            package("a", 0, vec![file("gitconfig", ".gitconfig")]),
+        ]);
+        assert_eq!(report.collisions.len(), 1);
+        let collision = &report.collisions[0];
+        assert_eq!(collision.target, PathBuf::from(".gitconfig"));
+        assert_eq!(collision.claims[0].package, "a");
+        assert_eq!(collision.claims[1].package, "b");
+    }
+
+    #[test]
+    fn remap_within_one_package_collides() {
+        let report = detect(&[package(
+            "a",
+            0,
+            vec![
+                file(".profile", ".profile"),
+                file("dev-profile", ".profile"),
+            ],
+        )]);
+        assert_eq!(report.collisions.len(), 1);
+    }
+
+    #[test]
+    fn higher_priority_overrides() {
+        let report = detect(&[
+            package("base", 0, vec![file(".gitconfig", ".gitconfig")]),
+            package("work", 10, vec![file(".gitconfig", ".gitconfig")]),
+        ]);
+        assert!(report.collisions.is_empty());
+        assert_eq!(report.overrides.len(), 1);
+        assert_eq!(report.overrides[0].winner.package, "work");
+        assert!(report.is_overridden("base", Path::new(".gitconfig")));
+        assert!(!report.is_overridden("work", Path::new(".gitconfig")));
+    }
+}
@@ -0,0 +1,334 @@
+//! Numbered snapshots of the resolved tree and lock, under `generations/` in the state dir.
+//!
+//! ```text
+//! generations/
+//!   objects/<sha256>          resolved file contents, stored once however many generations use them
+//!   <n>/generation.toml       when it was taken, what changed, and source -> object
+//!   <n>/towboat.lock          the lock as of that sync
+//!   current                   the generation the deployed tree matches
+//! ```
+//!
+//! Each sync that changes the resolved tree records a new generation;
+//! `towboat rollback` restores one.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::deploy::lock::LockFile;
+use crate::error::{Result, TowboatError};
+use crate::resolve::resolver::compute_hash;
+
+/// Generations kept when `[system] keep_generations` is not set.
+pub const DEFAULT_KEEP: usize = 10;
+
+/// Metadata for one generation (`<n>/generation.toml`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Generation {
+    pub number: u32,
+    pub created: DateTime<Utc>,
+    /// Files added, changed or removed relative to the generation it replaced.
+    pub changed: usize,
+    /// Resolved contents by lock source (`bash/.bashrc`) -> object hash.
+    pub files: BTreeMap<String, String>,
+}
+
+/// The generation store of one stow directory.
+#[derive(Debug, Clone)]
+pub struct Generations {
+    dir: PathBuf,
+}
+
+impl Generations {
+    /// The store under `towboat_dir` (see [`crate::deploy::state::StateDir`]).
+    pub fn new(towboat_dir: &Path) -> Self {
+        Self {
+            dir: towboat_dir.join("generations"),
+        }
+    }
+
+    /// All generations, oldest first.
+    pub fn list(&self) -> Result<Vec<Generation>> {
+        let mut generations = Vec::new();
+        for number in self.numbers()? {
+            generations.push(self.load(number)?);
+        }
+        Ok(generations)
+    }
+
+    /// Metadata for generation `number`.
+    pub fn load(&self, number: u32) -> Result<Generation> {
+        let path = self.generation_dir(number).join("generation.toml");
+        if !path.exists() {
+            return Err(TowboatError::GenerationNotFound(number));
+        }
+        let content = std::fs::read_to_string(&path)?;
+        toml::from_str(&content)
+            .map_err(|e| TowboatError::GenerationCorrupt(format!("{}: {e}", path.display())))
+    }
+
+    /// The lock recorded with generation `number`.
+    pub fn load_lock(&self, number: u32) -> Result<LockFile> {
+        LockFile::load(&self.generation_dir(number).join("towboat.lock"))
+    }
+
+    /// Contents of a stored resolved file.
+    pub fn read_object(&self, hash: &str) -> Result<String> {
+        Ok(std::fs::read_to_string(self.object_path(hash))?)
+    }
+
+    /// The generation the deployed tree matches, if any.
+    pub fn current(&self) -> Option<u32> {
+        std::fs::read_to_string(self.dir.join("current"))
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+    }
+
+    pub fn set_current(&self, number: u32) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(self.dir.join("current"), format!("{number}\n"))?;
+        Ok(())
+    }
+
+    /// Record the resolved files tracked by `lock` as a new generation, then
+    /// prune down to `keep`. Returns `None` when the tree matches the current
+    /// generation, so syncs that change nothing don't push old ones out.
+    pub fn snapshot(
+        &self,
+        resolved_dir: &Path,
+        lock: &LockFile,
+        keep: usize,
+    ) -> Result<Option<Generation>> {
+        let mut files = BTreeMap::new();
+        for entry in lock.entries() {
+            // The lock already knows each hash; only files new to the store are read
+            if !entry.resolved_hash.is_empty() && self.object_path(&entry.resolved_hash).exists() {
+                files.insert(entry.source.clone(), entry.resolved_hash.clone());
+                continue;
+            }
+            // Missing resolved files can't be restored; `status` reports them as broken
+            let Ok(content) = std::fs::read_to_string(resolved_dir.join(&entry.source)) else {
+                continue;
+            };
+            let hash = compute_hash(&content);
+            let object = self.object_path(&hash);
+            if !object.exists() {
+                std::fs::create_dir_all(self.dir.join("objects"))?;
+                std::fs::write(&object, content)?;
+            }
+            files.insert(entry.source.clone(), hash);
+        }
+
+        let previous = match self.current() {
+            Some(number) => self.load(number).ok(),
+            None => None,
+        };
+        let changed = match &previous {
+            Some(previous) if previous.files == files => return Ok(None),
+            Some(previous) => changed_files(&previous.files, &files),
+            None => files.len(),
+        };
+
+        let number = self.numbers()?.last().map_or(1, |n| n + 1);
+        let generation = Generation {
+            number,
+            created: Utc::now(),
+            changed,
+            files,
+        };
+        let dir = self.generation_dir(number);
+        std::fs::create_dir_all(&dir)?;
+        let content = toml::to_string_pretty(&generation)
+            .map_err(|e| TowboatError::GenerationCorrupt(e.to_string()))?;
+        std::fs::write(dir.join("generation.toml"), content)?;
+        lock.save(&dir.join("towboat.lock"))?;
+        self.set_current(number)?;
+
+        self.prune(keep)?;
+        Ok(Some(generation))
+    }
+
+    /// Remove the oldest generations beyond `keep` (never the current one),
+    /// then any objects no remaining generation refers to. Returns how many
+    /// generations were removed.
+    pub fn prune(&self, keep: usize) -> Result<usize> {
+        let numbers = self.numbers()?;
+        let current = self.current();
+        let excess = numbers.len().saturating_sub(keep.max(1));
+        let mut removed = 0;
+        for &number in numbers.iter().filter(|&&n| Some(n) != current).take(excess) {
+            std::fs::remove_dir_all(self.generation_dir(number))?;
+            removed += 1;
+        }
+        if removed == 0 {
+            return Ok(0);
+        }
+
+        let mut referenced = HashSet::new();
+        for generation in self.list()? {
+            referenced.extend(generation.files.into_values());
+        }
+        for entry in std::fs::read_dir(self.dir.join("objects"))? {
+            let entry = entry?;
+            if !referenced.contains(entry.file_name().to_string_lossy().as_ref()) {
+                std::fs::remove_file(entry.path())?;
+            }
+        }
+        Ok(removed)
+    }
+
+    fn numbers(&self) -> Result<Vec<u32>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut numbers = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            if let Some(number) = entry.file_name().to_str().and_then(|s| s.parse().ok())
+                && entry.path().is_dir()
+            {
+                numbers.push(number);
+            }
+        }
+        numbers.sort_unstable();
+        Ok(numbers)
+    }
+
+    fn generation_dir(&self, number: u32) -> PathBuf {
+        self.dir.join(number.to_string())
+    }
+
+    fn object_path(&self, hash: &str) -> PathBuf {
+        self.dir.join("objects").join(hash)
+    }
+}
+
+/// Sources added, removed or whose content differs between two generations.
+fn changed_files(old: &BTreeMap<String, String>, new: &BTreeMap<String, String>) -> usize {
+    let modified_or_removed = old
+        .iter()
+        .filter(|(source, hash)| new.get(*source) != Some(*hash))
+        .count();
+    let added = new
+        .keys()
+        .filter(|source| !old.contains_key(*source))
+        .count();
+    modified_or_removed + added
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deploy::lock::LockEntry;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn entry(source: &str, content: &str) -> LockEntry {
+        LockEntry {
+            package: "bash".to_string(),
+            source: source.to_string(),
+            source_hash: String::new(),
+            resolved_hash: compute_hash(content),
+            target: source.trim_start_matches("bash/").to_string(),
+            tags_matched: Vec::new(),
+            inputs: None,
+            source_stat: None,
+            resolved_stat: None,
+        }
+    }
+
+    fn sync(dir: &Path, files: &[(&str, &str)]) -> LockFile {
+        let mut lock = LockFile::default();
+        for (source, content) in files {
+            let path = dir.join("resolved").join(source);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+            lock.upsert(entry(source, content));
+        }
+        lock
+    }
+
+    #[test]
+    fn snapshots_dedupe_contents_and_skip_unchanged_trees() {
+        let dir = TempDir::new().unwrap();
+        let store = Generations::new(dir.path());
+        let resolved = dir.path().join("resolved");
+
+        let lock = sync(
+            dir.path(),
+            &[("bash/.bashrc", "one\n"), ("bash/.profile", "p\n")],
+        );
+        let first = store.snapshot(&resolved, &lock, 10).unwrap().unwrap();
+        assert_eq!((first.number, first.changed), (1, 2));
+        assert!(store.snapshot(&resolved, &lock, 10).unwrap().is_none());
+
+        let lock = sync(
+            dir.path(),
+            &[("bash/.bashrc", "two\n"), ("bash/.profile", "p\n")],
+        );
+        let second = store.snapshot(&resolved, &lock, 10).unwrap().unwrap();
+        assert_eq!((second.number, second.changed), (2, 1));
+        assert_eq!(store.current(), Some(2));
+        assert_eq!(
+            fs::read_dir(dir.path().join("generations/objects"))
+                .unwrap()
+                .count(),
+            3
+        );
+        assert_eq!(
+            store.read_object(&first.files["bash/.bashrc"]).unwrap(),
+            "one\n"
+        );
+        assert_eq!(store.load_lock(1).unwrap().entries().len(), 2);
+    }
+
+    #[test]
+    fn snapshots_take_hashes_from_the_lock_and_read_only_new_objects() {
+        let dir = TempDir::new().unwrap();
+        let store = Generations::new(dir.path());
+        let resolved = dir.path().join("resolved");
+
+        let lock = sync(dir.path(), &[("bash/.bashrc", "one\n")]);
+        store.snapshot(&resolved, &lock, 10).unwrap().unwrap();
+
+        // Already stored: recorded from the lock without reading the file
+        let mut lock = sync(dir.path(), &[("bash/.profile", "p\n")]);
+        lock.upsert(entry("bash/.bashrc", "one\n"));
+        fs::remove_file(resolved.join("bash/.bashrc")).unwrap();
+        let second = store.snapshot(&resolved, &lock, 10).unwrap().unwrap();
+        assert_eq!(second.files["bash/.bashrc"], compute_hash("one\n"));
+        assert_eq!(
+            store.read_object(&second.files["bash/.profile"]).unwrap(),
+            "p\n"
+        );
+    }
+
+    #[test]
+    fn prune_keeps_newest_and_current_and_drops_unused_objects() {
+        let dir = TempDir::new().unwrap();
+        let store = Generations::new(dir.path());
+        let resolved = dir.path().join("resolved");
+        for content in ["a\n", "b\n", "c\n"] {
+            let lock = sync(dir.path(), &[("bash/.bashrc", content)]);
+            store.snapshot(&resolved, &lock, 10).unwrap();
+        }
+
+        // Rolled back to 1: it survives, 2 is the oldest left to drop
+        store.set_current(1).unwrap();
+        assert_eq!(store.prune(2).unwrap(), 1);
+        let numbers: Vec<u32> = store.list().unwrap().iter().map(|g| g.number).collect();
+        assert_eq!(numbers, [1, 3]);
+        assert_eq!(
+            fs::read_dir(dir.path().join("generations/objects"))
+                .unwrap()
+                .count(),
+            2
+        );
+        assert!(matches!(
+            store.load(2),
+            Err(TowboatError::GenerationNotFound(2))
+        ));
+    }
+}
@@ -0,0 +1,226 @@
+//! Append-only record of what each sync, rollback and relocate did
+//! (`history.jsonl` in the state dir).
+//!
+//! One JSON object per line, oldest first. Unlike the lock, which only knows
+//! the current state, the history answers "when did `.gitconfig` last change
+//! on this machine, and under which tags?".
+
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::manifest::SystemManifest;
+use crate::error::{Result, TowboatError};
+
+/// The command that produced a record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Sync,
+    Rollback,
+    Relocate,
+}
+
+impl Action {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Action::Sync => "sync",
+            Action::Rollback => "rollback",
+            Action::Relocate => "relocate",
+        }
+    }
+}
+
+/// What happened to one deployed file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    Added,
+    Updated,
+    Removed,
+    /// Edited in place since the last sync, so left alone.
+    Drifted,
+}
+
+impl Change {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Change::Added => "added",
+            Change::Updated => "updated",
+            Change::Removed => "removed",
+            Change::Drifted => "drifted",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileChange {
+    pub package: String,
+    /// Path relative to the target directory (e.g. `.gitconfig`).
+    pub target: String,
+    pub change: Change,
+}
+
+/// One line of the history.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    pub time: DateTime<Utc>,
+    pub action: Action,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// The `[profiles.<name>]` whose tags match the active tags, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// Active tags, sorted.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Package the command was limited to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    /// Generation recorded (sync) or restored (rollback).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generation: Option<u32>,
+    #[serde(default)]
+    pub files: Vec<FileChange>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+impl HistoryRecord {
+    /// A record stamped with the current time, user and host, and the tags and
+    /// matching profile of `manifest` when it could be loaded.
+    pub fn new(action: Action, manifest: Option<&SystemManifest>) -> Self {
+        let mut tags = Vec::new();
+        let mut profiles = Vec::new();
+        if let Some(manifest) = manifest {
+            let active: HashSet<&String> = manifest.system.tags.iter().collect();
+            profiles = manifest
+                .profiles
+                .iter()
+                .filter(|(_, profile)| profile.tags.iter().collect::<HashSet<_>>() == active)
+                .map(|(name, _)| name.clone())
+                .collect();
+            profiles.sort();
+            tags = active.into_iter().cloned().collect();
+            tags.sort();
+        }
+
+        Self {
+            time: Utc::now(),
+            action,
+            user: std::env::var("USER")
+                .or_else(|_| std::env::var("USERNAME"))
+                .ok(),
+            host: hostname(),
+            profile: profiles.into_iter().next(),
+            tags,
+            package: None,
+            generation: None,
+            files: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+}
+
+/// The history file of one stow directory.
+#[derive(Debug, Clone)]
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+    /// The history under `towboat_dir` (see [`crate::deploy::state::StateDir`]).
+    pub fn new(towboat_dir: &Path) -> Self {
+        Self {
+            path: towboat_dir.join("history.jsonl"),
+        }
+    }
+
+    pub fn append(&self, record: &HistoryRecord) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let line = serde_json::to_string(record)
+            .map_err(|e| TowboatError::HistoryCorrupt(e.to_string()))?;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{line}")?;
+        Ok(())
+    }
+
+    /// All records, oldest first. Empty if nothing has been recorded yet.
+    pub fn read(&self) -> Result<Vec<HistoryRecord>> {
+        let content = match std::fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line).map_err(|e| {
+                    TowboatError::HistoryCorrupt(format!("{}:{}: {e}", self.path.display(), i + 1))
+                })
+            })
+            .collect()
+    }
+}
+
+fn hostname() -> Option<String> {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/proc/sys/kernel/hostname").ok())
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn records_append_and_read_back_in_order() {
+        let dir = TempDir::new().unwrap();
+        let history = History::new(dir.path());
+        assert!(history.read().unwrap().is_empty());
+
+        let manifest: SystemManifest = toml::from_str(
+            "[system]\ntags = [\"work\", \"linux\"]\n\n[profiles.box]\ntags = [\"linux\", \"work\"]\n",
+        )
+        .unwrap();
+        let mut sync = HistoryRecord::new(Action::Sync, Some(&manifest));
+        sync.files.push(FileChange {
+            package: "git".to_string(),
+            target: ".gitconfig".to_string(),
+            change: Change::Updated,
+        });
+        let rollback = HistoryRecord::new(Action::Rollback, None);
+        history.append(&sync).unwrap();
+        history.append(&rollback).unwrap();
+
+        let records = history.read().unwrap();
+        assert_eq!(records, [sync, rollback]);
+        assert_eq!(records[0].tags, ["linux", "work"]);
+        assert_eq!(records[0].profile.as_deref(), Some("box"));
+        assert!(records[1].tags.is_empty());
+    }
+
+    #[test]
+    fn corrupt_lines_are_reported_with_line_number() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("history.jsonl"), "{}\n").unwrap();
+        let err = History::new(dir.path()).read().unwrap_err();
+        assert!(err.to_string().contains("history.jsonl:1:"), "{err}");
+    }
+}
@@ -0,0 +1,250 @@
+//! Running package hooks.
+//!
+//! Hooks run through `sh -c` in the package directory. Context is passed in
+//! environment variables:
+//!
+//! | Variable | Value |
+//! |---|---|
+//! | `TOWBOAT_HOOK` | `pre_sync`, `post_sync`, `post_change` or `on_unlink` |
+//! | `TOWBOAT_PACKAGE` | Package name |
+//! | `TOWBOAT_STOW_DIR`, `TOWBOAT_TARGET_DIR` | Directories of the sync |
+//! | `TOWBOAT_CHANGED_FILES` | Newline-separated target paths that changed |
+//! | `TOWBOAT_UNLINKED_FILES` | Newline-separated target paths that were removed |
+
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config::hooks::{HookCommand, HooksConfig};
+use crate::error::Result;
+use crate::tags::matcher;
+
+/// Point in the sync at which a hook runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookKind {
+    PreSync,
+    PostSync,
+    PostChange,
+    OnUnlink,
+}
+
+impl HookKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HookKind::PreSync => "pre_sync",
+            HookKind::PostSync => "post_sync",
+            HookKind::PostChange => "post_change",
+            HookKind::OnUnlink => "on_unlink",
+        }
+    }
+
+    fn commands(self, hooks: &HooksConfig) -> &[HookCommand] {
+        match self {
+            HookKind::PreSync => &hooks.pre_sync,
+            HookKind::PostSync => &hooks.post_sync,
+            HookKind::PostChange => &hooks.post_change,
+            HookKind::OnUnlink => &hooks.on_unlink,
+        }
+    }
+}
+
+impl fmt::Display for HookKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Package and paths a hook runs for.
+#[derive(Debug, Clone)]
+pub struct HookContext<'a> {
+    pub package: &'a str,
+    pub package_dir: &'a Path,
+    pub stow_dir: &'a Path,
+    pub target_dir: &'a Path,
+    /// Absolute target paths changed by the sync.
+    pub changed: &'a [PathBuf],
+    /// Absolute target paths whose symlinks were removed.
+    pub unlinked: &'a [PathBuf],
+}
+
+/// Outcome of one hook command.
+#[derive(Debug, Clone)]
+pub struct HookResult {
+    pub package: String,
+    pub kind: HookKind,
+    pub command: String,
+    /// `None` on success, otherwise why the hook failed.
+    pub error: Option<String>,
+}
+
+impl HookResult {
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// The commands of `kind` whose tag filter matches the active tags.
+pub fn selected<'a>(
+    hooks: &'a HooksConfig,
+    kind: HookKind,
+    active_tags: &HashSet<String>,
+) -> Result<Vec<&'a HookCommand>> {
+    let mut selected = Vec::new();
+    for hook in kind.commands(hooks) {
+        if matches(hook, active_tags)? {
+            selected.push(hook);
+        }
+    }
+    Ok(selected)
+}
+
+fn matches(hook: &HookCommand, active_tags: &HashSet<String>) -> Result<bool> {
+    let expr = hook.tags_expr();
+    Ok(expr.is_empty() || matcher::parse(&expr)?.evaluate(active_tags))
+}
+
+/// Run the commands of `kind` whose tag filter matches, in order.
+///
+/// Every selected command runs even if an earlier one fails. A command whose
+/// tag filter doesn't parse doesn't run and counts as failed.
+pub fn run_hooks(
+    hooks: &HooksConfig,
+    kind: HookKind,
+    context: &HookContext,
+    active_tags: &HashSet<String>,
+) -> Vec<HookResult> {
+    let mut results = Vec::new();
+    for hook in kind.commands(hooks) {
+        let error = match matches(hook, active_tags) {
+            Ok(true) => run_command(hook.command(), kind, context).err(),
+            Ok(false) => continue,
+            Err(e) => Some(format!("invalid tags `{}`: {e}", hook.tags_expr())),
+        };
+        results.push(HookResult {
+            package: context.package.to_string(),
+            kind,
+            command: hook.command().to_string(),
+            error,
+        });
+    }
+    results
+}
+
+fn run_command(
+    command: &str,
+    kind: HookKind,
+    context: &HookContext,
+) -> std::result::Result<(), String> {
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(context.package_dir)
+        .env("TOWBOAT_HOOK", kind.as_str())
+        .env("TOWBOAT_PACKAGE", context.package)
+        .env("TOWBOAT_STOW_DIR", context.stow_dir)
+        .env("TOWBOAT_TARGET_DIR", context.target_dir)
+        .env("TOWBOAT_CHANGED_FILES", join_paths(context.changed))
+        .env("TOWBOAT_UNLINKED_FILES", join_paths(context.unlinked))
+        .status()
+        .map_err(|e| format!("failed to start: {e}"))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(match status.code() {
+            Some(code) => format!("exit code {code}"),
+            None => "terminated by signal".to_string(),
+        })
+    }
+}
+
+fn join_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn tags(names: &[&str]) -> HashSet<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn tag_filter_selects_commands() {
+        let hooks: HooksConfig = toml::from_str(
+            r#"post_sync = ["echo always", { run = "echo mac", tags = "macos" }, { run = "echo linux", tags = "linux" }]"#,
+        )
+        .unwrap();
+
+        let selected = selected(&hooks, HookKind::PostSync, &tags(&["linux"])).unwrap();
+        let commands: Vec<_> = selected.iter().map(|h| h.command()).collect();
+        assert_eq!(commands, vec!["echo always", "echo linux"]);
+    }
+
+    #[test]
+    fn hooks_receive_context_and_report_failures() {
+        let dir = TempDir::new().unwrap();
+        let hooks: HooksConfig = toml::from_str(
+            r#"post_change = ["printf '%s|%s|%s' \"$TOWBOAT_HOOK\" \"$TOWBOAT_PACKAGE\" \"$TOWBOAT_CHANGED_FILES\" > out", "exit 3"]"#,
+        )
+        .unwrap();
+        let changed = vec![PathBuf::from("/home/u/.a"), PathBuf::from("/home/u/.b")];
+        let context = HookContext {
+            package: "bash",
+            package_dir: dir.path(),
+            stow_dir: dir.path(),
+            target_dir: Path::new("/home/u"),
+            changed: &changed,
+            unlinked: &[],
+        };
+
+        let results = run_hooks(&hooks, HookKind::PostChange, &context, &tags(&[]));
+
+        assert_eq!(results.len(), 2);
+        assert!(results[0].succeeded());
+        assert_eq!(results[1].error.as_deref(), Some("exit code 3"));
+        assert_eq!(
+            fs::read_to_string(dir.path().join("out")).unwrap(),
+            "post_change|bash|/home/u/.a\n/home/u/.b"
+        );
+    }
+
+    #[test]
+    fn invalid_tag_filter_fails_its_hook_only() {
+        let dir = TempDir::new().unwrap();
+        let hooks: HooksConfig = toml::from_str(
+            r#"post_sync = [{ run = "touch skipped", tags = "linux &" }, "touch ran"]"#,
+        )
+        .unwrap();
+        let context = HookContext {
+            package: "bash",
+            package_dir: dir.path(),
+            stow_dir: dir.path(),
+            target_dir: dir.path(),
+            changed: &[],
+            unlinked: &[],
+        };
+
+        let results = run_hooks(&hooks, HookKind::PostSync, &context, &tags(&["linux"]));
+
+        assert_eq!(results.len(), 2);
+        assert!(
+            results[0]
+                .error
+                .as_deref()
+                .unwrap()
+                .starts_with("invalid tags `linux &`")
+        );
+        assert!(results[1].succeeded());
+        assert!(!dir.path().join("skipped").exists());
+        assert!(dir.path().join("ran").exists());
+    }
+}
@@ -3,14 +3,26 @@
 //! The lock file tracks the state of all deployed files:
 //! - Source hash and resolved hash enable three-way drift detection
 //! - Tags matched at deployment time
+//! - Input fingerprint: the tags and variables that shaped each resolved file
 //! - Symlink target path
+//! - Size, mtime and inode of source and resolved files, to skip re-hashing
+//!   files that weren't touched since the last sync
+//! - Hashes of run-once / run-on-change scripts that ran
+//!
+//! Entries are looked up through an index by (package, source) and by target,
+//! built on first use and kept up to date by the mutating methods.
 
+use std::collections::HashMap;
 use std::path::Path;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::config::scripts::ScriptMode;
 use crate::error::{Result, TowboatError};
+use crate::resolve::Inputs;
 
 /// The lock file structure.
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -25,7 +37,78 @@ pub struct LockFile {
 
     /// Per-file lock entries.
     #[serde(default)]
-    pub files: Vec<LockEntry>,
+    files: Vec<LockEntry>,
+
+    /// Scripts that ran successfully.
+    #[serde(default)]
+    pub scripts: Vec<ScriptEntry>,
+
+    /// Lookup index over `files`, built lazily.
+    #[serde(skip)]
+    index: OnceLock<LockIndex>,
+
+    /// Modification time of the lock file when it was loaded, in nanoseconds.
+    #[serde(skip)]
+    saved_at_ns: Option<i64>,
+}
+
+/// Positions in [`LockFile::files`] by package and source, and by target.
+#[derive(Debug, Clone, Default)]
+struct LockIndex {
+    by_source: HashMap<String, HashMap<String, usize>>,
+    by_target: HashMap<String, usize>,
+}
+
+impl LockIndex {
+    fn build(files: &[LockEntry]) -> Self {
+        let mut index = Self::default();
+        for (i, entry) in files.iter().enumerate() {
+            index.insert(entry, i);
+        }
+        index
+    }
+
+    fn insert(&mut self, entry: &LockEntry, i: usize) {
+        self.by_source
+            .entry(entry.package.clone())
+            .or_default()
+            .insert(entry.source.clone(), i);
+        self.by_target.entry(entry.target.clone()).or_insert(i);
+    }
+}
+
+/// Size, modification time and inode of a file.
+///
+/// When both the source and the resolved file still match the stats recorded at
+/// the last sync, their hashes are assumed unchanged and the files aren't read.
+/// Fields are `i64` because TOML integers are signed; they are only compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FileStat {
+    pub size: i64,
+    /// Nanoseconds since the Unix epoch.
+    pub mtime_ns: i64,
+    pub inode: i64,
+}
+
+impl FileStat {
+    /// Stat `path`, following symlinks; `None` if it doesn't exist.
+    pub fn of(path: &Path) -> Option<FileStat> {
+        let metadata = std::fs::metadata(path).ok()?;
+        #[cfg(unix)]
+        let inode = std::os::unix::fs::MetadataExt::ino(&metadata) as i64;
+        #[cfg(not(unix))]
+        let inode = 0;
+        Some(FileStat {
+            size: metadata.len() as i64,
+            mtime_ns: metadata.modified().ok().map_or(0, nanos_since_epoch),
+            inode,
+        })
+    }
+}
+
+fn nanos_since_epoch(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as i64)
 }
 
 fn default_version() -> u32 {
@@ -38,6 +121,9 @@ impl Default for LockFile {
             version: 1,
             last_sync: None,
             files: Vec::new(),
+            scripts: Vec::new(),
+            index: OnceLock::new(),
+            saved_at_ns: None,
         }
     }
 }
@@ -63,10 +149,39 @@ pub struct LockEntry {
     /// Tag expression strings that matched when this file was included.
     #[serde(default)]
     pub tags_matched: Vec<String>,
+
+    /// Tags and variables that shaped the resolved file (absent in older locks).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inputs: Option<Inputs>,
+
+    /// Stat of the source file when it was last hashed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_stat: Option<FileStat>,
+
+    /// Stat of the resolved file when it was last hashed or written.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_stat: Option<FileStat>,
+}
+
+/// A script run recorded in the lock file.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ScriptEntry {
+    /// Script path relative to stow directory (e.g. "scripts/install.sh").
+    pub path: String,
+
+    /// Run mode at the time of the last run.
+    pub mode: ScriptMode,
+
+    /// SHA256 hash of the resolved script that ran.
+    pub resolved_hash: String,
+
+    /// When the script last ran successfully.
+    pub last_run: DateTime<Utc>,
 }
 
 /// Drift state for a deployed file.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum FileState {
     /// Source and resolved hashes match lock — nothing to do.
     UpToDate,
@@ -82,6 +197,55 @@ pub enum FileState {
     Stale,
     /// File is new (not in lock).
     New,
+    /// Target exists but isn't the symlink towboat would create.
+    Foreign,
+    /// Symlink points into the stow dir this one was moved or copied from.
+    Moved,
+}
+
+impl FileState {
+    /// Every state, in display order.
+    pub const ALL: [FileState; 9] = [
+        FileState::UpToDate,
+        FileState::SourceChanged,
+        FileState::Drifted,
+        FileState::Conflict,
+        FileState::Broken,
+        FileState::Stale,
+        FileState::New,
+        FileState::Foreign,
+        FileState::Moved,
+    ];
+
+    /// Stable machine-readable name (matches the JSON serialization).
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileState::UpToDate => "up_to_date",
+            FileState::SourceChanged => "source_changed",
+            FileState::Drifted => "drifted",
+            FileState::Conflict => "conflict",
+            FileState::Broken => "broken",
+            FileState::Stale => "stale",
+            FileState::New => "new",
+            FileState::Foreign => "foreign",
+            FileState::Moved => "moved",
+        }
+    }
+
+    /// Short human-readable name, as used in summaries.
+    pub fn label(&self) -> &'static str {
+        match self {
+            FileState::UpToDate => "up to date",
+            FileState::SourceChanged => "source changed",
+            FileState::Drifted => "drifted",
+            FileState::Conflict => "conflict",
+            FileState::Broken => "broken",
+            FileState::Stale => "stale",
+            FileState::New => "new",
+            FileState::Foreign => "foreign",
+            FileState::Moved => "moved",
+        }
+    }
 }
 
 impl LockFile {
@@ -92,7 +256,10 @@ impl LockFile {
         }
 
         let content = std::fs::read_to_string(path)?;
-        toml::from_str(&content).map_err(|e| TowboatError::LockCorrupt(e.to_string()))
+        let mut lock: Self =
+            toml::from_str(&content).map_err(|e| TowboatError::LockCorrupt(e.to_string()))?;
+        lock.saved_at_ns = FileStat::of(path).map(|stat| stat.mtime_ns);
+        Ok(lock)
     }
 
     /// Save the lock file to disk.
@@ -106,16 +273,25 @@ impl LockFile {
         Ok(())
     }
 
+    /// All file entries, in the order they were recorded.
+    pub fn entries(&self) -> &[LockEntry] {
+        &self.files
+    }
+
+    fn index(&self) -> &LockIndex {
+        self.index.get_or_init(|| LockIndex::build(&self.files))
+    }
+
     /// Find a lock entry by package and source path.
     pub fn find(&self, package: &str, source: &str) -> Option<&LockEntry> {
-        self.files
-            .iter()
-            .find(|e| e.package == package && e.source == source)
+        let i = *self.index().by_source.get(package)?.get(source)?;
+        Some(&self.files[i])
     }
 
     /// Find a lock entry by target path.
     pub fn find_by_target(&self, target: &str) -> Option<&LockEntry> {
-        self.files.iter().find(|e| e.target == target)
+        let i = *self.index().by_target.get(target)?;
+        Some(&self.files[i])
     }
 
     /// Get all entries for a specific package.
@@ -125,19 +301,90 @@ impl LockFile {
 
     /// Remove all entries for a package.
     pub fn remove_package(&mut self, package: &str) {
-        self.files.retain(|e| e.package != package);
+        self.retain(|e| e.package != package);
+    }
+
+    /// Keep only the entries matching `keep`.
+    pub fn retain(&mut self, keep: impl FnMut(&LockEntry) -> bool) {
+        self.files.retain(keep);
+        self.index = OnceLock::new();
+    }
+
+    /// Record fresh stats for an entry whose hashes were just verified.
+    pub fn record_stats(
+        &mut self,
+        package: &str,
+        source: &str,
+        source_stat: FileStat,
+        resolved_stat: FileStat,
+    ) {
+        let Some(i) = self
+            .index()
+            .by_source
+            .get(package)
+            .and_then(|sources| sources.get(source))
+            .copied()
+        else {
+            return;
+        };
+        self.files[i].source_stat = Some(source_stat);
+        self.files[i].resolved_stat = Some(resolved_stat);
+    }
+
+    /// Whether `entry`'s source and resolved files are provably untouched since
+    /// their hashes were recorded.
+    ///
+    /// Both stats must match, and both files must predate the lock file itself:
+    /// a file written in the same clock tick as the lock could have changed after
+    /// it was stat'ed without changing its mtime.
+    pub fn stats_unchanged(
+        &self,
+        entry: &LockEntry,
+        source_stat: Option<FileStat>,
+        resolved_stat: Option<FileStat>,
+    ) -> bool {
+        let Some(saved_at) = self.saved_at_ns else {
+            return false;
+        };
+        let trusted = |recorded: Option<FileStat>, current: Option<FileStat>| {
+            recorded.is_some_and(|r| current == Some(r) && r.mtime_ns < saved_at)
+        };
+        trusted(entry.source_stat, source_stat) && trusted(entry.resolved_stat, resolved_stat)
+    }
+
+    /// Find the last recorded run of a script.
+    pub fn find_script(&self, path: &str) -> Option<&ScriptEntry> {
+        self.scripts.iter().find(|e| e.path == path)
+    }
+
+    /// Record a script run, replacing any earlier run of the same path.
+    pub fn upsert_script(&mut self, entry: ScriptEntry) {
+        self.scripts.retain(|e| e.path != entry.path);
+        self.scripts.push(entry);
     }
 
     /// Upsert a lock entry (update if exists, insert if new).
     pub fn upsert(&mut self, entry: LockEntry) {
-        if let Some(existing) = self
-            .files
-            .iter_mut()
-            .find(|e| e.package == entry.package && e.source == entry.source)
-        {
-            *existing = entry;
-        } else {
-            self.files.push(entry);
+        let existing = self
+            .index()
+            .by_source
+            .get(&entry.package)
+            .and_then(|sources| sources.get(&entry.source))
+            .copied();
+        match existing {
+            Some(i) => {
+                if self.files[i].target != entry.target {
+                    self.index = OnceLock::new();
+                }
+                self.files[i] = entry;
+            }
+            None => {
+                let i = self.files.len();
+                if let Some(index) = self.index.get_mut() {
+                    index.insert(&entry, i);
+                }
+                self.files.push(entry);
+            }
         }
     }
 }
@@ -145,9 +392,40 @@ impl LockFile {
 impl LockEntry {
     /// Determine the drift state of this entry given current hashes.
     pub fn state(&self, current_source_hash: &str, current_resolved_hash: &str) -> FileState {
-        let source_changed = current_source_hash != self.source_hash;
-        let resolved_changed = current_resolved_hash != self.resolved_hash;
+        Self::classify(
+            current_source_hash != self.source_hash,
+            current_resolved_hash != self.resolved_hash,
+        )
+    }
+
+    /// Like [`state`](Self::state), but changed inputs count as a source change.
+    ///
+    /// Entries from older locks without recorded inputs compare on hashes only.
+    pub fn state_with_inputs(
+        &self,
+        current_source_hash: &str,
+        current_resolved_hash: &str,
+        current_inputs: &Inputs,
+    ) -> FileState {
+        let inputs_changed = self
+            .inputs
+            .as_ref()
+            .is_some_and(|inputs| inputs != current_inputs);
+        Self::classify(
+            current_source_hash != self.source_hash || inputs_changed,
+            current_resolved_hash != self.resolved_hash,
+        )
+    }
 
+    /// Why the inputs changed since the last sync; empty if unchanged or unrecorded.
+    pub fn input_changes(&self, current_inputs: &Inputs) -> Vec<String> {
+        self.inputs
+            .as_ref()
+            .map(|inputs| inputs.changes(current_inputs))
+            .unwrap_or_default()
+    }
+
+    fn classify(source_changed: bool, resolved_changed: bool) -> FileState {
         match (source_changed, resolved_changed) {
             (false, false) => FileState::UpToDate,
             (true, false) => FileState::SourceChanged,
@@ -170,6 +448,9 @@ mod tests {
             resolved_hash: "def".into(),
             target: ".bashrc".into(),
             tags_matched: vec!["linux".into()],
+            inputs: None,
+            source_stat: None,
+            resolved_stat: None,
         };
         assert_eq!(entry.state("abc", "def"), FileState::UpToDate);
     }
@@ -183,6 +464,9 @@ mod tests {
             resolved_hash: "def".into(),
             target: ".bashrc".into(),
             tags_matched: vec![],
+            inputs: None,
+            source_stat: None,
+            resolved_stat: None,
         };
         assert_eq!(entry.state("xyz", "def"), FileState::SourceChanged);
     }
@@ -196,6 +480,9 @@ mod tests {
             resolved_hash: "def".into(),
             target: ".bashrc".into(),
             tags_matched: vec![],
+            inputs: None,
+            source_stat: None,
+            resolved_stat: None,
         };
         assert_eq!(entry.state("abc", "xyz"), FileState::Drifted);
     }
@@ -209,10 +496,96 @@ mod tests {
             resolved_hash: "def".into(),
             target: ".bashrc".into(),
             tags_matched: vec![],
+            inputs: None,
+            source_stat: None,
+            resolved_stat: None,
         };
         assert_eq!(entry.state("xyz", "uvw"), FileState::Conflict);
     }
 
+    #[test]
+    fn lock_entry_state_with_changed_inputs() {
+        let locked = Inputs {
+            variables: [("email".to_string(), "a@b".to_string())].into(),
+            ..Default::default()
+        };
+        let mut entry = LockEntry {
+            package: "git".into(),
+            source: "git/.gitconfig".into(),
+            source_hash: "abc".into(),
+            resolved_hash: "def".into(),
+            target: ".gitconfig".into(),
+            tags_matched: vec![],
+            inputs: Some(locked.clone()),
+            source_stat: None,
+            resolved_stat: None,
+        };
+        let current = Inputs {
+            variables: [("email".to_string(), "c@d".to_string())].into(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            entry.state_with_inputs("abc", "def", &locked),
+            FileState::UpToDate
+        );
+        assert_eq!(
+            entry.state_with_inputs("abc", "def", &current),
+            FileState::SourceChanged
+        );
+        assert_eq!(
+            entry.state_with_inputs("abc", "xyz", &current),
+            FileState::Conflict
+        );
+        assert_eq!(
+            entry.input_changes(&current),
+            vec!["variable `email` changed"]
+        );
+
+        // Older locks without inputs only compare hashes
+        entry.inputs = None;
+        assert_eq!(
+            entry.state_with_inputs("abc", "def", &current),
+            FileState::UpToDate
+        );
+    }
+
+    #[test]
+    fn lock_entry_inputs_roundtrip() {
+        let lock = LockFile {
+            files: vec![LockEntry {
+                package: "git".into(),
+                source: "git/.gitconfig".into(),
+                source_hash: "abc".into(),
+                resolved_hash: "def".into(),
+                target: ".gitconfig".into(),
+                tags_matched: vec![],
+                inputs: Some(Inputs {
+                    tags: [("work".to_string(), true)].into(),
+                    variables: [("email".to_string(), "a@b".to_string())].into(),
+                }),
+                source_stat: Some(FileStat {
+                    size: 3,
+                    mtime_ns: 1_700_000_000_000_000_000,
+                    inode: 42,
+                }),
+                resolved_stat: None,
+            }],
+            ..Default::default()
+        };
+        let serialized = toml::to_string_pretty(&lock).unwrap();
+        let deserialized: LockFile = toml::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.files[0].inputs, lock.files[0].inputs);
+    }
+
+    #[test]
+    fn file_state_as_str_matches_serde() {
+        for state in FileState::ALL {
+            let json = serde_json::to_string(&state).unwrap();
+            assert_eq!(json, format!("\"{}\"", state.as_str()));
+        }
+    }
+
     #[test]
     fn lock_file_upsert_insert() {
         let mut lock = LockFile::default();
@@ -223,6 +596,9 @@ mod tests {
             resolved_hash: "def".into(),
             target: ".bashrc".into(),
             tags_matched: vec![],
+            inputs: None,
+            source_stat: None,
+            resolved_stat: None,
         });
         assert_eq!(lock.files.len(), 1);
     }
@@ -237,6 +613,9 @@ mod tests {
             resolved_hash: "def".into(),
             target: ".bashrc".into(),
             tags_matched: vec![],
+            inputs: None,
+            source_stat: None,
+            resolved_stat: None,
         });
         lock.upsert(LockEntry {
             package: "bash".into(),
@@ -245,6 +624,9 @@ mod tests {
             resolved_hash: "uvw".into(),
             target: ".bashrc".into(),
             tags_matched: vec![],
+            inputs: None,
+            source_stat: None,
+            resolved_stat: None,
         });
         assert_eq!(lock.files.len(), 1);
         assert_eq!(lock.files[0].source_hash, "xyz");
@@ -261,6 +643,9 @@ mod tests {
                     resolved_hash: "def".into(),
                     target: ".bashrc".into(),
                     tags_matched: vec![],
+                    inputs: None,
+                    source_stat: None,
+                    resolved_stat: None,
                 },
                 LockEntry {
                     package: "vim".into(),
@@ -269,6 +654,9 @@ mod tests {
                     resolved_hash: "jkl".into(),
                     target: ".vimrc".into(),
                     tags_matched: vec![],
+                    inputs: None,
+                    source_stat: None,
+                    resolved_stat: None,
                 },
             ],
             ..Default::default()
@@ -278,6 +666,64 @@ mod tests {
         assert!(lock.find("bash", "nonexistent").is_none());
     }
 
+    fn entry(package: &str, source: &str, target: &str) -> LockEntry {
+        LockEntry {
+            package: package.into(),
+            source: source.into(),
+            source_hash: "abc".into(),
+            resolved_hash: "def".into(),
+            target: target.into(),
+            tags_matched: vec![],
+            inputs: None,
+            source_stat: None,
+            resolved_stat: None,
+        }
+    }
+
+    #[test]
+    fn lock_index_follows_mutations() {
+        let mut lock = LockFile::default();
+        lock.upsert(entry("bash", "bash/.bashrc", ".bashrc"));
+        assert!(lock.find_by_target(".bashrc").is_some());
+
+        // Insert after the index was built, then retarget
+        lock.upsert(entry("vim", "vim/.vimrc", ".vimrc"));
+        assert_eq!(lock.find("vim", "vim/.vimrc").unwrap().target, ".vimrc");
+        lock.upsert(entry("vim", "vim/.vimrc", ".config/vim/vimrc"));
+        assert!(lock.find_by_target(".vimrc").is_none());
+        assert!(lock.find_by_target(".config/vim/vimrc").is_some());
+
+        lock.remove_package("bash");
+        assert!(lock.find("bash", "bash/.bashrc").is_none());
+        assert!(lock.find("vim", "vim/.vimrc").is_some());
+        assert!(lock.find("vim", "bash/.bashrc").is_none());
+    }
+
+    #[test]
+    fn stats_are_trusted_only_before_the_lock_was_saved() {
+        let stat = |mtime_ns| FileStat {
+            size: 10,
+            mtime_ns,
+            inode: 7,
+        };
+        let mut tracked = entry("bash", "bash/.bashrc", ".bashrc");
+        tracked.source_stat = Some(stat(100));
+        tracked.resolved_stat = Some(stat(200));
+
+        let mut lock = LockFile::default();
+        // Never saved: nothing is trusted
+        assert!(!lock.stats_unchanged(&tracked, Some(stat(100)), Some(stat(200))));
+
+        lock.saved_at_ns = Some(300);
+        assert!(lock.stats_unchanged(&tracked, Some(stat(100)), Some(stat(200))));
+        assert!(!lock.stats_unchanged(&tracked, Some(stat(101)), Some(stat(200))));
+        assert!(!lock.stats_unchanged(&tracked, Some(stat(100)), None));
+
+        // Written in the same tick as the lock: could have changed unnoticed
+        lock.saved_at_ns = Some(200);
+        assert!(!lock.stats_unchanged(&tracked, Some(stat(100)), Some(stat(200))));
+    }
+
     #[test]
     fn lock_file_remove_package() {
         let mut lock = LockFile {
@@ -289,6 +735,9 @@ mod tests {
                     resolved_hash: "def".into(),
                     target: ".bashrc".into(),
                     tags_matched: vec![],
+                    inputs: None,
+                    source_stat: None,
+                    resolved_stat: None,
                 },
                 LockEntry {
                     package: "vim".into(),
@@ -297,6 +746,9 @@ mod tests {
                     resolved_hash: "jkl".into(),
                     target: ".vimrc".into(),
                     tags_matched: vec![],
+                    inputs: None,
+                    source_stat: None,
+                    resolved_stat: None,
                 },
             ],
             ..Default::default()
@@ -318,7 +770,11 @@ mod tests {
                 resolved_hash: "def456".into(),
                 target: ".bashrc".into(),
                 tags_matched: vec!["macos".into(), "laptop".into()],
+                inputs: None,
+                source_stat: None,
+                resolved_stat: None,
             }],
+            ..Default::default()
         };
         let serialized = toml::to_string_pretty(&lock).unwrap();
         let deserialized: LockFile = toml::from_str(&serialized).unwrap();
@@ -326,4 +782,39 @@ mod tests {
         assert_eq!(deserialized.files.len(), 1);
         assert_eq!(deserialized.files[0].source_hash, "abc123");
     }
+
+    #[test]
+    fn script_entries_roundtrip_and_upsert() {
+        let mut lock = LockFile::default();
+        lock.upsert_script(ScriptEntry {
+            path: "scripts/a.sh".into(),
+            mode: ScriptMode::OnChange,
+            resolved_hash: "one".into(),
+            last_run: Utc::now(),
+        });
+        lock.upsert_script(ScriptEntry {
+            path: "scripts/a.sh".into(),
+            mode: ScriptMode::OnChange,
+            resolved_hash: "two".into(),
+            last_run: Utc::now(),
+        });
+
+        let serialized = toml::to_string_pretty(&lock).unwrap();
+        let deserialized: LockFile = toml::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.scripts.len(), 1);
+        assert_eq!(
+            deserialized
+                .find_script("scripts/a.sh")
+                .unwrap()
+                .resolved_hash,
+            "two"
+        );
+        assert_eq!(deserialized.scripts[0].mode, ScriptMode::OnChange);
+    }
+
+    #[test]
+    fn lock_without_scripts_still_loads() {
+        let lock: LockFile = toml::from_str("version = 1\nfiles = []\n").unwrap();
+        assert!(lock.scripts.is_empty());
+    }
 }
@@ -1,2 +1,8 @@
+pub mod collision;
+pub mod generations;
+pub mod history;
+pub mod hooks;
 pub mod lock;
+pub mod scripts;
+pub mod state;
 pub mod symlink;
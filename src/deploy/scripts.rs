@@ -0,0 +1,277 @@
+//! Run-once and run-on-change scripts.
+//!
+//! Scripts come from `[[scripts]]` in the manifest and from the `scripts/`
+//! directory of the stow root, where files named `run_once_*` or
+//! `run_onchange_*` are picked up automatically. Each script is resolved like a
+//! dotfile (tag sections + templates) into the state dir's `scripts/` and executed
+//! from there; the hash of the resolved script is recorded in the lock.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::process::Command;
+
+use crate::config::scripts::{ScriptConfig, ScriptMode};
+use crate::deploy::lock::LockFile;
+use crate::error::Result;
+use crate::resolve::resolver::{compute_hash, resolve_file};
+use crate::tags::matcher;
+
+/// Directory (relative to the stow root) scanned for conventional scripts.
+pub const SCRIPTS_DIR: &str = "scripts";
+
+const ONCE_PREFIX: &str = "run_once_";
+const ONCHANGE_PREFIX: &str = "run_onchange_";
+
+/// A script to consider on sync.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Script {
+    /// Path relative to the stow directory.
+    pub path: String,
+    pub mode: ScriptMode,
+    /// Tag expression filter (empty means "always").
+    pub tags: String,
+}
+
+/// A script resolved for the active tags and variables.
+#[derive(Debug, Clone)]
+pub struct ResolvedScript {
+    pub script: Script,
+    pub content: String,
+    pub hash: String,
+}
+
+impl ResolvedScript {
+    /// Whether the script should run on the next sync.
+    pub fn is_pending(&self, lock: &LockFile) -> bool {
+        match (self.script.mode, lock.find_script(&self.script.path)) {
+            (_, None) => true,
+            (ScriptMode::Once, Some(_)) => false,
+            (ScriptMode::OnChange, Some(entry)) => entry.resolved_hash != self.hash,
+        }
+    }
+}
+
+/// Collect `[[scripts]]` entries, then conventional scripts in `scripts/` (sorted
+/// by name) that aren't declared explicitly.
+pub fn collect(stow_dir: &Path, configs: &[ScriptConfig]) -> Result<Vec<Script>> {
+    let mut scripts: Vec<Script> = configs
+        .iter()
+        .map(|c| Script {
+            path: c.path.clone(),
+            mode: c.run,
+            tags: c.tags.to_expr_string(),
+        })
+        .collect();
+
+    let dir = stow_dir.join(SCRIPTS_DIR);
+    if dir.is_dir() {
+        let declared: HashSet<String> = scripts.iter().map(|s| s.path.clone()).collect();
+        let mut found = Vec::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let mode = if name.starts_with(ONCE_PREFIX) {
+                ScriptMode::Once
+            } else if name.starts_with(ONCHANGE_PREFIX) {
+                ScriptMode::OnChange
+            } else {
+                continue;
+            };
+            let path = format!("{SCRIPTS_DIR}/{name}");
+            if !declared.contains(&path) {
+                found.push(Script {
+                    path,
+                    mode,
+                    tags: String::new(),
+                });
+            }
+        }
+        found.sort_by(|a, b| a.path.cmp(&b.path));
+        scripts.extend(found);
+    }
+
+    Ok(scripts)
+}
+
+/// Resolve every script whose tag filter matches the active tags.
+pub fn resolve(
+    stow_dir: &Path,
+    scripts: &[Script],
+    active_tags: &HashSet<String>,
+    variables: &HashMap<String, String>,
+) -> Result<Vec<ResolvedScript>> {
+    let mut resolved = Vec::new();
+    for script in scripts {
+        resolved.extend(resolve_one(stow_dir, script, active_tags, variables)?);
+    }
+    Ok(resolved)
+}
+
+/// Resolve one script, or `None` if its tag filter doesn't match.
+pub fn resolve_one(
+    stow_dir: &Path,
+    script: &Script,
+    active_tags: &HashSet<String>,
+    variables: &HashMap<String, String>,
+) -> Result<Option<ResolvedScript>> {
+    if !script.tags.is_empty() && !matcher::parse(&script.tags)?.evaluate(active_tags) {
+        return Ok(None);
+    }
+    let (content, _had_tags) = resolve_file(&stow_dir.join(&script.path), active_tags, variables)?;
+    Ok(Some(ResolvedScript {
+        script: script.clone(),
+        hash: compute_hash(&content),
+        content,
+    }))
+}
+
+/// Write the resolved script under `scripts_dir` and run it in `target_dir`.
+///
+/// Scripts with a `#!` line are executed directly, others through `sh`.
+pub fn run(
+    resolved: &ResolvedScript,
+    scripts_dir: &Path,
+    stow_dir: &Path,
+    target_dir: &Path,
+) -> std::result::Result<(), String> {
+    let path = scripts_dir.join(&resolved.script.path);
+    write_executable(&path, &resolved.content).map_err(|e| format!("failed to write: {e}"))?;
+
+    let mut command = if resolved.content.starts_with("#!") {
+        Command::new(&path)
+    } else {
+        let mut sh = Command::new("sh");
+        sh.arg(&path);
+        sh
+    };
+    let status = command
+        .current_dir(target_dir)
+        .env("TOWBOAT_STOW_DIR", stow_dir)
+        .env("TOWBOAT_TARGET_DIR", target_dir)
+        .env("TOWBOAT_SCRIPT", &resolved.script.path)
+        .env("TOWBOAT_SCRIPT_MODE", resolved.script.mode.as_str())
+        .status()
+        .map_err(|e| format!("failed to start: {e}"))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(match status.code() {
+            Some(code) => format!("exit code {code}"),
+            None => "terminated by signal".to_string(),
+        })
+    }
+}
+
+fn write_executable(path: &Path, content: &str) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, content)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deploy::lock::ScriptEntry;
+    use chrono::Utc;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn tags(names: &[&str]) -> HashSet<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn script(path: &str, mode: ScriptMode) -> Script {
+        Script {
+            path: path.to_string(),
+            mode,
+            tags: String::new(),
+        }
+    }
+
+    #[test]
+    fn collect_declared_and_conventional_scripts() {
+        let dir = TempDir::new().unwrap();
+        let scripts_dir = dir.path().join(SCRIPTS_DIR);
+        fs::create_dir_all(&scripts_dir).unwrap();
+        fs::write(scripts_dir.join("run_once_b.sh"), "").unwrap();
+        fs::write(scripts_dir.join("run_onchange_a.sh"), "").unwrap();
+        fs::write(scripts_dir.join("helper.sh"), "").unwrap();
+        fs::write(scripts_dir.join("run_once_declared.sh"), "").unwrap();
+
+        let configs: Vec<ScriptConfig> = vec![ScriptConfig {
+            path: "scripts/run_once_declared.sh".to_string(),
+            run: ScriptMode::OnChange,
+            tags: crate::config::package::TagsSpec::Expr("linux".to_string()),
+        }];
+
+        let scripts = collect(dir.path(), &configs).unwrap();
+        assert_eq!(
+            scripts,
+            vec![
+                Script {
+                    path: "scripts/run_once_declared.sh".to_string(),
+                    mode: ScriptMode::OnChange,
+                    tags: "linux".to_string(),
+                },
+                script("scripts/run_once_b.sh", ScriptMode::Once),
+                script("scripts/run_onchange_a.sh", ScriptMode::OnChange),
+            ]
+        );
+    }
+
+    #[test]
+    fn resolve_applies_tags_and_templates() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("a.sh"),
+            "# {linux-\necho ${{ name }}\n# -linux}\n",
+        )
+        .unwrap();
+        let mut skipped = script("a.sh", ScriptMode::Once);
+        skipped.tags = "macos".to_string();
+        let variables = HashMap::from([("name".to_string(), "box".to_string())]);
+
+        let resolved = resolve(
+            dir.path(),
+            &[script("a.sh", ScriptMode::Once), skipped],
+            &tags(&["linux"]),
+            &variables,
+        )
+        .unwrap();
+
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].content, "echo box\n");
+    }
+
+    #[test]
+    fn pending_semantics() {
+        let resolved = |mode| ResolvedScript {
+            script: script("s.sh", mode),
+            content: String::new(),
+            hash: "new".to_string(),
+        };
+        let mut lock = LockFile::default();
+        assert!(resolved(ScriptMode::Once).is_pending(&lock));
+        assert!(resolved(ScriptMode::OnChange).is_pending(&lock));
+
+        lock.upsert_script(ScriptEntry {
+            path: "s.sh".to_string(),
+            mode: ScriptMode::OnChange,
+            resolved_hash: "old".to_string(),
+            last_run: Utc::now(),
+        });
+        assert!(!resolved(ScriptMode::Once).is_pending(&lock));
+        assert!(resolved(ScriptMode::OnChange).is_pending(&lock));
+    }
+}
@@ -0,0 +1,259 @@
+//! Where towboat keeps per-machine state: resolved files, the lock, script
+//! records, generations and history.
+//!
+//! `[system] state_dir` picks the directory. By default it is
+//! `$XDG_STATE_HOME/towboat/<repo-id>` (`~/.local/state/towboat/...` without
+//! `XDG_STATE_HOME`), so resolved secrets and host-specific output stay out of
+//! the dotfiles repo. Stow dirs from before `state_dir` kept all of this in
+//! `<stow>/.towboat/`; [`StateDir::migrate`] moves it.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::expand_tilde;
+use crate::config::manifest::{SystemConfig, SystemManifest};
+use crate::deploy::symlink;
+use crate::error::{Result, TowboatError};
+use crate::resolve::resolver::compute_hash;
+
+/// The state dir inside the stow dir used before `[system] state_dir`.
+pub const LEGACY_DIR: &str = ".towboat";
+
+/// File in the state dir naming the stow dir it was last synced from.
+const STOW_DIR_FILE: &str = "stow_dir";
+
+/// The state directory of one stow dir.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateDir {
+    root: PathBuf,
+    /// A directory still holding the state that belongs in `root`: the legacy
+    /// `<stow>/.towboat`, or one passed to [`StateDir::adopt`].
+    pending: Option<PathBuf>,
+}
+
+impl StateDir {
+    /// The state dir of `stow_dir` as configured by its `[system]` table.
+    pub fn new(stow_dir: &Path, system: &SystemConfig) -> Result<Self> {
+        let root = match &system.state_dir {
+            Some(dir) => stow_dir.join(expand_tilde(dir)),
+            None => default_root(stow_dir)?,
+        };
+        let legacy = stow_dir.join(LEGACY_DIR);
+        let pending = (legacy.is_dir() && legacy != root && !root.exists()).then_some(legacy);
+        Ok(Self { root, pending })
+    }
+
+    /// [`StateDir::new`] for commands that don't need the manifest otherwise.
+    /// When `towboat.toml` can't be loaded, only its `state_dir` is read, so a
+    /// mistake elsewhere in the file doesn't hide the state.
+    pub fn for_stow(stow_dir: &Path) -> Result<Self> {
+        let path = stow_dir.join("towboat.toml");
+        let system = SystemManifest::load(&path)
+            .map(|manifest| manifest.system)
+            .unwrap_or_else(|_| SystemConfig {
+                state_dir: configured_state_dir(&path),
+                ..Default::default()
+            });
+        Self::new(stow_dir, &system)
+    }
+
+    /// The directory holding the state: the legacy `.towboat/` until it has
+    /// been migrated, so read-only commands keep working before the next sync.
+    pub fn root(&self) -> &Path {
+        self.pending.as_deref().unwrap_or(&self.root)
+    }
+
+    /// Where the state lives once [`StateDir::migrate`] has run.
+    pub fn destination(&self) -> &Path {
+        &self.root
+    }
+
+    /// Take over the state in `dir`, e.g. the state dir of the stow dir's
+    /// previous location; [`StateDir::migrate`] moves it into place.
+    pub fn adopt(&mut self, dir: PathBuf) {
+        self.pending = Some(dir);
+    }
+
+    pub fn resolved_dir(&self) -> PathBuf {
+        self.root().join("resolved")
+    }
+
+    pub fn lock_path(&self) -> PathBuf {
+        self.root().join("towboat.lock")
+    }
+
+    pub fn scripts_dir(&self) -> PathBuf {
+        self.root().join("scripts")
+    }
+
+    /// Note which stow dir the state belongs to, so that after the stow dir
+    /// moves, the links still leading here can name it for `relocate --from`.
+    pub fn record_stow_dir(&self, stow_dir: &Path) -> Result<()> {
+        fs::create_dir_all(self.root())?;
+        fs::write(
+            self.root().join(STOW_DIR_FILE),
+            format!("{}\n", stow_dir.display()),
+        )?;
+        Ok(())
+    }
+
+    /// Move a legacy `<stow>/.towboat/` (or an adopted dir) to the configured
+    /// location, returning where it was. Links into it are left for `sync` or
+    /// `relocate` to re-point.
+    pub fn migrate(&mut self) -> Result<Option<PathBuf>> {
+        let Some(legacy) = self.pending.take() else {
+            return Ok(None);
+        };
+        if let Some(parent) = self.root.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        match fs::rename(&legacy, &self.root) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+                copy_tree(&legacy, &self.root)?;
+                fs::remove_dir_all(&legacy)?;
+            }
+            Err(e) => {
+                self.pending = Some(legacy);
+                return Err(e.into());
+            }
+        }
+        Ok(Some(legacy))
+    }
+}
+
+/// `<basename>-<hash of the path>`, so two clones of one repo don't share state.
+pub fn repo_id(stow_dir: &Path) -> String {
+    // A stow dir that has since moved can't be canonicalized; resolve `..`
+    // lexically so `--from ../dots` names the same dir as its absolute path
+    let stow_dir = fs::canonicalize(stow_dir)
+        .or_else(|_| std::path::absolute(stow_dir).map(|dir| symlink::normalize(&dir)))
+        .unwrap_or_else(|_| stow_dir.to_path_buf());
+    let name = stow_dir
+        .file_name()
+        .map_or("root".into(), |name| name.to_string_lossy());
+    let hash = compute_hash(&stow_dir.to_string_lossy());
+    format!("{name}-{}", &hash[..12])
+}
+
+/// The stow dir last synced into the state dir at `root`, if recorded.
+pub fn recorded_stow_dir(root: &Path) -> Option<PathBuf> {
+    let content = fs::read_to_string(root.join(STOW_DIR_FILE)).ok()?;
+    Some(PathBuf::from(content.trim_end_matches('\n')))
+}
+
+/// `[system] state_dir` of a manifest that is valid TOML but fails to load.
+fn configured_state_dir(manifest: &Path) -> Option<PathBuf> {
+    let table: toml::Table = fs::read_to_string(manifest).ok()?.parse().ok()?;
+    let dir = table.get("system")?.get("state_dir")?.as_str()?;
+    Some(PathBuf::from(dir))
+}
+
+/// `$XDG_STATE_HOME/towboat/<repo-id>`, or `~/.local/state/towboat/<repo-id>`.
+pub fn default_root(stow_dir: &Path) -> Result<PathBuf> {
+    let base = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+        .ok_or(TowboatError::NoStateDir)?;
+    Ok(base.join("towboat").join(repo_id(stow_dir)))
+}
+
+fn copy_tree(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let dest = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_tree(&entry.path(), &dest)?;
+        } else {
+            fs::copy(entry.path(), dest)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn system(state_dir: Option<&str>) -> SystemConfig {
+        SystemConfig {
+            state_dir: state_dir.map(PathBuf::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn configured_state_dir_is_relative_to_the_stow_dir() {
+        let stow = TempDir::new().unwrap();
+        let state = StateDir::new(stow.path(), &system(Some("state"))).unwrap();
+        assert_eq!(state.root(), stow.path().join("state"));
+        assert_eq!(state.lock_path(), stow.path().join("state/towboat.lock"));
+
+        let elsewhere = TempDir::new().unwrap();
+        let state = StateDir::new(stow.path(), &system(elsewhere.path().to_str())).unwrap();
+        assert_eq!(state.resolved_dir(), elsewhere.path().join("resolved"));
+    }
+
+    #[test]
+    fn for_stow_reads_state_dir_of_a_manifest_that_fails_to_load() {
+        let stow = TempDir::new().unwrap();
+        fs::write(
+            stow.path().join("towboat.toml"),
+            "[system]\nstate_dir = \"state\"\ntag = []\n",
+        )
+        .unwrap();
+        let state = StateDir::for_stow(stow.path()).unwrap();
+        assert_eq!(state.root(), stow.path().join("state"));
+    }
+
+    #[test]
+    fn stow_dir_is_recorded_in_the_state_dir() {
+        let stow = TempDir::new().unwrap();
+        let state = StateDir::new(stow.path(), &system(Some("state"))).unwrap();
+        assert_eq!(recorded_stow_dir(state.root()), None);
+        state.record_stow_dir(stow.path()).unwrap();
+        assert_eq!(recorded_stow_dir(state.root()).unwrap(), stow.path());
+    }
+
+    #[test]
+    fn repo_id_names_the_stow_dir_and_tells_clones_apart() {
+        let dir = TempDir::new().unwrap();
+        let (a, b) = (dir.path().join("a/dotfiles"), dir.path().join("b/dotfiles"));
+        let id = repo_id(&a);
+        assert!(id.starts_with("dotfiles-"), "{id}");
+        assert_eq!(id, repo_id(&a));
+        assert_ne!(id, repo_id(&b));
+        // Neither exists, so `..` is resolved without the filesystem
+        assert_eq!(id, repo_id(&dir.path().join("b/../a/./dotfiles")));
+    }
+
+    #[test]
+    fn legacy_dir_is_used_until_migrated() {
+        let stow = TempDir::new().unwrap();
+        let legacy = stow.path().join(LEGACY_DIR);
+        fs::create_dir_all(legacy.join("resolved/bash")).unwrap();
+        fs::write(legacy.join("resolved/bash/.bashrc"), "content").unwrap();
+        let target = TempDir::new().unwrap();
+        let root = target.path().join("state");
+
+        let mut state = StateDir::new(stow.path(), &system(root.to_str())).unwrap();
+        assert_eq!(state.root(), legacy);
+        assert_eq!(state.migrate().unwrap(), Some(legacy.clone()));
+        assert_eq!(state.root(), root);
+        assert!(!legacy.exists());
+        assert_eq!(
+            fs::read_to_string(root.join("resolved/bash/.bashrc")).unwrap(),
+            "content"
+        );
+        assert_eq!(state.migrate().unwrap(), None);
+
+        // `.towboat` configured explicitly is not a legacy dir
+        fs::create_dir_all(&legacy).unwrap();
+        let state = StateDir::new(stow.path(), &system(Some(LEGACY_DIR))).unwrap();
+        assert_eq!(state.root(), legacy);
+        assert!(state.pending.is_none());
+    }
+}
@@ -1,39 +1,110 @@
 //! Symlink CRUD: create, check, and remove symlinks from target to resolved files.
 
 use std::fs;
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 
+use crate::config::manifest::SystemConfig;
 use crate::error::{Result, TowboatError};
 
+/// How a link refers to its resolved file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LinkStyle {
+    /// `/home/me/.local/state/towboat/dotfiles-3f2a9c1b0d4e/resolved/bash/.bashrc`
+    #[default]
+    Absolute,
+    /// `.local/state/towboat/dotfiles-3f2a9c1b0d4e/resolved/bash/.bashrc`, from
+    /// the link's directory, so links survive moving the state and target dirs
+    /// together.
+    Relative,
+}
+
+impl LinkStyle {
+    /// The style configured by `[system] relative_links`.
+    pub fn of(system: &SystemConfig) -> Self {
+        if system.relative_links {
+            LinkStyle::Relative
+        } else {
+            LinkStyle::Absolute
+        }
+    }
+}
+
+/// The path stored in a link at `link_path` to `resolved_path`.
+pub fn link_target(resolved_path: &Path, link_path: &Path, style: LinkStyle) -> PathBuf {
+    match style {
+        LinkStyle::Absolute => resolved_path.to_path_buf(),
+        LinkStyle::Relative => {
+            // Compare real directories, so a symlinked home still resolves
+            let real = |path: &Path| {
+                fs::canonicalize(path)
+                    .or_else(|_| std::path::absolute(path))
+                    .unwrap_or_else(|_| path.to_path_buf())
+            };
+            let from = link_path.parent().map(real).unwrap_or_default();
+            let to = match (resolved_path.parent(), resolved_path.file_name()) {
+                (Some(parent), Some(name)) => real(parent).join(name),
+                _ => real(resolved_path),
+            };
+            relative_path(&from, &to)
+        }
+    }
+}
+
+/// `to` as seen from the directory `from`; both absolute.
+fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from: Vec<Component> = from.components().collect();
+    let to: Vec<Component> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut path = PathBuf::new();
+    for _ in common..from.len() {
+        path.push("..");
+    }
+    for component in &to[common..] {
+        path.push(component);
+    }
+    path
+}
+
 /// Create a symlink at `link_path` pointing to `resolved_path`.
 ///
 /// Creates parent directories as needed. Errors if the target already exists
 /// unless `force` is true.
 pub fn create_symlink(resolved_path: &Path, link_path: &Path, force: bool) -> Result<()> {
+    create_link(resolved_path, link_path, force, LinkStyle::Absolute)
+}
+
+/// [`create_symlink`], storing the link target in the given style.
+pub fn create_link(
+    resolved_path: &Path,
+    link_path: &Path,
+    force: bool,
+    style: LinkStyle,
+) -> Result<()> {
     if link_path.exists() || link_path.is_symlink() {
         if force {
-            remove_symlink(link_path)?;
+            // Replace only the link: pruning empty parents here would remove
+            // the directory the new link goes into, up to the target itself
+            fs::remove_file(link_path)?;
         } else {
             return Err(TowboatError::TargetExists(link_path.to_path_buf()));
         }
     }
 
-    // Ensure parent directory exists (must be after remove_symlink which may clean up parents)
+    // Ensure parent directory exists
     if let Some(parent) = link_path.parent() {
         fs::create_dir_all(parent)?;
     }
+    let target = link_target(resolved_path, link_path, style);
 
     #[cfg(unix)]
-    std::os::unix::fs::symlink(resolved_path, link_path).map_err(|e| {
-        TowboatError::SymlinkFailed {
-            link_source: resolved_path.to_path_buf(),
-            link_target: link_path.to_path_buf(),
-            reason: e.to_string(),
-        }
+    std::os::unix::fs::symlink(&target, link_path).map_err(|e| TowboatError::SymlinkFailed {
+        link_source: resolved_path.to_path_buf(),
+        link_target: link_path.to_path_buf(),
+        reason: e.to_string(),
     })?;
 
     #[cfg(windows)]
-    std::os::windows::fs::symlink_file(resolved_path, link_path).map_err(|e| {
+    std::os::windows::fs::symlink_file(&target, link_path).map_err(|e| {
         TowboatError::SymlinkFailed {
             link_source: resolved_path.to_path_buf(),
             link_target: link_path.to_path_buf(),
@@ -65,15 +136,70 @@ pub fn remove_symlink(path: &Path) -> Result<()> {
     Ok(())
 }
 
-/// Check if a symlink exists and points to the expected target.
+/// Check if a symlink exists and points to the expected target, whether it
+/// stores an absolute or a relative path.
 pub fn symlink_matches(link_path: &Path, expected_target: &Path) -> bool {
-    if !link_path.is_symlink() {
+    let Ok(target) = fs::read_link(link_path) else {
+        return false;
+    };
+    if target == expected_target {
+        return true;
+    }
+    if target.is_absolute() {
         return false;
     }
-    match fs::read_link(link_path) {
-        Ok(target) => target == expected_target,
-        Err(_) => false,
+    match (
+        fs::canonicalize(link_path),
+        fs::canonicalize(expected_target),
+    ) {
+        (Ok(actual), Ok(expected)) => actual == expected,
+        // Dangling: compare where the link would lead
+        _ => {
+            let parent = link_path.parent().unwrap_or(Path::new(""));
+            normalize(&parent.join(target)) == normalize(expected_target)
+        }
+    }
+}
+
+/// Check if a symlink points to the expected target and stores it in `style`,
+/// i.e. creating it again would change nothing.
+pub fn link_is_current(link_path: &Path, expected_target: &Path, style: LinkStyle) -> bool {
+    fs::read_link(link_path)
+        .is_ok_and(|target| target == link_target(expected_target, link_path, style))
+}
+
+/// If the link at `link_path` leads to `<somewhere>/resolved/<source>` other
+/// than `resolved_path`, return `<somewhere>`: the state dir it was deployed
+/// from before it (or the stow dir holding a legacy `.towboat/`) moved.
+pub fn old_state_dir(link_path: &Path, resolved_path: &Path, source: &str) -> Option<PathBuf> {
+    let target = fs::read_link(link_path).ok()?;
+    let target = normalize(&link_path.parent().unwrap_or(Path::new("")).join(target));
+    if target == normalize(resolved_path) || symlink_matches(link_path, resolved_path) {
+        return None;
+    }
+    let suffix = Path::new("resolved").join(source);
+    let mut state_dir = target.clone();
+    for _ in suffix.components() {
+        state_dir = state_dir.parent()?.to_path_buf();
+    }
+    (state_dir.join(&suffix) == target).then_some(state_dir)
+}
+
+/// Resolve `.` and `..` without touching the filesystem.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push("..");
+                }
+            }
+            other => normalized.push(other),
+        }
     }
+    normalized
 }
 
 /// Check if a symlink is broken (exists as symlink but target doesn't exist).
@@ -200,4 +326,54 @@ mod tests {
 
         assert!(link.is_symlink());
     }
+
+    #[test]
+    fn relative_links_are_computed_from_the_link_parent() {
+        assert_eq!(
+            relative_path(
+                Path::new("/home/me/.config/git"),
+                Path::new("/home/me/dotfiles/git/config")
+            ),
+            Path::new("../../dotfiles/git/config")
+        );
+
+        let dir = TempDir::new().unwrap();
+        let resolved = dir.path().join("stow/.towboat/resolved/git/config");
+        let link = dir.path().join("home/.config/git/config");
+        fs::create_dir_all(resolved.parent().unwrap()).unwrap();
+        fs::write(&resolved, "content").unwrap();
+        create_link(&resolved, &link, false, LinkStyle::Relative).unwrap();
+
+        assert_eq!(
+            fs::read_link(&link).unwrap(),
+            Path::new("../../../stow/.towboat/resolved/git/config")
+        );
+        assert_eq!(fs::read_to_string(&link).unwrap(), "content");
+        assert!(symlink_matches(&link, &resolved));
+        assert!(link_is_current(&link, &resolved, LinkStyle::Relative));
+        assert!(!link_is_current(&link, &resolved, LinkStyle::Absolute));
+    }
+
+    #[test]
+    fn old_state_dir_finds_links_into_a_moved_state_dir() {
+        let dir = TempDir::new().unwrap();
+        let old = dir.path().join("old");
+        let new = dir.path().join("new");
+        let resolved = new.join(".towboat/resolved/bash/.bashrc");
+        fs::create_dir_all(resolved.parent().unwrap()).unwrap();
+        fs::write(&resolved, "content").unwrap();
+        let link = dir.path().join(".bashrc");
+
+        create_symlink(&old.join(".towboat/resolved/bash/.bashrc"), &link, false).unwrap();
+        assert_eq!(
+            old_state_dir(&link, &resolved, "bash/.bashrc"),
+            Some(old.join(".towboat"))
+        );
+        assert_eq!(old_state_dir(&link, &resolved, "git/.gitconfig"), None);
+
+        create_symlink(&resolved, &link, true).unwrap();
+        assert_eq!(old_state_dir(&link, &resolved, "bash/.bashrc"), None);
+        create_symlink(&dir.path().join("elsewhere"), &link, true).unwrap();
+        assert_eq!(old_state_dir(&link, &resolved, "bash/.bashrc"), None);
+    }
 }
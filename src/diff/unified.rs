@@ -0,0 +1,205 @@
+//! Line-based unified diffs between two versions of a file.
+//!
+//! Uses the patience algorithm (via `similar`) and groups changes into hunks
+//! with a configurable number of context lines, like `diff -u`.
+
+use serde::Serialize;
+use similar::{Algorithm, ChangeTag, TextDiff};
+
+/// Default number of unchanged lines shown around each change.
+pub const DEFAULT_CONTEXT: usize = 3;
+
+/// Marker line emitted after a line that has no trailing newline.
+const NO_NEWLINE_MARKER: &str = "\\ No newline at end of file";
+
+/// A contiguous group of changes with surrounding context.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Hunk {
+    /// First line of the hunk in the old content (1-based; 0 when `old_lines` is 0).
+    pub old_start: usize,
+    pub old_lines: usize,
+    /// First line of the hunk in the new content (1-based; 0 when `new_lines` is 0).
+    pub new_start: usize,
+    pub new_lines: usize,
+    /// Lines prefixed with ` ` (context), `-` (removed) or `+` (added), without
+    /// trailing newlines.
+    pub lines: Vec<String>,
+}
+
+impl Hunk {
+    /// The `@@ -a,b +c,d @@` header for this hunk.
+    pub fn header(&self) -> String {
+        format!(
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_lines, self.new_start, self.new_lines
+        )
+    }
+}
+
+/// Compute the hunks that turn `old` into `new`.
+///
+/// Returns an empty list when the contents are identical.
+pub fn hunks(old: &str, new: &str, context: usize) -> Vec<Hunk> {
+    let diff = TextDiff::configure()
+        .algorithm(Algorithm::Patience)
+        .diff_lines(old, new);
+
+    diff.grouped_ops(context)
+        .into_iter()
+        .filter(|group| !group.is_empty())
+        .map(|group| {
+            let first = &group[0];
+            let last = &group[group.len() - 1];
+            let old_range = first.old_range().start..last.old_range().end;
+            let new_range = first.new_range().start..last.new_range().end;
+
+            let mut lines = Vec::new();
+            for op in &group {
+                for change in diff.iter_changes(op) {
+                    let sign = match change.tag() {
+                        ChangeTag::Equal => ' ',
+                        ChangeTag::Delete => '-',
+                        ChangeTag::Insert => '+',
+                    };
+                    let text = change.to_string_lossy();
+                    let text = text.strip_suffix('\n').unwrap_or(&text);
+                    lines.push(format!("{sign}{text}"));
+                    if change.missing_newline() {
+                        lines.push(NO_NEWLINE_MARKER.to_string());
+                    }
+                }
+            }
+
+            Hunk {
+                old_start: range_start(&old_range),
+                old_lines: old_range.len(),
+                new_start: range_start(&new_range),
+                new_lines: new_range.len(),
+                lines,
+            }
+        })
+        .collect()
+}
+
+/// Unified diff ranges are 1-based, except that an empty range names the line before it.
+fn range_start(range: &std::ops::Range<usize>) -> usize {
+    if range.is_empty() {
+        range.start
+    } else {
+        range.start + 1
+    }
+}
+
+/// Render hunks as a unified diff with `---`/`+++` headers.
+///
+/// With `color`, uses ANSI escapes: bold headers, cyan hunk headers,
+/// red removals and green additions.
+pub fn format_unified(old_label: &str, new_label: &str, hunks: &[Hunk], color: bool) -> String {
+    let paint = |code: &str, text: &str| {
+        if color {
+            format!("\x1b[{code}m{text}\x1b[0m\n")
+        } else {
+            format!("{text}\n")
+        }
+    };
+
+    let mut out = String::new();
+    out.push_str(&paint("1", &format!("--- {old_label}")));
+    out.push_str(&paint("1", &format!("+++ {new_label}")));
+
+    for hunk in hunks {
+        out.push_str(&paint("36", &hunk.header()));
+        for line in &hunk.lines {
+            let code = match line.as_bytes().first() {
+                Some(b'-') => "31",
+                Some(b'+') => "32",
+                _ => "0",
+            };
+            if code == "0" {
+                out.push_str(line);
+                out.push('\n');
+            } else {
+                out.push_str(&paint(code, line));
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_content_has_no_hunks() {
+        assert!(hunks("a\nb\n", "a\nb\n", DEFAULT_CONTEXT).is_empty());
+    }
+
+    #[test]
+    fn single_line_change_with_context() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+        let new = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n";
+        let result = hunks(old, new, 2);
+
+        assert_eq!(result.len(), 1);
+        let hunk = &result[0];
+        assert_eq!(hunk.header(), "@@ -3,5 +3,5 @@");
+        assert_eq!(hunk.lines, vec![" 3", " 4", "-5", "+five", " 6", " 7"]);
+    }
+
+    #[test]
+    fn distant_changes_produce_separate_hunks() {
+        let old: String = (1..=20).map(|i| format!("line {i}\n")).collect();
+        let new = old
+            .replace("line 2\n", "two\n")
+            .replace("line 19\n", "nineteen\n");
+        let result = hunks(&old, &new, 1);
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].old_start, 1);
+        assert_eq!(result[1].old_start, 18);
+    }
+
+    #[test]
+    fn insertion_does_not_misalign_following_lines() {
+        // A positional comparison would report every line after the insert as changed
+        let old = "a\nb\nc\n";
+        let new = "a\ninserted\nb\nc\n";
+        let result = hunks(old, new, 0);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].lines, vec!["+inserted"]);
+        assert_eq!(result[0].header(), "@@ -1,0 +2,1 @@");
+    }
+
+    #[test]
+    fn new_file_against_empty() {
+        let result = hunks("", "one\ntwo\n", DEFAULT_CONTEXT);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].header(), "@@ -0,0 +1,2 @@");
+        assert_eq!(result[0].lines, vec!["+one", "+two"]);
+    }
+
+    #[test]
+    fn missing_trailing_newline_is_marked() {
+        let result = hunks("a\n", "a\nb", DEFAULT_CONTEXT);
+        assert_eq!(
+            result[0].lines,
+            vec![" a", "+b", "\\ No newline at end of file"]
+        );
+    }
+
+    #[test]
+    fn format_plain_and_colored() {
+        let result = hunks("old\n", "new\n", DEFAULT_CONTEXT);
+
+        let plain = format_unified("a/.bashrc", "b/.bashrc", &result, false);
+        assert_eq!(
+            plain,
+            "--- a/.bashrc\n+++ b/.bashrc\n@@ -1,1 +1,1 @@\n-old\n+new\n"
+        );
+
+        let colored = format_unified("a/.bashrc", "b/.bashrc", &result, true);
+        assert!(colored.contains("\x1b[31m-old\x1b[0m"));
+        assert!(colored.contains("\x1b[32m+new\x1b[0m"));
+    }
+}
@@ -0,0 +1,321 @@
+//! Ignore rules applied during discovery.
+//!
+//! Sources, from lowest to highest precedence:
+//! - `.boatignore` at the stow root (applies to every package)
+//! - `.stow-local-ignore` in the package (GNU Stow regex semantics)
+//! - `.boatignore` in the package root
+//! - `ignore = [...]` in the package config
+//! - `ignore = [...]` in nested `boat.toml` files (relative to their directory)
+//!
+//! `.boatignore` files and `ignore` entries use gitignore syntax: `*.swp`,
+//! `/README.md`, `docs/`, `!keep.me`, `**/*.orig`.
+
+use std::path::{Path, PathBuf};
+
+use ::ignore::gitignore::{Gitignore, GitignoreBuilder};
+use regex::Regex;
+
+use crate::config::package::PackageConfig;
+use crate::error::{Result, TowboatError};
+
+/// File name of gitignore-style ignore files.
+pub const BOATIGNORE: &str = ".boatignore";
+
+/// File name of GNU Stow's per-package ignore list.
+pub const STOW_LOCAL_IGNORE: &str = ".stow-local-ignore";
+
+/// Compiled ignore rules for one package.
+#[derive(Debug, Clone)]
+pub struct IgnoreRules {
+    package_root: PathBuf,
+    /// `.stow-local-ignore` regexes (lowest precedence after the global file).
+    stow_local: Vec<StowPattern>,
+    /// Global `.boatignore`, checked before `stow_local`.
+    global: Option<Gitignore>,
+    /// Package-level and nested gitignore matchers, in increasing precedence.
+    matchers: Vec<Gitignore>,
+}
+
+#[derive(Debug, Clone)]
+struct StowPattern {
+    regex: Regex,
+    /// Patterns containing `/` match the package-relative path; others match the file name.
+    match_path: bool,
+}
+
+impl IgnoreRules {
+    /// Build the rules for a package directory.
+    ///
+    /// The global `.boatignore` is read from the parent of `package_dir` (the stow root).
+    pub fn for_package(package_dir: &Path, config: &PackageConfig) -> Result<Self> {
+        let global_path = package_dir
+            .parent()
+            .map(|stow_dir| stow_dir.join(BOATIGNORE));
+        let global = match global_path {
+            Some(path) if path.is_file() => {
+                let mut builder = GitignoreBuilder::new(package_dir);
+                add_file(&mut builder, &path)?;
+                Some(build(&builder, &path.display().to_string())?)
+            }
+            _ => None,
+        };
+
+        let stow_local_path = package_dir.join(STOW_LOCAL_IGNORE);
+        let stow_local = if stow_local_path.is_file() {
+            parse_stow_local_ignore(&std::fs::read_to_string(&stow_local_path)?).map_err(
+                |reason| TowboatError::InvalidIgnorePattern {
+                    origin: stow_local_path.display().to_string(),
+                    reason,
+                },
+            )?
+        } else {
+            Vec::new()
+        };
+
+        let mut builder = GitignoreBuilder::new(package_dir);
+        let package_ignore = package_dir.join(BOATIGNORE);
+        if package_ignore.is_file() {
+            add_file(&mut builder, &package_ignore)?;
+        }
+
+        let mut rules = Self {
+            package_root: package_dir.to_path_buf(),
+            stow_local,
+            global,
+            matchers: vec![build(&builder, &package_ignore.display().to_string())?],
+        };
+        rules.push_config(package_dir, config)?;
+        Ok(rules)
+    }
+
+    /// Return a copy of these rules extended with the `ignore` patterns of a
+    /// nested `boat.toml` rooted at `config_root`.
+    pub fn with_nested(&self, config_root: &Path, config: &PackageConfig) -> Result<Self> {
+        let mut rules = self.clone();
+        rules.push_config(config_root, config)?;
+        Ok(rules)
+    }
+
+    fn push_config(&mut self, config_root: &Path, config: &PackageConfig) -> Result<()> {
+        if config.ignore.is_empty() {
+            return Ok(());
+        }
+        let origin = format!("`ignore` of {}", config_root.display());
+        let mut builder = GitignoreBuilder::new(config_root);
+        for pattern in &config.ignore {
+            builder
+                .add_line(None, pattern)
+                .map_err(|e| TowboatError::InvalidIgnorePattern {
+                    origin: origin.clone(),
+                    reason: e.to_string(),
+                })?;
+        }
+        self.matchers.push(build(&builder, &origin)?);
+        Ok(())
+    }
+
+    /// Whether `path` (inside the package) should be skipped.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        for matcher in self.matchers.iter().rev() {
+            let matched = matcher.matched(path, is_dir);
+            if matched.is_ignore() {
+                return true;
+            }
+            if matched.is_whitelist() {
+                return false;
+            }
+        }
+
+        if self.matches_stow_local(path) {
+            return true;
+        }
+
+        self.global
+            .as_ref()
+            .is_some_and(|g| g.matched(path, is_dir).is_ignore())
+    }
+
+    fn matches_stow_local(&self, path: &Path) -> bool {
+        if self.stow_local.is_empty() {
+            return false;
+        }
+        let Ok(relative) = path.strip_prefix(&self.package_root) else {
+            return false;
+        };
+        let relative = format!("/{}", relative.to_string_lossy());
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        self.stow_local.iter().any(|p| {
+            if p.match_path {
+                p.regex.is_match(&relative)
+            } else {
+                p.regex.is_match(&name)
+            }
+        })
+    }
+}
+
+fn add_file(builder: &mut GitignoreBuilder, path: &Path) -> Result<()> {
+    // The builder reports per-line glob errors without failing the whole file;
+    // treat any of them as a hard error so typos don't silently include files.
+    match builder.add(path) {
+        Some(e) => Err(TowboatError::InvalidIgnorePattern {
+            origin: path.display().to_string(),
+            reason: e.to_string(),
+        }),
+        None => Ok(()),
+    }
+}
+
+fn build(builder: &GitignoreBuilder, origin: &str) -> Result<Gitignore> {
+    builder
+        .build()
+        .map_err(|e| TowboatError::InvalidIgnorePattern {
+            origin: origin.to_string(),
+            reason: e.to_string(),
+        })
+}
+
+/// Parse `.stow-local-ignore` content: one regex per line, `#` comments, blank lines skipped.
+/// Each regex must match the whole file name (or the `/`-prefixed package path if it
+/// contains a `/`), as in GNU Stow.
+fn parse_stow_local_ignore(content: &str) -> std::result::Result<Vec<StowPattern>, String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let regex = Regex::new(&format!("^(?:{line})$")).map_err(|e| e.to_string())?;
+            Ok(StowPattern {
+                regex,
+                match_path: line.contains('/'),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn config_with_ignore(patterns: &[&str]) -> PackageConfig {
+        let ignore = patterns
+            .iter()
+            .map(|p| format!("{p:?}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        toml::from_str(&format!("ignore = [{ignore}]")).unwrap()
+    }
+
+    #[test]
+    fn config_patterns_use_gitignore_syntax() {
+        let dir = TempDir::new().unwrap();
+        let pkg = dir.path().join("pkg");
+        fs::create_dir_all(&pkg).unwrap();
+
+        let rules =
+            IgnoreRules::for_package(&pkg, &config_with_ignore(&["*.swp", "/README.md"])).unwrap();
+
+        assert!(rules.is_ignored(&pkg.join(".vimrc.swp"), false));
+        assert!(rules.is_ignored(&pkg.join("sub/x.swp"), false));
+        assert!(rules.is_ignored(&pkg.join("README.md"), false));
+        // Anchored pattern only matches at the package root
+        assert!(!rules.is_ignored(&pkg.join("docs/README.md"), false));
+        assert!(!rules.is_ignored(&pkg.join(".vimrc"), false));
+    }
+
+    #[test]
+    fn package_boatignore_and_negation() {
+        let dir = TempDir::new().unwrap();
+        let pkg = dir.path().join("pkg");
+        fs::create_dir_all(&pkg).unwrap();
+        fs::write(pkg.join(BOATIGNORE), "*.md\n.git/\n").unwrap();
+
+        let rules =
+            IgnoreRules::for_package(&pkg, &config_with_ignore(&["!CHANGELOG.md"])).unwrap();
+
+        assert!(rules.is_ignored(&pkg.join("README.md"), false));
+        assert!(rules.is_ignored(&pkg.join(".git"), true));
+        // Config patterns take precedence over the package .boatignore
+        assert!(!rules.is_ignored(&pkg.join("CHANGELOG.md"), false));
+    }
+
+    #[test]
+    fn global_boatignore_applies_to_every_package() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join(BOATIGNORE), "*~\n").unwrap();
+        let pkg = dir.path().join("pkg");
+        fs::create_dir_all(&pkg).unwrap();
+
+        let rules = IgnoreRules::for_package(&pkg, &PackageConfig::default()).unwrap();
+        assert!(rules.is_ignored(&pkg.join(".bashrc~"), false));
+        assert!(!rules.is_ignored(&pkg.join(".bashrc"), false));
+    }
+
+    #[test]
+    fn package_whitelist_overrides_global() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join(BOATIGNORE), "*.md\n").unwrap();
+        let pkg = dir.path().join("pkg");
+        fs::create_dir_all(&pkg).unwrap();
+        fs::write(pkg.join(BOATIGNORE), "!notes.md\n").unwrap();
+
+        let rules = IgnoreRules::for_package(&pkg, &PackageConfig::default()).unwrap();
+        assert!(rules.is_ignored(&pkg.join("README.md"), false));
+        assert!(!rules.is_ignored(&pkg.join("notes.md"), false));
+    }
+
+    #[test]
+    fn stow_local_ignore_regexes() {
+        let dir = TempDir::new().unwrap();
+        let pkg = dir.path().join("pkg");
+        fs::create_dir_all(&pkg).unwrap();
+        fs::write(
+            pkg.join(STOW_LOCAL_IGNORE),
+            "# comment\n\\.git\nREADME.*\n/docs/.*\\.txt\n",
+        )
+        .unwrap();
+
+        let rules = IgnoreRules::for_package(&pkg, &PackageConfig::default()).unwrap();
+        assert!(rules.is_ignored(&pkg.join(".git"), true));
+        assert!(rules.is_ignored(&pkg.join("README.md"), false));
+        assert!(rules.is_ignored(&pkg.join("sub/README"), false));
+        assert!(rules.is_ignored(&pkg.join("docs/a.txt"), false));
+        assert!(!rules.is_ignored(&pkg.join("a.txt"), false));
+        // Regexes are anchored to the whole name
+        assert!(!rules.is_ignored(&pkg.join(".gitconfig"), false));
+    }
+
+    #[test]
+    fn invalid_stow_regex_is_error() {
+        let dir = TempDir::new().unwrap();
+        let pkg = dir.path().join("pkg");
+        fs::create_dir_all(&pkg).unwrap();
+        fs::write(pkg.join(STOW_LOCAL_IGNORE), "(unclosed\n").unwrap();
+
+        let err = IgnoreRules::for_package(&pkg, &PackageConfig::default()).unwrap_err();
+        assert!(matches!(err, TowboatError::InvalidIgnorePattern { .. }));
+    }
+
+    #[test]
+    fn nested_config_patterns_are_relative_to_their_root() {
+        let dir = TempDir::new().unwrap();
+        let pkg = dir.path().join("pkg");
+        let nested = pkg.join("sub");
+        fs::create_dir_all(&nested).unwrap();
+
+        let rules = IgnoreRules::for_package(&pkg, &PackageConfig::default()).unwrap();
+        let nested_rules = rules
+            .with_nested(&nested, &config_with_ignore(&["/local.conf"]))
+            .unwrap();
+
+        assert!(nested_rules.is_ignored(&nested.join("local.conf"), false));
+        assert!(!nested_rules.is_ignored(&pkg.join("local.conf"), false));
+        assert!(!rules.is_ignored(&nested.join("local.conf"), false));
+    }
+}
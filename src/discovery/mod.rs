@@ -1 +1,3 @@
+pub mod ignore;
+pub mod variant;
 pub mod walker;
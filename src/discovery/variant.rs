@@ -0,0 +1,154 @@
+//! Filename-based tag suffixes for alternate files.
+//!
+//! A package can hold several variants of the same file, selected by tags in
+//! the file name instead of `[targets]` entries:
+//!
+//! - `.gitconfig##work`, `.gitconfig##linux,laptop`
+//! - `.gitconfig.tag-work`, `.gitconfig.tag-linux,laptop`
+//!
+//! All listed tags must be active. The suffix is stripped for the target path,
+//! and when several variants of one file match, the one naming the most tags
+//! wins. `##default` (or an unsuffixed file) is the fallback.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Separator of the `name##tags` form.
+const HASH_SEPARATOR: &str = "##";
+
+/// Separator of the `name.tag-tags` form.
+const TAG_SEPARATOR: &str = ".tag-";
+
+/// Tag that marks the fallback variant.
+const DEFAULT_TAG: &str = "default";
+
+/// Tags parsed from a file name suffix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    /// File name with the suffix removed.
+    pub base_name: String,
+    /// Tags that must all be active (empty for `##default`).
+    pub tags: Vec<String>,
+}
+
+impl Variant {
+    /// Parse a file name; `None` if it has no (valid) tag suffix.
+    pub fn parse(file_name: &str) -> Option<Self> {
+        let (base, suffix) = file_name
+            .split_once(HASH_SEPARATOR)
+            .or_else(|| file_name.rsplit_once(TAG_SEPARATOR))?;
+
+        if base.is_empty() || suffix.is_empty() {
+            return None;
+        }
+
+        let tags: Vec<String> = suffix.split(',').map(str::to_string).collect();
+        if !tags.iter().all(|t| is_valid_tag(t)) {
+            return None;
+        }
+
+        let tags = if tags == [DEFAULT_TAG] {
+            Vec::new()
+        } else {
+            tags
+        };
+        Some(Self {
+            base_name: base.to_string(),
+            tags,
+        })
+    }
+
+    /// Parse the file name of `path`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        Variant::parse(path.file_name()?.to_str()?)
+    }
+
+    /// Whether every tag in the suffix is active.
+    pub fn matches(&self, active_tags: &HashSet<String>) -> bool {
+        self.tags.iter().all(|t| active_tags.contains(t))
+    }
+
+    /// Number of tags named; higher wins among matching variants.
+    pub fn specificity(&self) -> usize {
+        self.tags.len()
+    }
+
+    /// `path` with its file name replaced by the base name.
+    pub fn strip(&self, path: &Path) -> PathBuf {
+        path.with_file_name(&self.base_name)
+    }
+
+    /// The suffix tags as written (`default` for the fallback).
+    pub fn describe(&self) -> String {
+        if self.tags.is_empty() {
+            DEFAULT_TAG.to_string()
+        } else {
+            self.tags.join(",")
+        }
+    }
+}
+
+fn is_valid_tag(tag: &str) -> bool {
+    !tag.is_empty()
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(names: &[&str]) -> HashSet<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_hash_suffix() {
+        let v = Variant::parse(".gitconfig##linux,laptop").unwrap();
+        assert_eq!(v.base_name, ".gitconfig");
+        assert_eq!(v.tags, vec!["linux", "laptop"]);
+        assert_eq!(v.specificity(), 2);
+    }
+
+    #[test]
+    fn parse_tag_suffix() {
+        let v = Variant::parse(".gitconfig.tag-work").unwrap();
+        assert_eq!(v.base_name, ".gitconfig");
+        assert_eq!(v.tags, vec!["work"]);
+    }
+
+    #[test]
+    fn default_suffix_is_fallback() {
+        let v = Variant::parse("init.lua##default").unwrap();
+        assert_eq!(v.base_name, "init.lua");
+        assert_eq!(v.specificity(), 0);
+        assert!(v.matches(&tags(&[])));
+        assert_eq!(v.describe(), "default");
+    }
+
+    #[test]
+    fn plain_and_malformed_names_are_not_variants() {
+        assert_eq!(Variant::parse(".gitconfig"), None);
+        assert_eq!(Variant::parse("##linux"), None);
+        assert_eq!(Variant::parse("a##"), None);
+        assert_eq!(Variant::parse("a##linux,,work"), None);
+        assert_eq!(Variant::parse("a##lin ux"), None);
+    }
+
+    #[test]
+    fn all_tags_must_be_active() {
+        let v = Variant::parse("x##linux,laptop").unwrap();
+        assert!(v.matches(&tags(&["linux", "laptop", "work"])));
+        assert!(!v.matches(&tags(&["linux"])));
+    }
+
+    #[test]
+    fn strip_replaces_file_name_only() {
+        let v = Variant::from_path(Path::new(".config/git/config##work")).unwrap();
+        assert_eq!(
+            v.strip(Path::new(".config/git/config##work")),
+            PathBuf::from(".config/git/config")
+        );
+    }
+}
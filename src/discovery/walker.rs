@@ -3,13 +3,17 @@
 //! Walks a package directory, consults `boat.toml` configuration, and returns
 //! the list of files that should be included for the active tags.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::path::{Path, PathBuf};
 
+use globset::{GlobBuilder, GlobMatcher};
 use walkdir::WalkDir;
 
 use crate::config::package::PackageConfig;
-use crate::error::Result;
+use crate::discovery::ignore::{BOATIGNORE, IgnoreRules, STOW_LOCAL_IGNORE};
+use crate::discovery::variant::Variant;
+use crate::error::{Result, TowboatError};
 use crate::tags::matcher;
 
 /// A file discovered during directory walking.
@@ -23,30 +27,365 @@ pub struct DiscoveredFile {
     pub target_path: PathBuf,
     /// The tag expression that matched.
     pub matched_expr: String,
+    /// The `[targets]` rule that included this file.
+    pub rule: MatchRule,
+    /// Filename tag suffix (`.gitconfig##work`), if the file is a variant.
+    pub variant: Option<Variant>,
+}
+
+impl DiscoveredFile {
+    /// Specificity among variants of the same target; unsuffixed files count as 0.
+    pub fn specificity(&self) -> usize {
+        self.variant.as_ref().map_or(0, Variant::specificity)
+    }
+}
+
+/// Kind of rule that decides whether a file is included.
+///
+/// Precedence, highest first: exact entry, glob entry (longest pattern first),
+/// parent directory entry (nearest first), `[default]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MatchRule {
+    /// `[targets]` entry for the file's exact path.
+    Exact(String),
+    /// `[targets]` entry whose key is a glob, e.g. `"bin/*.sh"`.
+    Glob(String),
+    /// `[targets]` entry for a parent directory (tag inheritance).
+    Directory(String),
+    /// `[default]` with `include_all = true`.
+    Default,
+}
+
+impl fmt::Display for MatchRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatchRule::Exact(key) => write!(f, "exact entry {key:?}"),
+            MatchRule::Glob(key) => write!(f, "glob entry {key:?}"),
+            MatchRule::Directory(key) => write!(f, "directory entry {key:?}"),
+            MatchRule::Default => write!(f, "[default] include_all"),
+        }
+    }
+}
+
+/// A rule that applies to a file, with its tag expression.
+#[derive(Debug, Clone)]
+pub struct RuleCandidate {
+    pub rule: MatchRule,
+    /// Tag expression string (empty means "no opinion", fall through to the next rule).
+    pub expr: String,
+    /// Remapped target path (only honored for exact entries).
+    pub target: Option<String>,
+}
+
+/// Why a single file is or isn't discovered, as reported by `towboat explain`.
+#[derive(Debug, Clone)]
+pub struct FileExplanation {
+    /// Path relative to the package directory.
+    pub relative_path: PathBuf,
+    /// Directory whose config is in effect (package root or a nested `boat.toml` dir).
+    pub config_root: PathBuf,
+    /// Whether the file (or a parent directory) is excluded by ignore rules.
+    pub ignored: bool,
+    /// Filename tag suffix, if the file is a variant.
+    pub variant: Option<Variant>,
+    /// All rules that apply to the file, in precedence order.
+    pub candidates: Vec<RuleCandidate>,
+    /// Index into `candidates` of the deciding rule and whether its expression matched.
+    /// `None` if no rule has an opinion (file is not included).
+    pub decision: Option<(usize, bool)>,
+    /// Target path if the file is included.
+    pub target_path: Option<PathBuf>,
+}
+
+impl FileExplanation {
+    /// Whether the file would be deployed.
+    pub fn included(&self) -> bool {
+        self.target_path.is_some()
+    }
+}
+
+/// `[targets]` of one config, with glob keys precompiled.
+struct TargetRules<'a> {
+    config: &'a PackageConfig,
+    /// Glob keys, most specific (longest) first.
+    globs: Vec<(&'a str, GlobMatcher)>,
+}
+
+impl<'a> TargetRules<'a> {
+    fn new(config: &'a PackageConfig) -> Result<Self> {
+        let mut globs = Vec::new();
+        for key in config.targets.keys().filter(|k| is_glob(k)) {
+            let glob = GlobBuilder::new(key)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| TowboatError::InvalidGlob {
+                    pattern: key.clone(),
+                    reason: e.kind().to_string(),
+                })?;
+            globs.push((key.as_str(), glob.compile_matcher()));
+        }
+        globs.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        Ok(Self { config, globs })
+    }
+
+    /// All rules that apply to `config_relative`, in precedence order.
+    fn candidates(&self, config_relative: &Path) -> Vec<RuleCandidate> {
+        let mut candidates = Vec::new();
+        let path_str = config_relative.to_string_lossy();
+
+        // 1. Explicit target entry
+        if let Some(target_config) = self.config.targets.get(path_str.as_ref()) {
+            candidates.push(RuleCandidate {
+                rule: MatchRule::Exact(path_str.to_string()),
+                expr: target_config.tags.to_expr_string(),
+                target: target_config.target.clone(),
+            });
+        }
+
+        // 2. Glob entries
+        for (key, glob) in &self.globs {
+            if *key != path_str && glob.is_match(config_relative) {
+                candidates.push(RuleCandidate {
+                    rule: MatchRule::Glob(key.to_string()),
+                    expr: self.config.targets[*key].tags.to_expr_string(),
+                    target: None,
+                });
+            }
+        }
+
+        // 3. Parent directory entries (tag inheritance)
+        let mut check_path: &Path = config_relative;
+        while let Some(parent) = check_path.parent() {
+            if parent == Path::new("") {
+                break;
+            }
+            let parent_str = parent.to_string_lossy();
+            if let Some(parent_config) = self.config.targets.get(parent_str.as_ref()) {
+                candidates.push(RuleCandidate {
+                    rule: MatchRule::Directory(parent_str.to_string()),
+                    expr: parent_config.tags.to_expr_string(),
+                    target: None,
+                });
+            }
+            check_path = parent;
+        }
+
+        // 4. Default behavior
+        let defaults = self.config.default.as_ref().cloned().unwrap_or_default();
+        if defaults.include_all {
+            candidates.push(RuleCandidate {
+                rule: MatchRule::Default,
+                expr: defaults.default_tag,
+                target: None,
+            });
+        }
+
+        candidates
+    }
+}
+
+/// Whether a `[targets]` key contains glob metacharacters.
+fn is_glob(key: &str) -> bool {
+    key.contains(['*', '?', '[', '{'])
+}
+
+/// Pick the deciding rule: the first candidate with a non-empty tag expression.
+/// Returns its index and whether the expression matched the active tags.
+fn decide(
+    candidates: &[RuleCandidate],
+    active_tags: &HashSet<String>,
+) -> Result<Option<(usize, bool)>> {
+    for (i, candidate) in candidates.iter().enumerate() {
+        if !candidate.expr.is_empty() {
+            return Ok(Some((i, evaluate_expr(&candidate.expr, active_tags)?)));
+        }
+    }
+    Ok(None)
 }
 
 /// Walk a package directory and return all files matching the active tags.
 ///
 /// Respects `boat.toml` configuration including:
 /// - Explicit target entries with tag expressions
+/// - Glob target entries (`"bin/*.sh"`, `".config/**/*.local"`)
 /// - Directory tag inheritance
 /// - Nested `boat.toml` files (subdirectory precedence)
 /// - Default behavior for unconfigured files
+/// - Ignore rules (`.boatignore`, `.stow-local-ignore`, `ignore = [...]`)
+/// - Filename tag suffixes (`.gitconfig##work`), keeping the most specific variant
 pub fn discover_package(
     package_dir: &Path,
     config: &PackageConfig,
     active_tags: &HashSet<String>,
 ) -> Result<Vec<DiscoveredFile>> {
+    let ignore = IgnoreRules::for_package(package_dir, config)?;
+    let rules = TargetRules::new(config)?;
     let mut results = Vec::new();
     walk_dir(
         package_dir,
         package_dir,
         package_dir,
-        config,
+        &rules,
+        &ignore,
         active_tags,
         &mut results,
     )?;
-    Ok(results)
+    select_variants(results)
+}
+
+/// For each target with tag-suffixed variants, keep only the most specific one.
+///
+/// Errors if the two most specific variants name the same number of tags.
+fn select_variants(files: Vec<DiscoveredFile>) -> Result<Vec<DiscoveredFile>> {
+    let mut by_target: HashMap<&Path, Vec<usize>> = HashMap::new();
+    for (i, file) in files.iter().enumerate() {
+        by_target.entry(&file.target_path).or_default().push(i);
+    }
+
+    let mut dropped = HashSet::new();
+    for (target, mut indices) in by_target {
+        if indices.len() < 2 || indices.iter().all(|&i| files[i].variant.is_none()) {
+            continue;
+        }
+        indices.sort_by_key(|&i| std::cmp::Reverse(files[i].specificity()));
+        let (best, runner_up) = (&files[indices[0]], &files[indices[1]]);
+        if best.specificity() == runner_up.specificity() {
+            return Err(TowboatError::AmbiguousVariant {
+                target: target.to_path_buf(),
+                first: best.relative_path.clone(),
+                second: runner_up.relative_path.clone(),
+            });
+        }
+        dropped.extend(indices[1..].iter().copied());
+    }
+
+    Ok(files
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| !dropped.contains(i))
+        .map(|(_, file)| file)
+        .collect())
+}
+
+/// Explain how a single file inside `package_dir` is treated by discovery.
+///
+/// Follows nested `boat.toml` files and ignore rules the same way
+/// [`discover_package`] does, without walking the rest of the package.
+pub fn explain_file(
+    package_dir: &Path,
+    config: &PackageConfig,
+    active_tags: &HashSet<String>,
+    file_path: &Path,
+) -> Result<FileExplanation> {
+    let relative = file_path
+        .strip_prefix(package_dir)
+        .map_err(|_| std::io::Error::other("file is not inside the package directory"))?
+        .to_path_buf();
+
+    let mut ignore = IgnoreRules::for_package(package_dir, config)?;
+    let mut effective_config = config.clone();
+    let mut config_root = package_dir.to_path_buf();
+    let mut ignored = false;
+
+    // Descend through parent directories, picking up nested boat.toml files
+    let mut dir = package_dir.to_path_buf();
+    let components: Vec<_> = relative.components().collect();
+    for component in &components[..components.len().saturating_sub(1)] {
+        dir.push(component);
+        if is_builtin_skip(&dir) || ignore.is_ignored(&dir, true) {
+            ignored = true;
+        }
+        let nested_config_path = dir.join("boat.toml");
+        if nested_config_path.exists() {
+            effective_config = PackageConfig::load(&nested_config_path)?;
+            ignore = ignore.with_nested(&dir, &effective_config)?;
+            config_root = dir.clone();
+        }
+    }
+    if is_builtin_skip(file_path) || ignore.is_ignored(file_path, false) {
+        ignored = true;
+    }
+
+    let rules = TargetRules::new(&effective_config)?;
+    let config_relative = file_path
+        .strip_prefix(&config_root)
+        .map_err(|_| std::io::Error::other("failed to strip prefix"))?;
+    let variant = Variant::from_path(file_path);
+    let (rule_path, target_relative) = match &variant {
+        Some(v) => (v.strip(config_relative), v.strip(&relative)),
+        None => (config_relative.to_path_buf(), relative.clone()),
+    };
+    let candidates = rules.candidates(&rule_path);
+    let decision = decide(&candidates, active_tags)?;
+    let variant_matches = variant.as_ref().is_none_or(|v| v.matches(active_tags));
+
+    let target_path = match decision {
+        Some((i, true)) if !ignored && variant_matches => {
+            Some(target_for(&candidates[i], &target_relative))
+        }
+        _ => None,
+    };
+
+    Ok(FileExplanation {
+        relative_path: relative,
+        config_root,
+        ignored,
+        variant,
+        candidates,
+        decision,
+        target_path,
+    })
+}
+
+/// Every file discovery considers in `package_dir`, whether or not its tags match.
+///
+/// Applies the same builtin skips, ignore rules and nested `boat.toml` ignores as
+/// [`discover_package`]. Paths are sorted.
+pub fn source_files(package_dir: &Path, config: &PackageConfig) -> Result<Vec<PathBuf>> {
+    fn walk(dir: &Path, ignore: &IgnoreRules, files: &mut Vec<PathBuf>) -> Result<()> {
+        for entry in WalkDir::new(dir).min_depth(1).max_depth(1) {
+            let entry = entry.map_err(|e| std::io::Error::other(format!("walkdir error: {e}")))?;
+            let path = entry.path();
+            if is_builtin_skip(path) || ignore.is_ignored(path, path.is_dir()) {
+                continue;
+            }
+            if path.is_dir() {
+                let nested_config_path = path.join("boat.toml");
+                if nested_config_path.exists() {
+                    let nested = PackageConfig::load(&nested_config_path)?;
+                    walk(path, &ignore.with_nested(path, &nested)?, files)?;
+                } else {
+                    walk(path, ignore, files)?;
+                }
+            } else if path.is_file() {
+                files.push(path.to_path_buf());
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    walk(
+        package_dir,
+        &IgnoreRules::for_package(package_dir, config)?,
+        &mut files,
+    )?;
+    files.sort();
+    Ok(files)
+}
+
+/// Config files, internal dirs, and common OS junk that are never discovered.
+fn is_builtin_skip(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|name| {
+            name == "boat.toml"
+                || name == ".towboat"
+                || name == "towboat.toml"
+                || name == ".DS_Store"
+                || name == BOATIGNORE
+                || name == STOW_LOCAL_IGNORE
+        })
 }
 
 /// `package_root` — top-level package dir (for computing output relative paths).
@@ -55,7 +394,8 @@ fn walk_dir(
     package_root: &Path,
     config_root: &Path,
     dir: &Path,
-    config: &PackageConfig,
+    rules: &TargetRules,
+    ignore: &IgnoreRules,
     active_tags: &HashSet<String>,
     results: &mut Vec<DiscoveredFile>,
 ) -> Result<()> {
@@ -67,14 +407,11 @@ fn walk_dir(
         let entry = entry.map_err(|e| std::io::Error::other(format!("walkdir error: {e}")))?;
         let path = entry.path();
 
-        // Skip config files, internal dirs, and common OS junk
-        if let Some(name) = path.file_name().and_then(|n| n.to_str())
-            && (name == "boat.toml"
-                || name == ".towboat"
-                || name == "towboat.toml"
-                || name == ".DS_Store"
-                || name == ".stow-local-ignore")
-        {
+        if is_builtin_skip(path) {
+            continue;
+        }
+
+        if path != dir && ignore.is_ignored(path, path.is_dir()) {
             continue;
         }
 
@@ -83,11 +420,14 @@ fn walk_dir(
             let nested_config_path = path.join("boat.toml");
             if nested_config_path.exists() {
                 let nested_config = PackageConfig::load(&nested_config_path)?;
+                let nested_rules = TargetRules::new(&nested_config)?;
+                let nested_ignore = ignore.with_nested(path, &nested_config)?;
                 walk_dir(
                     package_root,
                     path,
                     path,
-                    &nested_config,
+                    &nested_rules,
+                    &nested_ignore,
                     active_tags,
                     results,
                 )?;
@@ -97,14 +437,15 @@ fn walk_dir(
                     package_root,
                     config_root,
                     path,
-                    config,
+                    rules,
+                    ignore,
                     active_tags,
                     results,
                 )?;
             }
         } else if path.is_file()
             && let Some(discovered) =
-                check_file(package_root, config_root, path, config, active_tags)?
+                check_file(package_root, config_root, path, rules, active_tags)?
         {
             results.push(discovered);
         }
@@ -121,7 +462,7 @@ fn check_file(
     package_root: &Path,
     config_root: &Path,
     file_path: &Path,
-    config: &PackageConfig,
+    rules: &TargetRules,
     active_tags: &HashSet<String>,
 ) -> Result<Option<DiscoveredFile>> {
     // Path relative to package root (used in output)
@@ -134,69 +475,41 @@ fn check_file(
         .strip_prefix(config_root)
         .map_err(|_| std::io::Error::other("failed to strip prefix"))?;
 
-    let config_path_str = config_relative.to_string_lossy();
-
-    // 1. Check explicit target entry
-    if let Some(target_config) = config.targets.get(config_path_str.as_ref()) {
-        let expr_str = target_config.tags.to_expr_string();
-        if !expr_str.is_empty() && evaluate_expr(&expr_str, active_tags)? {
-            let target_path = target_config
-                .target
-                .as_ref()
-                .map(PathBuf::from)
-                .unwrap_or_else(|| relative.to_path_buf());
-            return Ok(Some(DiscoveredFile {
-                source_path: file_path.to_path_buf(),
-                relative_path: relative.to_path_buf(),
-                target_path,
-                matched_expr: expr_str,
-            }));
-        }
-        // Explicitly configured but doesn't match — skip
-        if !expr_str.is_empty() {
-            return Ok(None);
-        }
+    // Variants are matched by their suffix tags, then by the rules for the stripped name
+    let variant = Variant::from_path(file_path);
+    if variant.as_ref().is_some_and(|v| !v.matches(active_tags)) {
+        return Ok(None);
     }
-
-    // 2. Check parent directory entries (tag inheritance)
-    let mut check_path: &Path = config_relative;
-    while let Some(parent) = check_path.parent() {
-        if parent == Path::new("") {
-            break;
-        }
-        let parent_str = parent.to_string_lossy();
-        if let Some(parent_config) = config.targets.get(parent_str.as_ref()) {
-            let expr_str = parent_config.tags.to_expr_string();
-            if !expr_str.is_empty() && evaluate_expr(&expr_str, active_tags)? {
-                return Ok(Some(DiscoveredFile {
-                    source_path: file_path.to_path_buf(),
-                    relative_path: relative.to_path_buf(),
-                    target_path: relative.to_path_buf(),
-                    matched_expr: expr_str,
-                }));
-            }
-            if !expr_str.is_empty() {
-                return Ok(None);
-            }
-        }
-        check_path = parent;
-    }
-
-    // 3. Check default behavior
-    let defaults = config.default.as_ref().cloned().unwrap_or_default();
-    if defaults.include_all {
-        let expr_str = defaults.default_tag.clone();
-        if evaluate_expr(&expr_str, active_tags)? {
-            return Ok(Some(DiscoveredFile {
+    let (rule_path, target_relative) = match &variant {
+        Some(v) => (v.strip(config_relative), v.strip(relative)),
+        None => (config_relative.to_path_buf(), relative.to_path_buf()),
+    };
+
+    let candidates = rules.candidates(&rule_path);
+    match decide(&candidates, active_tags)? {
+        Some((i, true)) => {
+            let candidate = &candidates[i];
+            Ok(Some(DiscoveredFile {
                 source_path: file_path.to_path_buf(),
                 relative_path: relative.to_path_buf(),
-                target_path: relative.to_path_buf(),
-                matched_expr: expr_str,
-            }));
+                target_path: target_for(candidate, &target_relative),
+                matched_expr: candidate.expr.clone(),
+                rule: candidate.rule.clone(),
+                variant,
+            }))
         }
+        // Configured but doesn't match, or no rule applies — skip
+        _ => Ok(None),
     }
+}
 
-    Ok(None)
+/// Target path for an included file: exact entries may remap it, other rules keep
+/// the package-relative path.
+fn target_for(candidate: &RuleCandidate, relative: &Path) -> PathBuf {
+    match (&candidate.rule, &candidate.target) {
+        (MatchRule::Exact(_), Some(target)) => PathBuf::from(target),
+        _ => relative.to_path_buf(),
+    }
 }
 
 fn evaluate_expr(expr_str: &str, active_tags: &HashSet<String>) -> Result<bool> {
@@ -387,6 +700,70 @@ default_tag = "default"
         assert_eq!(results[0].relative_path, PathBuf::from(".bashrc"));
     }
 
+    #[test]
+    fn discover_respects_ignore_patterns() {
+        let dir = setup_package(
+            &[
+                (".bashrc", "content"),
+                (".bashrc.swp", "swap"),
+                ("README.md", "docs"),
+                (".git/HEAD", "ref"),
+                ("notes/todo.md", "todo"),
+            ],
+            r#"
+ignore = ["*.swp", ".git/"]
+
+[default]
+include_all = true
+default_tag = "default"
+"#,
+        );
+        fs::write(dir.path().join(".boatignore"), "/README.md\n").unwrap();
+
+        let config = PackageConfig::load(&dir.path().join("boat.toml")).unwrap();
+        let results = discover_package(dir.path(), &config, &tags(&["default"])).unwrap();
+
+        let mut found: Vec<_> = results.iter().map(|f| f.relative_path.clone()).collect();
+        found.sort();
+        assert_eq!(
+            found,
+            vec![PathBuf::from(".bashrc"), PathBuf::from("notes/todo.md")]
+        );
+    }
+
+    #[test]
+    fn source_files_ignore_tags_but_not_ignore_rules() {
+        let dir = setup_package(
+            &[
+                (".bashrc##linux", "content"),
+                (".bashrc.swp", "swap"),
+                ("nested/boat.toml", "ignore = [\"*.bak\"]\n"),
+                ("nested/a.conf", "a"),
+                ("nested/a.bak", "b"),
+            ],
+            r#"
+ignore = ["*.swp"]
+
+[targets]
+".bashrc" = { tags = "macos" }
+"#,
+        );
+
+        let config = PackageConfig::load(&dir.path().join("boat.toml")).unwrap();
+        let files: Vec<_> = source_files(dir.path(), &config)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.strip_prefix(dir.path()).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(
+            files,
+            vec![
+                PathBuf::from(".bashrc##linux"),
+                PathBuf::from("nested/a.conf")
+            ]
+        );
+    }
+
     #[test]
     fn discover_no_matches() {
         let dir = setup_package(
@@ -401,4 +778,212 @@ default_tag = "default"
         let results = discover_package(dir.path(), &config, &tags(&["macos"])).unwrap();
         assert_eq!(results.len(), 0);
     }
+
+    #[test]
+    fn glob_keys_match_files() {
+        let dir = setup_package(
+            &[
+                ("bin/a.sh", "a"),
+                ("bin/sub/b.sh", "b"),
+                ("bin/c.py", "c"),
+                (".config/x/y/app.local", "d"),
+            ],
+            r#"
+[targets]
+"bin/*.sh" = { tags = "linux" }
+".config/**/*.local" = { tags = "work" }
+"#,
+        );
+
+        let config = PackageConfig::load(&dir.path().join("boat.toml")).unwrap();
+        let results = discover_package(dir.path(), &config, &tags(&["linux", "work"])).unwrap();
+
+        let mut paths: Vec<_> = results.iter().map(|f| f.relative_path.clone()).collect();
+        paths.sort();
+        // `*` does not cross `/`; `**` does
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from(".config/x/y/app.local"),
+                PathBuf::from("bin/a.sh")
+            ]
+        );
+        let sh = results
+            .iter()
+            .find(|f| f.relative_path == Path::new("bin/a.sh"))
+            .unwrap();
+        assert_eq!(sh.rule, MatchRule::Glob("bin/*.sh".to_string()));
+    }
+
+    #[test]
+    fn rule_precedence_exact_glob_directory_default() {
+        let dir = setup_package(
+            &[
+                ("bin/exact.sh", "1"),
+                ("bin/long.sh", "2"),
+                ("bin/other", "3"),
+                ("top", "4"),
+            ],
+            r#"
+[targets]
+"bin/exact.sh" = { tags = "macos" }
+"bin/*" = { tags = "macos" }
+"bin/*.sh" = { tags = "linux" }
+"bin" = { tags = "windows" }
+
+[default]
+include_all = true
+default_tag = "linux"
+"#,
+        );
+
+        let config = PackageConfig::load(&dir.path().join("boat.toml")).unwrap();
+        let results = discover_package(dir.path(), &config, &tags(&["linux"])).unwrap();
+
+        let mut found: Vec<_> = results
+            .iter()
+            .map(|f| {
+                (
+                    f.relative_path.to_string_lossy().into_owned(),
+                    f.rule.clone(),
+                )
+            })
+            .collect();
+        found.sort_by(|a, b| a.0.cmp(&b.0));
+        // exact.sh: exact entry (macos) wins over the glob; other: "bin/*" (macos)
+        // wins over directory entry; long.sh: longest glob wins
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "bin/long.sh".to_string(),
+                    MatchRule::Glob("bin/*.sh".to_string())
+                ),
+                ("top".to_string(), MatchRule::Default),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_glob_key_is_error() {
+        let dir = setup_package(
+            &[("a", "1")],
+            r#"
+[targets]
+"bin/[a" = { tags = "linux" }
+"#,
+        );
+
+        let config = PackageConfig::load(&dir.path().join("boat.toml")).unwrap();
+        let err = discover_package(dir.path(), &config, &tags(&["linux"])).unwrap_err();
+        assert!(matches!(err, TowboatError::InvalidGlob { .. }));
+    }
+
+    #[test]
+    fn explain_lists_candidates_in_precedence_order() {
+        let dir = setup_package(
+            &[("sub/bin/tool.sh", "x")],
+            "[targets]\n\"sub\" = { tags = \"work\" }\n",
+        );
+        fs::write(
+            dir.path().join("sub/boat.toml"),
+            "[targets]\n\"bin/*.sh\" = { tags = \"linux\" }\n\"bin\" = { tags = \"macos\" }\n",
+        )
+        .unwrap();
+
+        let config = PackageConfig::load(&dir.path().join("boat.toml")).unwrap();
+        let explanation = explain_file(
+            dir.path(),
+            &config,
+            &tags(&["linux"]),
+            &dir.path().join("sub/bin/tool.sh"),
+        )
+        .unwrap();
+
+        assert_eq!(explanation.config_root, dir.path().join("sub"));
+        let rules: Vec<_> = explanation
+            .candidates
+            .iter()
+            .map(|c| c.rule.clone())
+            .collect();
+        assert_eq!(
+            rules,
+            vec![
+                MatchRule::Glob("bin/*.sh".to_string()),
+                MatchRule::Directory("bin".to_string())
+            ]
+        );
+        assert_eq!(explanation.decision, Some((0, true)));
+        assert!(explanation.included());
+        assert_eq!(
+            explanation.target_path,
+            Some(PathBuf::from("sub/bin/tool.sh"))
+        );
+    }
+
+    #[test]
+    fn discover_picks_most_specific_variant() {
+        let dir = setup_package(
+            &[
+                (".gitconfig", "plain"),
+                (".gitconfig##work", "work"),
+                (".gitconfig.tag-work,laptop", "work laptop"),
+                (".gitconfig##macos", "macos"),
+            ],
+            "[default]\ninclude_all = true\n",
+        );
+
+        let config = PackageConfig::load(&dir.path().join("boat.toml")).unwrap();
+
+        let results = discover_package(dir.path(), &config, &tags(&["default", "work"])).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].relative_path, PathBuf::from(".gitconfig##work"));
+        assert_eq!(results[0].target_path, PathBuf::from(".gitconfig"));
+
+        let results =
+            discover_package(dir.path(), &config, &tags(&["default", "work", "laptop"])).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].relative_path,
+            PathBuf::from(".gitconfig.tag-work,laptop")
+        );
+
+        // No variant matches: the unsuffixed file is the fallback
+        let results = discover_package(dir.path(), &config, &tags(&["default"])).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].relative_path, PathBuf::from(".gitconfig"));
+    }
+
+    #[test]
+    fn variants_use_rules_of_stripped_name() {
+        let dir = setup_package(
+            &[("bin/run.sh##linux", "l"), ("bin/run.sh##macos", "m")],
+            "[targets]\n\"bin/*.sh\" = { tags = \"work\" }\n",
+        );
+
+        let config = PackageConfig::load(&dir.path().join("boat.toml")).unwrap();
+        let results = discover_package(dir.path(), &config, &tags(&["linux", "work"])).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].target_path, PathBuf::from("bin/run.sh"));
+
+        let results = discover_package(dir.path(), &config, &tags(&["linux"])).unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn equally_specific_variants_are_an_error() {
+        let dir = setup_package(
+            &[(".gitconfig##work", "w"), (".gitconfig##laptop", "l")],
+            "[default]\ninclude_all = true\n",
+        );
+
+        let config = PackageConfig::load(&dir.path().join("boat.toml")).unwrap();
+        let err = discover_package(dir.path(), &config, &tags(&["default", "work", "laptop"]))
+            .unwrap_err();
+        assert!(matches!(err, TowboatError::AmbiguousVariant { .. }));
+
+        // Only one matches: no ambiguity
+        let results = discover_package(dir.path(), &config, &tags(&["default", "work"])).unwrap();
+        assert_eq!(results.len(), 1);
+    }
 }
@@ -12,6 +12,14 @@ pub enum TowboatError {
     #[error("package config not found: expected boat.toml at {0}")]
     PackageConfigNotFound(PathBuf),
 
+    #[error("{}:{line}:{column}: {message}", path.display())]
+    InvalidConfig {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+
     #[error("invalid tag expression: {0}")]
     InvalidTagExpr(String),
 
@@ -34,12 +42,40 @@ pub enum TowboatError {
     #[error("lock file corrupt: {0}")]
     LockCorrupt(String),
 
+    #[error("generation {0} not found (see `towboat generations`)")]
+    GenerationNotFound(u32),
+
+    #[error("generation corrupt: {0}")]
+    GenerationCorrupt(String),
+
+    #[error("history corrupt: {0}")]
+    HistoryCorrupt(String),
+
+    #[error("cannot locate the state directory: set [system] state_dir, XDG_STATE_HOME or HOME")]
+    NoStateDir,
+
     #[error("package not found: {0}")]
     PackageNotFound(String),
 
+    #[error("profile not found: {0} (declare it as [profiles.{0}] in towboat.toml)")]
+    ProfileNotFound(String),
+
     #[error("mismatched tag delimiters: opened with {open:?} but closed with {close:?}")]
     MismatchedTagDelimiters { open: String, close: String },
 
+    #[error("invalid glob {pattern:?} in [targets]: {reason}")]
+    InvalidGlob { pattern: String, reason: String },
+
+    #[error("ambiguous variants for {target}: {first} and {second} match with equal specificity")]
+    AmbiguousVariant {
+        target: PathBuf,
+        first: PathBuf,
+        second: PathBuf,
+    },
+
+    #[error("invalid ignore pattern in {origin}: {reason}")]
+    InvalidIgnorePattern { origin: String, reason: String },
+
     #[error(transparent)]
     Io(#[from] std::io::Error),
 }
@@ -1,11 +1,13 @@
 //! Towboat v2 — A cross-platform dotfile manager with build tags and templates.
 //!
-//! All files are resolved (tag processing + template substitution) into
-//! `.towboat/resolved/`, and symlinks always point to resolved files.
+//! All files are resolved (tag processing + template substitution) into the
+//! state dir's `resolved/` (see [`deploy::state`]), and symlinks always point
+//! to resolved files.
 
 pub mod commands;
 pub mod config;
 pub mod deploy;
+pub mod diff;
 pub mod discovery;
 pub mod error;
 pub mod resolve;
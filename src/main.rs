@@ -1,9 +1,18 @@
 //! Towboat CLI — subcommand-based interface for dotfile management.
 
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{ArgGroup, Args, Parser, Subcommand};
+use towboat::commands::diff::DiffOptions;
+use towboat::commands::export::{ExportDest, ExportOptions};
+use towboat::commands::log::LogOptions;
+use towboat::commands::relocate::RelocateOptions;
+use towboat::commands::status::StatusOptions;
+use towboat::commands::sync::SyncOptions;
+use towboat::commands::watch::WatchOptions;
+use towboat::commands::{ColorChoice, OutputFormat};
+use towboat::config::overrides::{Overrides, TagChange, parse_assignment};
 
 #[derive(Parser)]
 #[command(
@@ -24,6 +33,30 @@ struct Cli {
     command: Commands,
 }
 
+/// Simulate another machine without editing towboat.toml.
+#[derive(Args)]
+struct SimulateArgs {
+    /// Tag changes: `+tag` adds, `-tag` removes, bare tags replace the set
+    #[arg(long, value_name = "TAGS", value_delimiter = ',', allow_hyphen_values = true, value_parser = TagChange::parse)]
+    tags: Vec<TagChange>,
+    /// Override a template variable (repeatable)
+    #[arg(long = "set", value_name = "VAR=VALUE", value_parser = parse_assignment)]
+    set: Vec<(String, String)>,
+    /// Use the tags and variables of [profiles.<NAME>]
+    #[arg(long, value_name = "NAME")]
+    as_host: Option<String>,
+}
+
+impl From<SimulateArgs> for Overrides {
+    fn from(args: SimulateArgs) -> Self {
+        Overrides {
+            host: args.as_host,
+            tags: args.tags,
+            variables: args.set,
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Resolve packages and create/update symlinks
@@ -36,56 +69,282 @@ enum Commands {
         /// Overwrite existing files and resolve conflicts
         #[arg(short, long)]
         force: bool,
+        /// Do not run package hooks
+        #[arg(long)]
+        no_hooks: bool,
+        /// Do not run run-once / run-on-change scripts
+        #[arg(long)]
+        no_scripts: bool,
+        /// Worker threads for reading and resolving files (default: one per CPU)
+        #[arg(
+            short,
+            long,
+            value_name = "N",
+            default_value_t = 0,
+            hide_default_value = true
+        )]
+        jobs: usize,
+        /// Hash every file instead of trusting unchanged size, mtime and inode
+        #[arg(long)]
+        paranoid: bool,
+        /// Simulation overrides (require --dry-run)
+        #[command(flatten)]
+        simulate: SimulateArgs,
+    },
+    /// Re-sync packages as their sources, boat.toml or towboat.toml change
+    Watch {
+        /// Milliseconds without events before a batch of changes is synced
+        #[arg(long, value_name = "MS", default_value_t = 200)]
+        debounce: u64,
+        /// Do not run package hooks
+        #[arg(long)]
+        no_hooks: bool,
+        /// Worker threads for reading and resolving files (default: one per CPU)
+        #[arg(
+            short,
+            long,
+            value_name = "N",
+            default_value_t = 0,
+            hide_default_value = true
+        )]
+        jobs: usize,
     },
     /// Show per-file state (up-to-date, source-changed, drifted, conflict, broken)
     Status {
         /// Specific package to check (checks all if omitted)
         package: Option<String>,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+        /// Exit with the worst state found instead of 0
+        #[arg(
+            long,
+            long_help = "Exit with the worst state found instead of 0:\n  \
+                0   everything is up to date\n  \
+                1   towboat itself failed\n  \
+                2   usage error\n  \
+                10  pending: new, stale or source-changed files, or pending scripts\n  \
+                11  drifted: a resolved file was edited\n  \
+                12  conflict, or a foreign file occupies a target\n  \
+                13  broken or missing symlink, or one into an old state dir"
+        )]
+        check: bool,
+        /// Print nothing (with --check, only the exit code reports)
+        #[arg(short, long, requires = "check")]
+        quiet: bool,
+        /// Hash every file instead of trusting unchanged size, mtime and inode
+        #[arg(long)]
+        paranoid: bool,
     },
     /// Show what would change on next sync
     Diff {
         /// Specific package to diff (diffs all if omitted)
         package: Option<String>,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+        /// When to color the diff
+        #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+        color: ColorChoice,
+        /// Lines of context around each change
+        #[arg(short = 'U', long, default_value_t = towboat::diff::unified::DEFAULT_CONTEXT)]
+        unified: usize,
+        /// Also show edits made to resolved files, against a fresh resolve
+        #[arg(long)]
+        drifted: bool,
+        #[command(flatten)]
+        simulate: SimulateArgs,
+    },
+    /// Show why a file is or isn't deployed
+    Explain {
+        /// Source file (absolute, relative to the current directory, or to the stow
+        /// directory) or target path (absolute, or relative to the target directory)
+        path: PathBuf,
+        #[command(flatten)]
+        simulate: SimulateArgs,
+    },
+    /// Print a source file's resolved content to stdout
+    Render {
+        /// Source file (absolute, relative to the current directory, or to the stow directory)
+        path: PathBuf,
+        #[command(flatten)]
+        simulate: SimulateArgs,
+    },
+    /// Resolve packages into a tree of real files, without touching the target
+    #[command(group(ArgGroup::new("dest").required(true).args(["out", "tar"])))]
+    Export {
+        /// Specific package to export (exports all if omitted)
+        package: Option<String>,
+        /// Directory to write the tree into (must be new or empty)
+        #[arg(long, value_name = "DIR")]
+        out: Option<PathBuf>,
+        /// Tar archive to write instead of a directory
+        #[arg(long, value_name = "FILE")]
+        tar: Option<PathBuf>,
+        #[command(flatten)]
+        simulate: SimulateArgs,
+    },
+    /// List recorded sync generations
+    Generations,
+    /// Show what past syncs and rollbacks changed, newest first
+    Log {
+        /// Only show file changes of this package
+        #[arg(long)]
+        package: Option<String>,
+        /// Only show entries since an age (3d, 12h) or a date ("2026-10-01 14:30")
+        #[arg(long, value_name = "WHEN")]
+        since: Option<String>,
+    },
+    /// Restore the resolved files, symlinks and lock of an earlier generation
+    Rollback {
+        /// Generation to restore (default: the one before the current generation)
+        generation: Option<u32>,
+    },
+    /// Re-point every tracked link at this stow dir after it moved
+    #[command(alias = "repair")]
+    Relocate {
+        /// Show what would be re-pointed without changing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// The stow dir's previous location, to take over its state
+        #[arg(long, value_name = "OLD_DIR")]
+        from: Option<PathBuf>,
+    },
+    /// Check configs, sources and deployed symlinks for problems
+    Doctor {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Check tag references for typos, dead conditions and unreachable sections
+    Lint {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
     },
     /// Scaffold a new towboat.toml manifest
     Init,
 }
 
-fn expand_tilde(path: &Path) -> PathBuf {
-    if path.to_string_lossy() == "~" {
-        match std::env::var("HOME") {
-            Ok(home) => PathBuf::from(home),
-            Err(_) => path.to_path_buf(),
-        }
-    } else if let Some(rest) = path.to_string_lossy().strip_prefix("~/") {
-        match std::env::var("HOME") {
-            Ok(home) => PathBuf::from(home).join(rest),
-            Err(_) => path.to_path_buf(),
-        }
-    } else {
-        path.to_path_buf()
-    }
-}
-
 fn main() -> Result<()> {
     let cli = Cli::parse();
     let stow_dir = cli.dir.canonicalize().unwrap_or(cli.dir);
-    let target_dir = expand_tilde(&cli.target);
+    let target_dir = towboat::config::expand_tilde(&cli.target);
 
     match cli.command {
         Commands::Sync {
             package,
             dry_run,
             force,
+            no_hooks,
+            no_scripts,
+            jobs,
+            paranoid,
+            simulate,
+        } => {
+            let options = SyncOptions {
+                dry_run,
+                force,
+                no_hooks,
+                no_scripts,
+                overrides: simulate.into(),
+                jobs,
+                paranoid,
+                no_generation: false,
+            };
+            towboat::commands::sync::run(&stow_dir, &target_dir, package.as_deref(), &options)
+        }
+        Commands::Watch {
+            debounce,
+            no_hooks,
+            jobs,
+        } => {
+            let options = WatchOptions {
+                debounce: std::time::Duration::from_millis(debounce),
+                no_hooks,
+                jobs,
+            };
+            towboat::commands::watch::run(&stow_dir, &target_dir, &options)
+        }
+        Commands::Status {
+            package,
+            format,
+            check,
+            quiet,
+            paranoid,
+        } => {
+            let options = StatusOptions {
+                format,
+                quiet,
+                paranoid,
+            };
+            let health = towboat::commands::status::run(
+                &stow_dir,
+                &target_dir,
+                package.as_deref(),
+                &options,
+            )?;
+            if check {
+                std::process::exit(health.exit_code());
+            }
+            Ok(())
+        }
+        Commands::Diff {
+            package,
+            format,
+            color,
+            unified,
+            drifted,
+            simulate,
         } => {
-            towboat::commands::sync::run(&stow_dir, &target_dir, package.as_deref(), dry_run, force)
+            let options = DiffOptions {
+                format,
+                color: color.enabled(),
+                context: unified,
+                drifted,
+                overrides: simulate.into(),
+            };
+            towboat::commands::diff::run(&stow_dir, &target_dir, package.as_deref(), &options)
+        }
+        Commands::Explain { path, simulate } => {
+            towboat::commands::explain::run(&stow_dir, &target_dir, &path, &simulate.into())
+        }
+        Commands::Render { path, simulate } => {
+            towboat::commands::render::run(&stow_dir, &path, &simulate.into())
+        }
+        Commands::Export {
+            package,
+            out,
+            tar,
+            simulate,
+        } => {
+            let dest = match (out, tar) {
+                (Some(dir), _) => ExportDest::Dir(dir),
+                (None, Some(path)) => ExportDest::Tar(path),
+                (None, None) => unreachable!("clap requires --out or --tar"),
+            };
+            let options = ExportOptions {
+                dest,
+                overrides: simulate.into(),
+            };
+            towboat::commands::export::run(&stow_dir, package.as_deref(), &options)
+        }
+        Commands::Generations => towboat::commands::generations::run(&stow_dir),
+        Commands::Log { package, since } => {
+            towboat::commands::log::run(&stow_dir, &LogOptions { package, since })
         }
-        Commands::Status { package } => {
-            towboat::commands::status::run(&stow_dir, &target_dir, package.as_deref())
+        Commands::Rollback { generation } => {
+            towboat::commands::rollback::run(&stow_dir, &target_dir, generation)
         }
-        Commands::Diff { package } => {
-            towboat::commands::diff::run(&stow_dir, &target_dir, package.as_deref())
+        Commands::Relocate { dry_run, from } => towboat::commands::relocate::run(
+            &stow_dir,
+            &target_dir,
+            &RelocateOptions { dry_run, from },
+        ),
+        Commands::Doctor { format } => {
+            towboat::commands::doctor::run(&stow_dir, &target_dir, format)
         }
+        Commands::Lint { format } => towboat::commands::lint::run(&stow_dir, format),
         Commands::Init => towboat::commands::init::run(&stow_dir),
     }
 }
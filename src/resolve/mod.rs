@@ -2,8 +2,11 @@
 
 pub mod resolver;
 
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::PathBuf;
 
+use serde::{Deserialize, Serialize};
+
 /// A file that has been resolved (tags processed, templates substituted).
 #[derive(Debug, Clone)]
 pub struct ResolvedFile {
@@ -29,3 +32,97 @@ pub struct ResolveOutcome {
     /// Errors that occurred during resolution (non-fatal, collected).
     pub errors: Vec<String>,
 }
+
+/// The manifest values that shaped a resolved file.
+///
+/// Recorded in the lock so that editing `[variables]` or the active tags
+/// re-resolves affected files just like editing their source.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Inputs {
+    /// Tags referenced by the file's in-file sections, and whether each was active.
+    #[serde(default)]
+    pub tags: BTreeMap<String, bool>,
+    /// Variables substituted into the file, with their values.
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+}
+
+impl Inputs {
+    /// Whether these inputs still hold for the given manifest values.
+    ///
+    /// For an unchanged source this matches recomputing the inputs, without
+    /// reading the file: if no recorded tag flipped, the same sections are kept
+    /// and the same variables referenced.
+    pub fn holds(
+        &self,
+        active_tags: &HashSet<String>,
+        variables: &HashMap<String, String>,
+    ) -> bool {
+        self.tags
+            .iter()
+            .all(|(tag, active)| active_tags.contains(tag) == *active)
+            && self
+                .variables
+                .iter()
+                .all(|(name, value)| variables.get(name) == Some(value))
+    }
+
+    /// Human-readable reasons why `current` differs from these (locked) inputs.
+    ///
+    /// Only tags and variables present in both are compared; a file that starts
+    /// or stops referencing something has a changed source or a changed tag.
+    pub fn changes(&self, current: &Inputs) -> Vec<String> {
+        let mut changes = Vec::new();
+        for (tag, was_active) in &self.tags {
+            match current.tags.get(tag) {
+                Some(true) if !was_active => changes.push(format!("tag `{tag}` now active")),
+                Some(false) if *was_active => changes.push(format!("tag `{tag}` no longer active")),
+                _ => {}
+            }
+        }
+        for (name, value) in &self.variables {
+            if current.variables.get(name).is_some_and(|v| v != value) {
+                changes.push(format!("variable `{name}` changed"));
+            }
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs(tags: &[(&str, bool)], variables: &[(&str, &str)]) -> Inputs {
+        Inputs {
+            tags: tags.iter().map(|(t, a)| (t.to_string(), *a)).collect(),
+            variables: variables
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn changes_describe_tags_and_variables() {
+        let locked = inputs(&[("laptop", false), ("linux", true)], &[("email", "a@b")]);
+        let current = inputs(&[("laptop", true), ("linux", true)], &[("email", "c@d")]);
+        assert_eq!(
+            locked.changes(&current),
+            vec!["tag `laptop` now active", "variable `email` changed"]
+        );
+        assert!(locked.changes(&locked).is_empty());
+    }
+
+    #[test]
+    fn holds_checks_recorded_tags_and_variables() {
+        let locked = inputs(&[("laptop", false), ("linux", true)], &[("email", "a@b")]);
+        let active: HashSet<String> = ["linux".to_string()].into();
+        let vars = |email: &str| HashMap::from([("email".to_string(), email.to_string())]);
+
+        assert!(locked.holds(&active, &vars("a@b")));
+        assert!(!locked.holds(&active, &vars("c@d")));
+        assert!(!locked.holds(&HashSet::new(), &vars("a@b")));
+        assert!(!locked.holds(&active, &HashMap::new()));
+    }
+}
@@ -1,16 +1,17 @@
 //! File resolution: reads source, processes tags, substitutes templates, writes to resolved dir.
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 
+use rayon::prelude::*;
 use sha2::{Digest, Sha256};
 
 use crate::config::package::PackageConfig;
 use crate::discovery::walker::{self, DiscoveredFile};
 use crate::error::Result;
-use crate::resolve::{ResolveOutcome, ResolvedFile};
-use crate::tags::parser;
+use crate::resolve::{Inputs, ResolveOutcome, ResolvedFile};
+use crate::tags::{matcher, parser};
 use crate::template::engine;
 
 /// Compute SHA256 hash of content.
@@ -27,9 +28,17 @@ pub fn resolve_file(
     variables: &HashMap<String, String>,
 ) -> Result<(String, bool)> {
     let content = fs::read_to_string(source_path)?;
+    resolve_content(&content, active_tags, variables)
+}
 
+/// Resolve source content that has already been read.
+pub fn resolve_content(
+    content: &str,
+    active_tags: &HashSet<String>,
+    variables: &HashMap<String, String>,
+) -> Result<(String, bool)> {
     // Step 1: Process build tag sections
-    let parsed = parser::process_tags(&content, active_tags)?;
+    let parsed = parser::process_tags(content, active_tags)?;
 
     // Step 2: Substitute template variables
     let resolved = engine::render(&parsed.content, variables)?;
@@ -37,9 +46,36 @@ pub fn resolve_file(
     Ok((resolved, parsed.had_tags))
 }
 
+/// The tags and variables that shape `content` under the given manifest values.
+///
+/// Tags come from the file's in-file sections; variables are those referenced by
+/// the sections that are kept.
+pub fn inputs(
+    content: &str,
+    active_tags: &HashSet<String>,
+    variables: &HashMap<String, String>,
+) -> Result<Inputs> {
+    let mut tags = BTreeMap::new();
+    for (_line, expr) in parser::section_exprs(content) {
+        for tag in matcher::parse(&expr)?.variables() {
+            tags.insert(tag.to_string(), active_tags.contains(tag));
+        }
+    }
+
+    let parsed = parser::process_tags(content, active_tags)?;
+    let variables = engine::referenced_variables(&parsed.content)
+        .into_iter()
+        .filter_map(|name| variables.get(&name).map(|value| (name, value.clone())))
+        .collect();
+
+    Ok(Inputs { tags, variables })
+}
+
 /// Resolve all files in a package: discover, process, and write to resolved directory.
 ///
-/// Returns the resolve outcome and writes resolved files to `resolved_dir`.
+/// Files are resolved in parallel on the current rayon pool; the outcome lists
+/// them in discovery order. Returns the resolve outcome and writes resolved
+/// files to `resolved_dir`.
 pub fn resolve_package(
     package_name: &str,
     package_dir: &Path,
@@ -49,17 +85,23 @@ pub fn resolve_package(
     resolved_dir: &Path,
 ) -> Result<ResolveOutcome> {
     let discovered = walker::discover_package(package_dir, config, active_tags)?;
-    let mut outcome = ResolveOutcome::default();
+    let results: Vec<_> = discovered
+        .par_iter()
+        .map(|file| {
+            resolve_and_write(
+                package_name,
+                package_dir,
+                file,
+                active_tags,
+                variables,
+                resolved_dir,
+            )
+        })
+        .collect();
 
-    for file in discovered {
-        match resolve_and_write(
-            package_name,
-            package_dir,
-            &file,
-            active_tags,
-            variables,
-            resolved_dir,
-        ) {
+    let mut outcome = ResolveOutcome::default();
+    for (file, result) in discovered.iter().zip(results) {
+        match result {
             Ok(resolved) => outcome.resolved.push(resolved),
             Err(e) => outcome
                 .errors
@@ -70,25 +112,15 @@ pub fn resolve_package(
     Ok(outcome)
 }
 
-/// Resolve a single discovered file and write it to the resolved directory.
-fn resolve_and_write(
+/// Resolve a single discovered file in memory.
+pub fn resolve_discovered(
     package_name: &str,
-    _package_dir: &Path,
     file: &DiscoveredFile,
     active_tags: &HashSet<String>,
     variables: &HashMap<String, String>,
-    resolved_dir: &Path,
 ) -> Result<ResolvedFile> {
     let (content, had_tags) = resolve_file(&file.source_path, active_tags, variables)?;
 
-    // Write to resolved directory: .towboat/resolved/<package>/<relative_path>
-    let resolved_path = resolved_dir.join(package_name).join(&file.relative_path);
-
-    if let Some(parent) = resolved_path.parent() {
-        fs::create_dir_all(parent)?;
-    }
-    fs::write(&resolved_path, &content)?;
-
     // Source relative to stow directory (parent of package dir)
     let source_relative = PathBuf::from(package_name).join(&file.relative_path);
 
@@ -102,6 +134,28 @@ fn resolve_and_write(
     })
 }
 
+/// Resolve a single discovered file and write it to the resolved directory.
+fn resolve_and_write(
+    package_name: &str,
+    _package_dir: &Path,
+    file: &DiscoveredFile,
+    active_tags: &HashSet<String>,
+    variables: &HashMap<String, String>,
+    resolved_dir: &Path,
+) -> Result<ResolvedFile> {
+    let resolved = resolve_discovered(package_name, file, active_tags, variables)?;
+
+    // Write to resolved directory: <state>/resolved/<package>/<relative_path>
+    let resolved_path = resolved_dir.join(package_name).join(&file.relative_path);
+
+    if let Some(parent) = resolved_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&resolved_path, &resolved.content)?;
+
+    Ok(resolved)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -118,6 +172,26 @@ mod tests {
             .collect()
     }
 
+    #[test]
+    fn inputs_record_section_tags_and_used_variables() {
+        let content = "# {linux & !work-\nemail=${{ email }}\n# -linux & !work}\n# {macos-\nhost=${{ host }}\n# -macos}\n";
+        let vars = vars(&[("email", "a@b"), ("host", "box"), ("unused", "x")]);
+
+        let inputs = inputs(content, &tags(&["linux"]), &vars).unwrap();
+        assert_eq!(
+            inputs.tags,
+            BTreeMap::from([
+                ("linux".to_string(), true),
+                ("macos".to_string(), false),
+                ("work".to_string(), false),
+            ])
+        );
+        assert_eq!(
+            inputs.variables,
+            BTreeMap::from([("email".to_string(), "a@b".to_string())])
+        );
+    }
+
     #[test]
     fn compute_hash_deterministic() {
         let h1 = compute_hash("hello world");
@@ -1,7 +1,11 @@
 pub mod matcher;
+pub mod normal;
 pub mod parser;
 
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+
+pub use normal::{Cnf, Dnf, Literal};
 
 /// A boolean expression over build tags.
 ///
@@ -29,6 +33,150 @@ impl TagExpr {
             TagExpr::Or(lhs, rhs) => lhs.evaluate(active_tags) || rhs.evaluate(active_tags),
         }
     }
+
+    /// Evaluate every sub-expression, recording which were true or false.
+    ///
+    /// Unlike [`evaluate`](Self::evaluate), both sides of `&` and `|` are always
+    /// evaluated so the trace is complete.
+    pub fn trace(&self, active_tags: &HashSet<String>) -> EvalTrace<'_> {
+        let children = match self {
+            TagExpr::Tag(_) => Vec::new(),
+            TagExpr::Not(inner) => vec![inner.trace(active_tags)],
+            TagExpr::And(lhs, rhs) | TagExpr::Or(lhs, rhs) => {
+                vec![lhs.trace(active_tags), rhs.trace(active_tags)]
+            }
+        };
+        let value = match self {
+            TagExpr::Tag(name) => active_tags.contains(name),
+            TagExpr::Not(_) => !children[0].value,
+            TagExpr::And(..) => children[0].value && children[1].value,
+            TagExpr::Or(..) => children[0].value || children[1].value,
+        };
+        EvalTrace {
+            expr: self,
+            value,
+            children,
+        }
+    }
+
+    /// Every tag the expression references, sorted.
+    pub fn variables(&self) -> BTreeSet<&str> {
+        let mut tags = BTreeSet::new();
+        self.collect_variables(&mut tags);
+        tags
+    }
+
+    fn collect_variables<'a>(&'a self, tags: &mut BTreeSet<&'a str>) {
+        match self {
+            TagExpr::Tag(name) => {
+                tags.insert(name);
+            }
+            TagExpr::Not(inner) => inner.collect_variables(tags),
+            TagExpr::And(lhs, rhs) | TagExpr::Or(lhs, rhs) => {
+                lhs.collect_variables(tags);
+                rhs.collect_variables(tags);
+            }
+        }
+    }
+
+    /// True if the expression matches every set of active tags (e.g. `linux | !linux`).
+    pub fn is_always_true(&self) -> bool {
+        self.to_cnf().is_always_true()
+    }
+
+    /// True if the expression matches no set of active tags (e.g. `linux & !linux`).
+    pub fn is_always_false(&self) -> bool {
+        self.to_dnf().is_always_false()
+    }
+
+    /// Binding strength, for deciding where `Display` needs parentheses.
+    fn precedence(&self) -> u8 {
+        match self {
+            TagExpr::Or(..) => 0,
+            TagExpr::And(..) => 1,
+            TagExpr::Not(_) | TagExpr::Tag(_) => 2,
+        }
+    }
+
+    fn fmt_operand(&self, f: &mut fmt::Formatter<'_>, min_precedence: u8) -> fmt::Result {
+        if self.precedence() < min_precedence {
+            write!(f, "({self})")
+        } else {
+            write!(f, "{self}")
+        }
+    }
+}
+
+/// Pretty-prints with the parser's syntax and only the parentheses precedence
+/// requires: `linux & (laptop | desktop)`, `!(a & b)`, `a | b & c`.
+impl fmt::Display for TagExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TagExpr::Tag(name) => f.write_str(name),
+            TagExpr::Not(inner) => {
+                f.write_str("!")?;
+                inner.fmt_operand(f, 2)
+            }
+            TagExpr::And(lhs, rhs) => {
+                lhs.fmt_operand(f, 1)?;
+                f.write_str(" & ")?;
+                rhs.fmt_operand(f, 1)
+            }
+            TagExpr::Or(lhs, rhs) => {
+                lhs.fmt_operand(f, 0)?;
+                f.write_str(" | ")?;
+                rhs.fmt_operand(f, 0)
+            }
+        }
+    }
+}
+
+/// The result of [`TagExpr::trace`]: a value for every sub-expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalTrace<'a> {
+    pub expr: &'a TagExpr,
+    pub value: bool,
+    /// Traces of the operands, in order.
+    pub children: Vec<EvalTrace<'a>>,
+}
+
+impl EvalTrace<'_> {
+    fn fmt_indented(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        let value = if self.value { "true" } else { "false" };
+        write!(
+            f,
+            "{:indent$}{value:<6} {}",
+            "",
+            self.expr,
+            indent = depth * 2
+        )?;
+        if let TagExpr::Tag(_) = self.expr {
+            f.write_str(if self.value {
+                " (active)"
+            } else {
+                " (inactive)"
+            })?;
+        }
+        for child in &self.children {
+            writeln!(f)?;
+            child.fmt_indented(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// One line per sub-expression, indented by depth:
+///
+/// ```text
+/// false  linux & !work
+///   true   linux (active)
+///   false  !work
+///     true   work (active)
+/// ```
+impl fmt::Display for EvalTrace<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
 }
 
 #[cfg(test)]
@@ -95,6 +243,64 @@ mod tests {
         assert!(!expr.evaluate(&tags(&["macos", "laptop"])));
     }
 
+    fn parse(input: &str) -> TagExpr {
+        matcher::parse(input).unwrap()
+    }
+
+    #[test]
+    fn display_uses_minimal_parentheses() {
+        for (input, expected) in [
+            ("linux & (laptop | desktop)", "linux & (laptop | desktop)"),
+            ("(linux & laptop) | desktop", "linux & laptop | desktop"),
+            ("!(a & b)", "!(a & b)"),
+            ("!(a)", "!a"),
+            ("((a | b) | c)", "a | b | c"),
+            ("a | (b | c)", "a | b | c"),
+            ("!!a", "!!a"),
+        ] {
+            let expr = parse(input);
+            assert_eq!(expr.to_string(), expected, "{input}");
+            // Printing never changes the meaning
+            assert_eq!(parse(&expr.to_string()).to_dnf(), expr.to_dnf(), "{input}");
+        }
+    }
+
+    #[test]
+    fn trace_records_every_subterm() {
+        let expr = parse("linux & !work");
+        let trace = expr.trace(&tags(&["linux", "work"]));
+        assert!(!trace.value);
+        assert_eq!(
+            trace.to_string(),
+            "false  linux & !work\n  true   linux (active)\n  false  !work\n    true   work (active)"
+        );
+
+        // Both sides are traced even when the left decides
+        let expr = parse("macos & linux");
+        let trace = expr.trace(&tags(&["linux"]));
+        assert_eq!(trace.children.len(), 2);
+        assert!(trace.children[1].value);
+    }
+
+    #[test]
+    fn variables_are_sorted_and_unique() {
+        let expr = parse("work & (linux | !work) & laptop");
+        assert_eq!(
+            expr.variables().into_iter().collect::<Vec<_>>(),
+            vec!["laptop", "linux", "work"]
+        );
+    }
+
+    #[test]
+    fn constant_expressions() {
+        assert!(parse("linux | !linux").is_always_true());
+        assert!(parse("linux & !linux").is_always_false());
+        assert!(parse("(a | b) & !a & !b").is_always_false());
+        assert!(parse("!(a & !a)").is_always_true());
+        let expr = parse("linux & laptop");
+        assert!(!expr.is_always_true() && !expr.is_always_false());
+    }
+
     #[test]
     fn negation_in_and() {
         // linux & !server
@@ -0,0 +1,202 @@
+//! Normal forms of tag expressions.
+//!
+//! [`Dnf`] is an OR of ANDs of literals, [`Cnf`] an AND of ORs. Both are kept
+//! simplified: duplicate literals are merged, clauses that are trivially true
+//! (CNF) or false (DNF) are dropped, and clauses subsumed by a smaller one are
+//! removed. An empty DNF is therefore always false and an empty CNF always true.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::tags::TagExpr;
+
+/// A tag or its negation.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Literal {
+    pub tag: String,
+    pub negated: bool,
+}
+
+impl Literal {
+    fn complement(&self) -> Literal {
+        Literal {
+            tag: self.tag.clone(),
+            negated: !self.negated,
+        }
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negated {
+            f.write_str("!")?;
+        }
+        f.write_str(&self.tag)
+    }
+}
+
+type Clause = BTreeSet<Literal>;
+
+/// Disjunctive normal form: `a & b | !c`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dnf {
+    /// Each term is a conjunction of literals.
+    pub terms: Vec<Clause>,
+}
+
+/// Conjunctive normal form: `(a | b) & !c`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cnf {
+    /// Each clause is a disjunction of literals.
+    pub clauses: Vec<Clause>,
+}
+
+impl Dnf {
+    /// No term can be satisfied.
+    pub fn is_always_false(&self) -> bool {
+        self.terms.is_empty()
+    }
+}
+
+impl Cnf {
+    /// No clause can be falsified.
+    pub fn is_always_true(&self) -> bool {
+        self.clauses.is_empty()
+    }
+}
+
+impl TagExpr {
+    /// Convert to simplified disjunctive normal form.
+    pub fn to_dnf(&self) -> Dnf {
+        Dnf {
+            terms: normalize(self, false),
+        }
+    }
+
+    /// Convert to simplified conjunctive normal form.
+    pub fn to_cnf(&self) -> Cnf {
+        Cnf {
+            clauses: normalize(self, true),
+        }
+    }
+}
+
+/// Clauses of the DNF (or CNF) of `expr`, negations pushed to the tags.
+fn normalize(expr: &TagExpr, cnf: bool) -> Vec<Clause> {
+    simplify(expand(expr, false, cnf))
+}
+
+/// Expand `expr` (or `!expr` when `negated`) into clauses. In DNF a conjunction
+/// multiplies out and a disjunction concatenates; in CNF the other way round.
+fn expand(expr: &TagExpr, negated: bool, cnf: bool) -> Vec<Clause> {
+    match expr {
+        TagExpr::Tag(name) => vec![Clause::from([Literal {
+            tag: name.clone(),
+            negated,
+        }])],
+        TagExpr::Not(inner) => expand(inner, !negated, cnf),
+        TagExpr::And(lhs, rhs) | TagExpr::Or(lhs, rhs) => {
+            // De Morgan: under negation `&` becomes `|` and vice versa
+            let is_and = matches!(expr, TagExpr::And(..)) != negated;
+            let lhs = expand(lhs, negated, cnf);
+            let rhs = expand(rhs, negated, cnf);
+            if is_and != cnf {
+                lhs.iter()
+                    .flat_map(|a| rhs.iter().map(move |b| a.union(b).cloned().collect()))
+                    .collect()
+            } else {
+                lhs.into_iter().chain(rhs).collect()
+            }
+        }
+    }
+}
+
+/// Drop clauses containing a literal and its complement, then clauses that are
+/// supersets of (or equal to) another clause. The result is sorted.
+fn simplify(clauses: Vec<Clause>) -> Vec<Clause> {
+    let mut clauses: Vec<Clause> = clauses
+        .into_iter()
+        .filter(|clause| !clause.iter().any(|l| clause.contains(&l.complement())))
+        .collect();
+    clauses.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+    clauses.dedup();
+
+    let mut kept: Vec<Clause> = Vec::new();
+    for clause in clauses {
+        if !kept.iter().any(|smaller| smaller.is_subset(&clause)) {
+            kept.push(clause);
+        }
+    }
+    kept
+}
+
+fn fmt_clauses(
+    f: &mut fmt::Formatter<'_>,
+    clauses: &[Clause],
+    inner: &str,
+    outer: &str,
+    empty: &str,
+) -> fmt::Result {
+    if clauses.is_empty() {
+        return f.write_str(empty);
+    }
+    let parenthesize = clauses.len() > 1 && inner == " | ";
+    for (i, clause) in clauses.iter().enumerate() {
+        if i > 0 {
+            f.write_str(outer)?;
+        }
+        let literals: Vec<String> = clause.iter().map(Literal::to_string).collect();
+        if parenthesize && clause.len() > 1 {
+            write!(f, "({})", literals.join(inner))?;
+        } else {
+            f.write_str(&literals.join(inner))?;
+        }
+    }
+    Ok(())
+}
+
+/// `a & b | !c`; `false` when empty.
+impl fmt::Display for Dnf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_clauses(f, &self.terms, " & ", " | ", "false")
+    }
+}
+
+/// `(a | b) & !c`; `true` when empty.
+impl fmt::Display for Cnf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_clauses(f, &self.clauses, " | ", " & ", "true")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tags::matcher::parse;
+
+    #[test]
+    fn dnf_distributes_and_simplifies() {
+        let expr = parse("linux & (laptop | desktop)").unwrap();
+        assert_eq!(
+            expr.to_dnf().to_string(),
+            "desktop & linux | laptop & linux"
+        );
+        assert_eq!(expr.to_cnf().to_string(), "linux & (desktop | laptop)");
+
+        // Absorption: `a | a & b` is `a`
+        assert_eq!(parse("a | a & b").unwrap().to_dnf().to_string(), "a");
+        // De Morgan
+        assert_eq!(parse("!(a | b)").unwrap().to_dnf().to_string(), "!a & !b");
+        assert_eq!(parse("!(a & b)").unwrap().to_cnf().to_string(), "!a | !b");
+    }
+
+    #[test]
+    fn constants_print_as_true_and_false() {
+        let expr = parse("linux & !linux").unwrap();
+        assert!(expr.to_dnf().is_always_false());
+        assert_eq!(expr.to_dnf().to_string(), "false");
+
+        let expr = parse("linux | !linux").unwrap();
+        assert!(expr.to_cnf().is_always_true());
+        assert_eq!(expr.to_cnf().to_string(), "true");
+    }
+}
@@ -107,6 +107,17 @@ pub fn has_tag_sections(content: &str) -> bool {
     })
 }
 
+/// Tag expressions of every section opened in `content`, with 1-based line numbers.
+///
+/// Expressions are returned unparsed so callers can report parse errors themselves.
+pub fn section_exprs(content: &str) -> Vec<(usize, String)> {
+    content
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| try_parse_open(line.trim()).map(|(_, expr)| (i + 1, expr)))
+        .collect()
+}
+
 struct SectionState {
     prefix: String,
     tag_expr_str: String,
@@ -320,4 +331,21 @@ unix-only config
         assert!(result.content.contains("    indented content"));
         assert!(result.content.contains("        more indented"));
     }
+
+    #[test]
+    fn section_exprs_lists_open_markers() {
+        let content = "\
+a
+# {linux & !work-
+b
+# -linux & !work}
+// {lnux-
+c
+// -lnux}
+";
+        assert_eq!(
+            section_exprs(content),
+            vec![(2, "linux & !work".to_string()), (5, "lnux".to_string())]
+        );
+    }
 }
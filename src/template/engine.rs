@@ -85,6 +85,33 @@ pub fn render(content: &str, variables: &HashMap<String, String>) -> Result<Stri
     Ok(String::from_utf8(output).expect("template render produced invalid UTF-8"))
 }
 
+/// Names of the variables referenced by `${{ var }}` placeholders, in order of first use.
+///
+/// Escaped (`\${{`) and unterminated placeholders are skipped, as in [`render`].
+pub fn referenced_variables(content: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut rest = content;
+
+    while let Some(pos) = rest.find("${{") {
+        let escaped = rest[..pos].ends_with('\\');
+        let after = &rest[pos + 3..];
+        if escaped {
+            rest = after;
+            continue;
+        }
+        let Some(end) = after.find("}}") else {
+            break;
+        };
+        let name = after[..end].trim();
+        if !name.is_empty() && !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+        rest = &after[end + 2..];
+    }
+
+    names
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -214,4 +241,10 @@ mod tests {
         let result = render(content, &vars(&[("icon", "\u{F0109} 日本")])).unwrap();
         assert_eq!(result, "glyph: \u{F0109} 日本");
     }
+
+    #[test]
+    fn referenced_variables_skip_escapes_and_duplicates() {
+        let content = "${{ a }} \\${{ escaped }} ${{b}} ${{ a }} ${{}} ${{ unterminated";
+        assert_eq!(referenced_variables(content), vec!["a", "b"]);
+    }
 }
@@ -3,16 +3,42 @@
 use std::collections::{HashMap, HashSet};
 use std::fs;
 use tempfile::TempDir;
+use towboat::commands::diff::DiffOptions;
+use towboat::commands::status::StatusOptions;
+use towboat::commands::sync::SyncOptions;
+
+/// Where towboat keeps resolved files and the lock for `stow`.
+fn state_dir(stow: &std::path::Path) -> std::path::PathBuf {
+    towboat::deploy::state::StateDir::for_stow(stow)
+        .unwrap()
+        .root()
+        .to_path_buf()
+}
+
+/// Where towboat keeps state for `stow` by default, run with `XDG_STATE_HOME=home`.
+fn default_state_dir(home: &std::path::Path, stow: &std::path::Path) -> std::path::PathBuf {
+    home.join("towboat")
+        .join(towboat::deploy::state::repo_id(stow))
+}
+
+/// Drop `state_dir` from the manifest of a [`setup_stow_dir`] stow.
+fn use_default_state_dir(stow: &std::path::Path) {
+    let path = stow.join("towboat.toml");
+    let manifest = fs::read_to_string(&path).unwrap();
+    fs::write(&path, manifest.replace("state_dir = \".state\"\n", "")).unwrap();
+}
 
 /// Create a complete stow directory with manifest, packages, and configs.
 fn setup_stow_dir() -> TempDir {
     let dir = TempDir::new().unwrap();
 
-    // Create towboat.toml manifest
+    // Create towboat.toml manifest; state stays inside the temp dir, tests of
+    // the default location drop `state_dir` and set XDG_STATE_HOME instead
     fs::write(
         dir.path().join("towboat.toml"),
         r#"
 [system]
+state_dir = ".state"
 tags = ["linux", "laptop", "work"]
 
 [variables]
@@ -100,8 +126,7 @@ fn full_sync_workflow() {
         stow.path(),
         target.path(),
         None, // all packages
-        false,
-        false,
+        &SyncOptions::default(),
     )
     .unwrap();
 
@@ -132,11 +157,11 @@ fn full_sync_workflow() {
     assert!(vimrc.is_symlink());
 
     // Verify lock file was created
-    let lock_path = stow.path().join(".towboat/towboat.lock");
+    let lock_path = state_dir(stow.path()).join("towboat.lock");
     assert!(lock_path.exists());
 
     // Verify resolved directory structure
-    let resolved_dir = stow.path().join(".towboat/resolved");
+    let resolved_dir = state_dir(stow.path()).join("resolved");
     assert!(resolved_dir.join("bash/.bashrc").exists());
     assert!(resolved_dir.join("bash/.profile").exists());
     assert!(resolved_dir.join("git/.gitconfig").exists());
@@ -148,7 +173,13 @@ fn sync_single_package() {
     let stow = setup_stow_dir();
     let target = TempDir::new().unwrap();
 
-    towboat::commands::sync::run(stow.path(), target.path(), Some("bash"), false, false).unwrap();
+    towboat::commands::sync::run(
+        stow.path(),
+        target.path(),
+        Some("bash"),
+        &SyncOptions::default(),
+    )
+    .unwrap();
 
     assert!(target.path().join(".bashrc").is_symlink());
     assert!(target.path().join(".profile").is_symlink());
@@ -165,8 +196,7 @@ fn sync_nonexistent_package_errors() {
         stow.path(),
         target.path(),
         Some("nonexistent"),
-        false,
-        false,
+        &SyncOptions::default(),
     );
     assert!(result.is_err());
 }
@@ -176,7 +206,16 @@ fn sync_dry_run_no_changes() {
     let stow = setup_stow_dir();
     let target = TempDir::new().unwrap();
 
-    towboat::commands::sync::run(stow.path(), target.path(), None, true, false).unwrap();
+    towboat::commands::sync::run(
+        stow.path(),
+        target.path(),
+        None,
+        &SyncOptions {
+            dry_run: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
 
     // Nothing should be created
     assert!(!target.path().join(".bashrc").exists());
@@ -189,10 +228,12 @@ fn sync_idempotent() {
     let target = TempDir::new().unwrap();
 
     // First sync
-    towboat::commands::sync::run(stow.path(), target.path(), None, false, false).unwrap();
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
 
     // Second sync should succeed without force
-    towboat::commands::sync::run(stow.path(), target.path(), None, false, false).unwrap();
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
 
     // Files should still be correct
     let content = fs::read_to_string(target.path().join(".bashrc")).unwrap();
@@ -208,7 +249,16 @@ fn sync_force_overwrites() {
     fs::write(target.path().join(".bashrc"), "existing content").unwrap();
 
     // Sync with force should succeed
-    towboat::commands::sync::run(stow.path(), target.path(), None, false, true).unwrap();
+    towboat::commands::sync::run(
+        stow.path(),
+        target.path(),
+        None,
+        &SyncOptions {
+            force: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
 
     let content = fs::read_to_string(target.path().join(".bashrc")).unwrap();
     assert!(content.contains("--color=auto"));
@@ -220,7 +270,8 @@ fn sync_detects_source_change() {
     let target = TempDir::new().unwrap();
 
     // First sync
-    towboat::commands::sync::run(stow.path(), target.path(), None, false, false).unwrap();
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
 
     // Modify source
     fs::write(
@@ -236,7 +287,8 @@ alias ll='ls -la'
     .unwrap();
 
     // Re-sync
-    towboat::commands::sync::run(stow.path(), target.path(), None, false, false).unwrap();
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
 
     // Should include the new alias
     let content = fs::read_to_string(target.path().join(".bashrc")).unwrap();
@@ -295,7 +347,8 @@ fn status_with_no_lock_file() {
     let target = TempDir::new().unwrap();
 
     // Status before any sync should work gracefully
-    towboat::commands::status::run(stow.path(), target.path(), None).unwrap();
+    towboat::commands::status::run(stow.path(), target.path(), None, &StatusOptions::default())
+        .unwrap();
 }
 
 #[test]
@@ -304,13 +357,16 @@ fn full_lifecycle_sync_status_diff() {
     let target = TempDir::new().unwrap();
 
     // Sync
-    towboat::commands::sync::run(stow.path(), target.path(), None, false, false).unwrap();
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
 
     // Status should work
-    towboat::commands::status::run(stow.path(), target.path(), None).unwrap();
+    towboat::commands::status::run(stow.path(), target.path(), None, &StatusOptions::default())
+        .unwrap();
 
     // Diff should work (nothing changed)
-    towboat::commands::diff::run(stow.path(), target.path(), None).unwrap();
+    towboat::commands::diff::run(stow.path(), target.path(), None, &DiffOptions::default())
+        .unwrap();
 
     // Modify source and check diff
     fs::write(
@@ -319,7 +375,8 @@ fn full_lifecycle_sync_status_diff() {
     )
     .unwrap();
 
-    towboat::commands::diff::run(stow.path(), target.path(), None).unwrap();
+    towboat::commands::diff::run(stow.path(), target.path(), None, &DiffOptions::default())
+        .unwrap();
 }
 
 #[test]
@@ -373,6 +430,321 @@ fn cli_sync_dry_run() {
     assert!(!target.path().join(".bashrc").exists());
 }
 
+#[test]
+fn cli_status_json_format() {
+    use assert_cmd::Command;
+
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
+    fs::write(
+        state_dir(stow.path()).join("resolved/bash/.profile"),
+        "user edited this\n",
+    )
+    .unwrap();
+
+    let output = Command::cargo_bin("towboat")
+        .unwrap()
+        .args([
+            "--dir",
+            stow.path().to_str().unwrap(),
+            "--target",
+            target.path().to_str().unwrap(),
+            "status",
+            "--format",
+            "json",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let files = report["files"].as_array().unwrap();
+    assert_eq!(files.len(), 4);
+
+    let profile = files
+        .iter()
+        .find(|f| f["target"] == ".profile")
+        .expect(".profile should be reported");
+    assert_eq!(profile["package"], "bash");
+    assert_eq!(profile["source"], "bash/.profile");
+    assert_eq!(profile["state"], "drifted");
+    assert_ne!(profile["resolved_hash"], profile["locked_resolved_hash"]);
+
+    let bashrc = files.iter().find(|f| f["target"] == ".bashrc").unwrap();
+    assert_eq!(bashrc["state"], "up_to_date");
+}
+
+#[test]
+fn cli_status_porcelain_format() {
+    use assert_cmd::Command;
+
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+
+    towboat::commands::sync::run(
+        stow.path(),
+        target.path(),
+        Some("bash"),
+        &SyncOptions::default(),
+    )
+    .unwrap();
+
+    Command::cargo_bin("towboat")
+        .unwrap()
+        .args([
+            "--dir",
+            stow.path().to_str().unwrap(),
+            "--target",
+            target.path().to_str().unwrap(),
+            "status",
+            "bash",
+            "--format",
+            "porcelain",
+        ])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "up_to_date\tbash\tbash/.bashrc\t.bashrc\n",
+        ));
+}
+
+#[test]
+fn cli_diff_json_format() {
+    use assert_cmd::Command;
+
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+
+    towboat::commands::sync::run(
+        stow.path(),
+        target.path(),
+        Some("bash"),
+        &SyncOptions::default(),
+    )
+    .unwrap();
+    fs::write(
+        stow.path().join("bash/.profile"),
+        "source ~/.bashrc\nexport EDITOR=vim\n",
+    )
+    .unwrap();
+
+    let output = Command::cargo_bin("towboat")
+        .unwrap()
+        .args([
+            "--dir",
+            stow.path().to_str().unwrap(),
+            "--target",
+            target.path().to_str().unwrap(),
+            "diff",
+            "--format",
+            "json",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let files = report["files"].as_array().unwrap();
+
+    let profile = files.iter().find(|f| f["target"] == ".profile").unwrap();
+    assert_eq!(profile["change"], "modified");
+    let hunks = profile["hunks"].as_array().unwrap();
+    assert_eq!(hunks.len(), 1);
+    assert_eq!(hunks[0]["lines"][0], " source ~/.bashrc");
+    assert_eq!(hunks[0]["lines"][1], "+export EDITOR=vim");
+
+    // git package was never synced, so its file shows up as added
+    let gitconfig = files.iter().find(|f| f["target"] == ".gitconfig").unwrap();
+    assert_eq!(gitconfig["change"], "added");
+    assert!(gitconfig["old_hash"].is_null());
+}
+
+#[test]
+fn cli_diff_unified_output() {
+    use assert_cmd::Command;
+
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+
+    towboat::commands::sync::run(
+        stow.path(),
+        target.path(),
+        Some("bash"),
+        &SyncOptions::default(),
+    )
+    .unwrap();
+
+    // Insert a line in the middle: a positional diff would flag every line after it
+    fs::write(
+        stow.path().join("bash/.bashrc"),
+        r#"#!/bin/bash
+# Common
+export EDITOR=vim
+export PATH=$PATH:/usr/local/bin
+
+# {linux-
+alias ls='ls --color=auto'
+# -linux}
+
+# {macos-
+alias ls='ls -G'
+# -macos}
+"#,
+    )
+    .unwrap();
+
+    // Retarget .profile and drop nothing else
+    fs::write(
+        stow.path().join("bash/boat.toml"),
+        r#"
+[targets]
+".bashrc" = { tags = "linux" }
+".profile" = { target = ".bash_profile", tags = "linux | macos" }
+"#,
+    )
+    .unwrap();
+
+    Command::cargo_bin("towboat")
+        .unwrap()
+        .args([
+            "--dir",
+            stow.path().to_str().unwrap(),
+            "--target",
+            target.path().to_str().unwrap(),
+            "diff",
+            "bash",
+            "--color",
+            "never",
+            "-U",
+            "1",
+        ])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "M bash/.bashrc -> .bashrc\n\
+             --- a/.bashrc\n\
+             +++ b/.bashrc\n\
+             @@ -2,2 +2,3 @@\n \
+             # Common\n\
+             +export EDITOR=vim\n \
+             export PATH=$PATH:/usr/local/bin\n",
+        ))
+        .stdout(predicates::str::contains(
+            "R bash/.profile: .profile -> .bash_profile",
+        ));
+}
+
+#[test]
+fn cli_diff_removed_and_drifted() {
+    use assert_cmd::Command;
+    use predicates::prelude::*;
+
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+
+    towboat::commands::sync::run(
+        stow.path(),
+        target.path(),
+        Some("bash"),
+        &SyncOptions::default(),
+    )
+    .unwrap();
+
+    // Edit the resolved .bashrc (drift) and drop .profile from config (removal)
+    let resolved_bashrc = state_dir(stow.path()).join("resolved/bash/.bashrc");
+    let mut edited = fs::read_to_string(&resolved_bashrc).unwrap();
+    edited.push_str("alias gs='git status'\n");
+    fs::write(&resolved_bashrc, edited).unwrap();
+    fs::write(
+        stow.path().join("bash/boat.toml"),
+        "[targets]\n\".bashrc\" = { tags = \"linux\" }\n",
+    )
+    .unwrap();
+
+    let args = [
+        "--dir",
+        stow.path().to_str().unwrap(),
+        "--target",
+        target.path().to_str().unwrap(),
+        "diff",
+        "bash",
+        "--color",
+        "never",
+    ];
+
+    // Without --drifted, edits to resolved files are not sync changes
+    Command::cargo_bin("towboat")
+        .unwrap()
+        .args(args)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "D .profile (would be removed)\n--- a/.profile\n+++ /dev/null\n@@ -1,1 +0,0 @@\n-source ~/.bashrc\n",
+        ))
+        .stdout(predicates::str::contains("E bash/.bashrc").not());
+
+    Command::cargo_bin("towboat")
+        .unwrap()
+        .args(args)
+        .arg("--drifted")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("E bash/.bashrc -> .bashrc"))
+        .stdout(predicates::str::contains("+alias gs='git status'"));
+}
+
+#[test]
+fn cli_diff_shows_packages_removed_from_manifest() {
+    use assert_cmd::Command;
+
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
+
+    let manifest = fs::read_to_string(stow.path().join("towboat.toml")).unwrap();
+    fs::write(
+        stow.path().join("towboat.toml"),
+        manifest.replace("git = {}\n", ""),
+    )
+    .unwrap();
+
+    let diff = |extra: &[&str]| {
+        let mut cmd = Command::cargo_bin("towboat").unwrap();
+        cmd.args([
+            "--dir",
+            stow.path().to_str().unwrap(),
+            "--target",
+            target.path().to_str().unwrap(),
+            "diff",
+            "--color",
+            "never",
+        ])
+        .args(extra);
+        cmd
+    };
+    diff(&[])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "D .gitconfig (would be removed)\n--- a/.gitconfig\n+++ /dev/null\n",
+        ));
+    diff(&["--format", "porcelain"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "D\tgit\tgit/.gitconfig\t.gitconfig",
+        ));
+    // A package filter only covers that package
+    diff(&["bash"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("No changes would be applied."));
+}
+
 // --- Edge case tests ---
 
 #[test]
@@ -381,7 +753,8 @@ fn sync_package_removed_from_manifest_cleans_up() {
     let target = TempDir::new().unwrap();
 
     // First sync — all packages deployed
-    towboat::commands::sync::run(stow.path(), target.path(), None, false, false).unwrap();
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
     assert!(target.path().join(".vimrc").is_symlink());
 
     // Remove vim from manifest
@@ -389,6 +762,7 @@ fn sync_package_removed_from_manifest_cleans_up() {
         stow.path().join("towboat.toml"),
         r#"
 [system]
+state_dir = ".state"
 tags = ["linux", "laptop", "work"]
 
 [variables]
@@ -403,7 +777,8 @@ git = {}
     .unwrap();
 
     // Re-sync — vim should be cleaned up
-    towboat::commands::sync::run(stow.path(), target.path(), None, false, false).unwrap();
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
     assert!(
         !target.path().join(".vimrc").exists(),
         ".vimrc should be removed after vim is dropped from manifest"
@@ -416,7 +791,8 @@ fn sync_file_removed_from_boat_toml_cleans_up() {
     let target = TempDir::new().unwrap();
 
     // First sync
-    towboat::commands::sync::run(stow.path(), target.path(), None, false, false).unwrap();
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
     assert!(target.path().join(".profile").is_symlink());
 
     // Remove .profile from boat.toml targets
@@ -430,7 +806,13 @@ fn sync_file_removed_from_boat_toml_cleans_up() {
     .unwrap();
 
     // Re-sync bash
-    towboat::commands::sync::run(stow.path(), target.path(), Some("bash"), false, false).unwrap();
+    towboat::commands::sync::run(
+        stow.path(),
+        target.path(),
+        Some("bash"),
+        &SyncOptions::default(),
+    )
+    .unwrap();
     assert!(
         !target.path().join(".profile").exists(),
         ".profile should be removed after dropping from boat.toml"
@@ -446,6 +828,7 @@ fn sync_package_tag_requirement_not_met_skips() {
         dir.path().join("towboat.toml"),
         r#"
 [system]
+state_dir = ".state"
 tags = ["linux"]
 
 [packages]
@@ -466,7 +849,7 @@ restricted = { tags = ["server"] }
     .unwrap();
     fs::write(pkg.join(".config"), "server only\n").unwrap();
 
-    towboat::commands::sync::run(dir.path(), target.path(), None, false, false).unwrap();
+    towboat::commands::sync::run(dir.path(), target.path(), None, &SyncOptions::default()).unwrap();
 
     assert!(
         !target.path().join(".config").exists(),
@@ -483,6 +866,7 @@ fn sync_target_remap() {
         dir.path().join("towboat.toml"),
         r#"
 [system]
+state_dir = ".state"
 tags = ["linux"]
 
 [packages]
@@ -503,7 +887,7 @@ myapp = {}
     .unwrap();
     fs::write(pkg.join("dev-config.sh"), "export DEV=1\n").unwrap();
 
-    towboat::commands::sync::run(dir.path(), target.path(), None, false, false).unwrap();
+    towboat::commands::sync::run(dir.path(), target.path(), None, &SyncOptions::default()).unwrap();
 
     assert!(
         target.path().join(".config.sh").is_symlink(),
@@ -526,6 +910,7 @@ fn sync_default_include_all() {
         dir.path().join("towboat.toml"),
         r#"
 [system]
+state_dir = ".state"
 tags = ["default"]
 
 [packages]
@@ -550,7 +935,7 @@ default_tag = "default"
     fs::write(pkg.join(".aliasrc"), "alias hi='echo hi'\n").unwrap();
     fs::write(pkg.join(".envrc"), "export FOO=1\n").unwrap();
 
-    towboat::commands::sync::run(dir.path(), target.path(), None, false, false).unwrap();
+    towboat::commands::sync::run(dir.path(), target.path(), None, &SyncOptions::default()).unwrap();
 
     assert!(
         target.path().join(".aliasrc").is_symlink(),
@@ -571,6 +956,7 @@ fn sync_with_nested_directory() {
         dir.path().join("towboat.toml"),
         r#"
 [system]
+state_dir = ".state"
 tags = ["linux"]
 
 [packages]
@@ -594,7 +980,7 @@ config = {}
     fs::write(nested.join("settings.toml"), "theme = \"dark\"\n").unwrap();
     fs::write(nested.join("keybinds.toml"), "save = \"ctrl+s\"\n").unwrap();
 
-    towboat::commands::sync::run(dir.path(), target.path(), None, false, false).unwrap();
+    towboat::commands::sync::run(dir.path(), target.path(), None, &SyncOptions::default()).unwrap();
 
     assert!(
         target
@@ -657,7 +1043,8 @@ fn sync_conflict_detection_without_force() {
     let target = TempDir::new().unwrap();
 
     // First sync
-    towboat::commands::sync::run(stow.path(), target.path(), None, false, false).unwrap();
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
 
     // Modify BOTH source AND resolved file to create a conflict
     fs::write(
@@ -666,11 +1053,12 @@ fn sync_conflict_detection_without_force() {
     )
     .unwrap();
 
-    let resolved_bashrc = stow.path().join(".towboat/resolved/bash/.bashrc");
+    let resolved_bashrc = state_dir(stow.path()).join("resolved/bash/.bashrc");
     fs::write(&resolved_bashrc, "manually edited resolved file\n").unwrap();
 
     // Re-sync without force should report conflict
-    let result = towboat::commands::sync::run(stow.path(), target.path(), None, false, false);
+    let result =
+        towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default());
     assert!(result.is_err(), "Should error on conflict without --force");
     let err = result.unwrap_err().to_string();
     assert!(
@@ -685,7 +1073,8 @@ fn sync_conflict_resolved_with_force() {
     let target = TempDir::new().unwrap();
 
     // First sync
-    towboat::commands::sync::run(stow.path(), target.path(), None, false, false).unwrap();
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
 
     // Create a conflict (both source and resolved changed)
     fs::write(
@@ -693,11 +1082,20 @@ fn sync_conflict_resolved_with_force() {
         "#!/bin/bash\nnew source\n",
     )
     .unwrap();
-    let resolved_bashrc = stow.path().join(".towboat/resolved/bash/.bashrc");
+    let resolved_bashrc = state_dir(stow.path()).join("resolved/bash/.bashrc");
     fs::write(&resolved_bashrc, "edited resolved\n").unwrap();
 
     // Force should overwrite
-    towboat::commands::sync::run(stow.path(), target.path(), None, false, true).unwrap();
+    towboat::commands::sync::run(
+        stow.path(),
+        target.path(),
+        None,
+        &SyncOptions {
+            force: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
 
     let content = fs::read_to_string(target.path().join(".bashrc")).unwrap();
     assert!(
@@ -712,14 +1110,16 @@ fn sync_preserves_drifted_resolved_file() {
     let target = TempDir::new().unwrap();
 
     // First sync
-    towboat::commands::sync::run(stow.path(), target.path(), None, false, false).unwrap();
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
 
     // Edit only the resolved file (user edits via symlink)
-    let resolved_profile = stow.path().join(".towboat/resolved/bash/.profile");
+    let resolved_profile = state_dir(stow.path()).join("resolved/bash/.profile");
     fs::write(&resolved_profile, "user edited this\n").unwrap();
 
     // Re-sync without force — should preserve the drift
-    towboat::commands::sync::run(stow.path(), target.path(), None, false, false).unwrap();
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
 
     let content = fs::read_to_string(target.path().join(".profile")).unwrap();
     assert_eq!(
@@ -733,20 +1133,60 @@ fn lock_file_persists_across_syncs() {
     let stow = setup_stow_dir();
     let target = TempDir::new().unwrap();
 
-    towboat::commands::sync::run(stow.path(), target.path(), None, false, false).unwrap();
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
 
-    let lock_content = fs::read_to_string(stow.path().join(".towboat/towboat.lock")).unwrap();
+    let lock_content = fs::read_to_string(state_dir(stow.path()).join("towboat.lock")).unwrap();
     assert!(lock_content.contains("version = 1"));
     assert!(lock_content.contains("last_sync"));
     assert!(lock_content.contains("bash/.bashrc"));
 
     // Second sync should update lock
-    towboat::commands::sync::run(stow.path(), target.path(), None, false, false).unwrap();
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
 
-    let lock_content2 = fs::read_to_string(stow.path().join(".towboat/towboat.lock")).unwrap();
+    let lock_content2 = fs::read_to_string(state_dir(stow.path()).join("towboat.lock")).unwrap();
     assert!(lock_content2.contains("version = 1"));
 }
 
+#[test]
+fn sync_respects_global_and_package_ignores() {
+    let dir = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+
+    fs::write(
+        dir.path().join("towboat.toml"),
+        r#"
+[system]
+state_dir = ".state"
+tags = ["default"]
+
+[packages.misc]
+ignore = ["README.md"]
+"#,
+    )
+    .unwrap();
+    fs::write(dir.path().join(".boatignore"), "*.swp\n").unwrap();
+
+    let pkg = dir.path().join("misc");
+    fs::create_dir_all(&pkg).unwrap();
+    fs::write(pkg.join(".aliasrc"), "alias hi='echo hi'\n").unwrap();
+    fs::write(pkg.join(".aliasrc.swp"), "swap").unwrap();
+    fs::write(pkg.join("README.md"), "# misc\n").unwrap();
+
+    towboat::commands::sync::run(dir.path(), target.path(), None, &SyncOptions::default()).unwrap();
+
+    assert!(target.path().join(".aliasrc").is_symlink());
+    assert!(
+        !target.path().join(".aliasrc.swp").exists(),
+        "Global .boatignore should exclude swap files"
+    );
+    assert!(
+        !target.path().join("README.md").exists(),
+        "Package ignore patterns should exclude README.md"
+    );
+}
+
 // --- Inline config tests ---
 
 #[test]
@@ -759,6 +1199,7 @@ fn sync_with_inline_config() {
         dir.path().join("towboat.toml"),
         r#"
 [system]
+state_dir = ".state"
 tags = ["linux"]
 
 [packages.myapp]
@@ -776,7 +1217,7 @@ target_dir = "~"
     fs::write(pkg.join(".apprc"), "app config\n").unwrap();
     fs::write(pkg.join(".appconf"), "app conf\n").unwrap();
 
-    towboat::commands::sync::run(dir.path(), target.path(), None, false, false).unwrap();
+    towboat::commands::sync::run(dir.path(), target.path(), None, &SyncOptions::default()).unwrap();
 
     assert!(
         target.path().join(".apprc").is_symlink(),
@@ -801,6 +1242,7 @@ fn sync_inline_and_boat_toml_errors() {
         dir.path().join("towboat.toml"),
         r#"
 [system]
+state_dir = ".state"
 tags = ["linux"]
 
 [packages.conflicting]
@@ -825,7 +1267,8 @@ target_dir = "~"
     .unwrap();
     fs::write(pkg.join(".bashrc"), "content\n").unwrap();
 
-    let result = towboat::commands::sync::run(dir.path(), target.path(), None, false, false);
+    let result =
+        towboat::commands::sync::run(dir.path(), target.path(), None, &SyncOptions::default());
     assert!(result.is_err(), "Should error when both inline config and boat.toml exist");
     let err = result.unwrap_err().to_string();
     assert!(
@@ -844,6 +1287,7 @@ fn sync_mixed_inline_and_external() {
         dir.path().join("towboat.toml"),
         r#"
 [system]
+state_dir = ".state"
 tags = ["linux"]
 
 [packages]
@@ -874,7 +1318,7 @@ external = {}
     fs::create_dir_all(&inline_pkg).unwrap();
     fs::write(inline_pkg.join(".inlinerc"), "inline content\n").unwrap();
 
-    towboat::commands::sync::run(dir.path(), target.path(), None, false, false).unwrap();
+    towboat::commands::sync::run(dir.path(), target.path(), None, &SyncOptions::default()).unwrap();
 
     assert!(
         target.path().join(".extrc").is_symlink(),
@@ -891,3 +1335,1811 @@ external = {}
     let inline_content = fs::read_to_string(target.path().join(".inlinerc")).unwrap();
     assert_eq!(inline_content, "inline content\n");
 }
+
+#[test]
+fn sync_with_glob_targets() {
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+
+    let bin_dir = stow.path().join("bash/bin");
+    fs::create_dir_all(&bin_dir).unwrap();
+    fs::write(bin_dir.join("up.sh"), "echo up\n").unwrap();
+    fs::write(bin_dir.join("notes.txt"), "notes\n").unwrap();
+    fs::write(
+        stow.path().join("bash/boat.toml"),
+        r#"
+[targets]
+".bashrc" = { tags = "linux" }
+"bin/*.sh" = { tags = "linux" }
+"*.txt" = { tags = "macos" }
+"#,
+    )
+    .unwrap();
+
+    towboat::commands::sync::run(
+        stow.path(),
+        target.path(),
+        Some("bash"),
+        &SyncOptions::default(),
+    )
+    .unwrap();
+
+    assert!(target.path().join("bin/up.sh").is_symlink());
+    assert!(!target.path().join("bin/notes.txt").exists());
+    // `*` does not cross directories, so .profile has no rule and is skipped
+    assert!(!target.path().join(".profile").exists());
+}
+
+#[test]
+fn cli_explain_shows_matching_rule() {
+    use assert_cmd::Command;
+
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+    fs::write(
+        stow.path().join("bash/boat.toml"),
+        r#"
+[targets]
+".bashrc" = { tags = "macos" }
+".b*" = { tags = "linux" }
+"#,
+    )
+    .unwrap();
+
+    Command::cargo_bin("towboat")
+        .unwrap()
+        .args([
+            "--dir",
+            stow.path().to_str().unwrap(),
+            "--target",
+            target.path().to_str().unwrap(),
+            "explain",
+            "bash/.bashrc",
+        ])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Package: bash"))
+        .stdout(predicates::str::contains(
+            "exact entry \".bashrc\": macos  <- decides: not matched",
+        ))
+        .stdout(predicates::str::contains(
+            "glob entry \".b*\": linux  (shadowed)",
+        ))
+        .stdout(predicates::str::contains("Result: not deployed"));
+}
+
+#[test]
+fn sync_deploys_best_filename_variant() {
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+
+    let git_dir = stow.path().join("git");
+    fs::write(
+        git_dir.join("boat.toml"),
+        "[default]\ninclude_all = true\ndefault_tag = \"linux\"\n",
+    )
+    .unwrap();
+    fs::write(
+        git_dir.join(".gitconfig##work"),
+        "[user]\nemail = ${{ email }}\n",
+    )
+    .unwrap();
+    fs::write(
+        git_dir.join(".gitconfig.tag-macos"),
+        "[user]\nemail = mac\n",
+    )
+    .unwrap();
+
+    towboat::commands::sync::run(
+        stow.path(),
+        target.path(),
+        Some("git"),
+        &SyncOptions::default(),
+    )
+    .unwrap();
+
+    let content = fs::read_to_string(target.path().join(".gitconfig")).unwrap();
+    assert_eq!(content, "[user]\nemail = user@work.com\n");
+    assert!(!target.path().join(".gitconfig##work").exists());
+}
+
+#[test]
+fn sync_rejects_target_collisions_before_writing() {
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+
+    // A second package deploying .bashrc with the same (default) priority
+    fs::create_dir_all(stow.path().join("extra")).unwrap();
+    fs::write(stow.path().join("extra/.bashrc"), "# extra\n").unwrap();
+    fs::write(
+        stow.path().join("extra/boat.toml"),
+        "[default]\ninclude_all = true\ndefault_tag = \"linux\"\n",
+    )
+    .unwrap();
+    let manifest = fs::read_to_string(stow.path().join("towboat.toml")).unwrap();
+    fs::write(
+        stow.path().join("towboat.toml"),
+        manifest.replace("git = {}", "git = {}\nextra = {}"),
+    )
+    .unwrap();
+
+    let err = towboat::commands::sync::run(
+        stow.path(),
+        target.path(),
+        Some("git"),
+        &SyncOptions::default(),
+    )
+    .unwrap_err()
+    .to_string();
+    assert!(err.contains("Target collision: .bashrc"), "{err}");
+    assert!(
+        err.contains("bash/.bashrc (exact entry \".bashrc\", priority 0)"),
+        "{err}"
+    );
+    assert!(
+        err.contains("extra/.bashrc ([default] include_all, priority 0)"),
+        "{err}"
+    );
+    assert!(!target.path().join(".gitconfig").exists());
+
+    // A package that can't be discovered might own any target, so it stops filtered syncs too
+    fs::write(
+        stow.path().join("extra/boat.toml"),
+        "[default]\ninclude_al = true\n",
+    )
+    .unwrap();
+    let err = towboat::commands::sync::run(
+        stow.path(),
+        target.path(),
+        Some("git"),
+        &SyncOptions::default(),
+    )
+    .unwrap_err();
+    assert!(
+        format!("{err:#}").contains("Package 'extra' can't be discovered"),
+        "{err:#}"
+    );
+    assert!(!target.path().join(".gitconfig").exists());
+}
+
+#[test]
+fn sync_priority_overrides_other_package() {
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+
+    fs::create_dir_all(stow.path().join("extra")).unwrap();
+    fs::write(stow.path().join("extra/.bashrc"), "# extra\n").unwrap();
+    fs::write(
+        stow.path().join("extra/boat.toml"),
+        "[default]\ninclude_all = true\ndefault_tag = \"linux\"\n",
+    )
+    .unwrap();
+    let manifest = fs::read_to_string(stow.path().join("towboat.toml")).unwrap();
+    fs::write(
+        stow.path().join("towboat.toml"),
+        manifest.replace("git = {}", "git = {}\nextra = { priority = 10 }"),
+    )
+    .unwrap();
+
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
+
+    let content = fs::read_to_string(target.path().join(".bashrc")).unwrap();
+    assert_eq!(content, "# extra\n");
+    assert!(target.path().join(".profile").exists());
+}
+
+#[test]
+fn switching_variant_keeps_target_linked() {
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+
+    let git_dir = stow.path().join("git");
+    fs::write(
+        git_dir.join("boat.toml"),
+        "[default]\ninclude_all = true\ndefault_tag = \"linux\"\n",
+    )
+    .unwrap();
+    fs::remove_file(git_dir.join(".gitconfig")).unwrap();
+    fs::write(git_dir.join(".gitconfig##work"), "work\n").unwrap();
+    fs::write(git_dir.join(".gitconfig##default"), "fallback\n").unwrap();
+
+    towboat::commands::sync::run(
+        stow.path(),
+        target.path(),
+        Some("git"),
+        &SyncOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        fs::read_to_string(target.path().join(".gitconfig")).unwrap(),
+        "work\n"
+    );
+
+    // The old variant's stale entry must not remove the new variant's link
+    fs::remove_file(git_dir.join(".gitconfig##work")).unwrap();
+    towboat::commands::sync::run(
+        stow.path(),
+        target.path(),
+        Some("git"),
+        &SyncOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        fs::read_to_string(target.path().join(".gitconfig")).unwrap(),
+        "fallback\n"
+    );
+}
+
+/// Replace `bash = {}` in the manifest with an inline table carrying hooks.
+fn set_bash_hooks(stow: &TempDir, hooks: &str) {
+    let manifest = fs::read_to_string(stow.path().join("towboat.toml")).unwrap();
+    fs::write(
+        stow.path().join("towboat.toml"),
+        format!(
+            "{}\n[packages.bash.hooks]\n{hooks}",
+            manifest.replace("bash = {}\n", "")
+        ),
+    )
+    .unwrap();
+}
+
+#[test]
+fn sync_runs_hooks_with_changed_files() {
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+    let log = stow.path().join("hooks.log");
+    set_bash_hooks(
+        &stow,
+        &format!(
+            r#"
+pre_sync = "echo pre >> {log}"
+post_sync = "echo post >> {log}"
+post_change = "echo \"change $TOWBOAT_PACKAGE $(echo \"$TOWBOAT_CHANGED_FILES\" | wc -l)\" >> {log}"
+on_unlink = {{ run = "echo unlink $TOWBOAT_UNLINKED_FILES >> {log}", tags = "linux" }}
+"#,
+            log = log.display()
+        ),
+    );
+
+    towboat::commands::sync::run(
+        stow.path(),
+        target.path(),
+        Some("bash"),
+        &SyncOptions::default(),
+    )
+    .unwrap();
+    let lines: Vec<String> = fs::read_to_string(&log)
+        .unwrap()
+        .lines()
+        .map(|l| l.trim().to_string())
+        .collect();
+    assert_eq!(lines, vec!["pre", "post", "change bash 2"]);
+
+    // Nothing changed: post_change is skipped
+    fs::remove_file(&log).unwrap();
+    towboat::commands::sync::run(
+        stow.path(),
+        target.path(),
+        Some("bash"),
+        &SyncOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(fs::read_to_string(&log).unwrap(), "pre\npost\n");
+
+    // Removing a file from the config triggers on_unlink and post_change
+    fs::remove_file(&log).unwrap();
+    fs::write(
+        stow.path().join("bash/boat.toml"),
+        "[targets]\n\".bashrc\" = { tags = \"linux\" }\n",
+    )
+    .unwrap();
+    towboat::commands::sync::run(
+        stow.path(),
+        target.path(),
+        Some("bash"),
+        &SyncOptions::default(),
+    )
+    .unwrap();
+    let content = fs::read_to_string(&log).unwrap();
+    assert!(
+        content.contains(&format!(
+            "unlink {}",
+            target.path().join(".profile").display()
+        )),
+        "{content}"
+    );
+    assert!(content.contains("change bash"), "{content}");
+}
+
+#[test]
+fn failing_pre_sync_hook_skips_package() {
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+    set_bash_hooks(&stow, "pre_sync = \"exit 1\"\n");
+
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
+
+    assert!(!target.path().join(".bashrc").exists());
+    assert!(target.path().join(".gitconfig").exists());
+}
+
+#[test]
+fn cli_sync_no_hooks_and_summary() {
+    use assert_cmd::Command;
+
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+    let marker = stow.path().join("ran");
+    set_bash_hooks(
+        &stow,
+        &format!("post_sync = \"touch {}\"\n", marker.display()),
+    );
+
+    let run = |extra: &[&str]| {
+        let mut cmd = Command::cargo_bin("towboat").unwrap();
+        cmd.args([
+            "--dir",
+            stow.path().to_str().unwrap(),
+            "--target",
+            target.path().to_str().unwrap(),
+            "sync",
+            "bash",
+        ])
+        .args(extra)
+        .assert()
+        .success()
+    };
+
+    run(&["--no-hooks"]);
+    assert!(!marker.exists());
+
+    run(&[]).stdout(predicates::str::contains(format!(
+        "bash post_sync: touch {} — ok",
+        marker.display()
+    )));
+    assert!(marker.exists());
+}
+
+#[test]
+fn sync_runs_once_and_onchange_scripts() {
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+    let scripts_dir = stow.path().join("scripts");
+    fs::create_dir_all(&scripts_dir).unwrap();
+    fs::write(
+        scripts_dir.join("run_once_install.sh"),
+        "echo once >> \"$TOWBOAT_STOW_DIR/runs.log\"\n",
+    )
+    .unwrap();
+    fs::write(
+        stow.path().join("packages.sh"),
+        "#!/bin/sh\n# {linux-\necho \"change ${{ hostname }}\" >> \"$TOWBOAT_STOW_DIR/runs.log\"\n# -linux}\n",
+    )
+    .unwrap();
+    let manifest = fs::read_to_string(stow.path().join("towboat.toml")).unwrap();
+    fs::write(
+        stow.path().join("towboat.toml"),
+        format!("{manifest}\n[[scripts]]\npath = \"packages.sh\"\nrun = \"onchange\"\ntags = \"linux\"\n"),
+    )
+    .unwrap();
+    let log = stow.path().join("runs.log");
+
+    // Pending before the first sync; a filtered sync does not run scripts
+    towboat::commands::sync::run(
+        stow.path(),
+        target.path(),
+        Some("bash"),
+        &SyncOptions::default(),
+    )
+    .unwrap();
+    assert!(!log.exists());
+
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
+    assert_eq!(fs::read_to_string(&log).unwrap(), "change workbox\nonce\n");
+
+    // Nothing changed: nothing runs
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
+    assert_eq!(fs::read_to_string(&log).unwrap(), "change workbox\nonce\n");
+
+    // Changing a variable changes the resolved script, so onchange runs again
+    let manifest = fs::read_to_string(stow.path().join("towboat.toml")).unwrap();
+    fs::write(
+        stow.path().join("towboat.toml"),
+        manifest.replace("hostname = \"workbox\"", "hostname = \"newbox\""),
+    )
+    .unwrap();
+    fs::write(
+        scripts_dir.join("run_once_install.sh"),
+        "echo once-edited >> \"$TOWBOAT_STOW_DIR/runs.log\"\n",
+    )
+    .unwrap();
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(&log).unwrap(),
+        "change workbox\nonce\nchange newbox\n"
+    );
+
+    let lock = towboat::LockFile::load(&state_dir(stow.path()).join("towboat.lock")).unwrap();
+    assert_eq!(lock.scripts.len(), 2);
+}
+
+#[test]
+fn cli_status_lists_pending_scripts() {
+    use assert_cmd::Command;
+
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+    fs::create_dir_all(stow.path().join("scripts")).unwrap();
+    fs::write(stow.path().join("scripts/run_once_setup.sh"), "true\n").unwrap();
+
+    let status = |format: &str| {
+        Command::cargo_bin("towboat")
+            .unwrap()
+            .args([
+                "--dir",
+                stow.path().to_str().unwrap(),
+                "--target",
+                target.path().to_str().unwrap(),
+                "status",
+                "--format",
+                format,
+            ])
+            .output()
+            .unwrap()
+    };
+
+    let output = status("text");
+    assert!(
+        String::from_utf8_lossy(&output.stdout)
+            .contains("Pending scripts:\n  scripts/run_once_setup.sh (once)")
+    );
+
+    let report: serde_json::Value = serde_json::from_slice(&status("json").stdout).unwrap();
+    assert_eq!(
+        report["pending_scripts"][0]["path"],
+        "scripts/run_once_setup.sh"
+    );
+    assert_eq!(report["pending_scripts"][0]["mode"], "once");
+
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&status("json").stdout).unwrap();
+    assert!(report["pending_scripts"].as_array().unwrap().is_empty());
+
+    // A script that won't resolve is reported, and fails a sync before it writes anything
+    fs::write(
+        stow.path().join("scripts/run_onchange_bad.sh"),
+        "echo ${{ nope }}\n",
+    )
+    .unwrap();
+    let output = status("text");
+    assert!(output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stdout)
+            .contains("scripts/run_onchange_bad.sh (onchange): won't resolve:")
+    );
+    let report: serde_json::Value = serde_json::from_slice(&status("json").stdout).unwrap();
+    assert!(report["pending_scripts"][0]["error"].is_string());
+
+    let fresh = TempDir::new().unwrap();
+    towboat::commands::sync::run(stow.path(), fresh.path(), None, &SyncOptions::default())
+        .unwrap_err();
+    assert!(fs::read_dir(fresh.path()).unwrap().next().is_none());
+}
+
+#[test]
+fn doctor_reports_config_and_deploy_problems() {
+    use towboat::commands::doctor::{self, Severity};
+
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
+    assert!(
+        doctor::diagnose(stow.path(), target.path())
+            .unwrap()
+            .iter()
+            .all(|f| f.severity != Severity::Error)
+    );
+
+    // Links that already lead to our resolved files aren't foreign, even untracked
+    let lock_path = state_dir(stow.path()).join("towboat.lock");
+    let lock = fs::read(&lock_path).unwrap();
+    fs::remove_file(&lock_path).unwrap();
+    let findings = doctor::diagnose(stow.path(), target.path()).unwrap();
+    assert!(
+        findings.iter().all(|f| f.check != "foreign-target"),
+        "{findings:?}"
+    );
+    fs::write(&lock_path, lock).unwrap();
+
+    // Misspelled key, undefined variable, broken link and an orphaned resolved file.
+    fs::write(
+        stow.path().join("git/boat.toml"),
+        "[targets]\n\".gitconfig\" = { tag = \"linux\" }\n",
+    )
+    .unwrap();
+    fs::write(stow.path().join("bash/.profile"), "name=${{ user_name }}\n").unwrap();
+    // Sections and packages for other tags are checked too
+    fs::write(
+        stow.path().join("bash/.bash_aliases"),
+        "# {macos-\nalias brew=${{ brew_prefix }}/bin/brew\n# -macos}\n",
+    )
+    .unwrap();
+    fs::write(stow.path().join("vim/.gvimrc"), "set guifont=${{ font }}\n").unwrap();
+    let manifest = fs::read_to_string(stow.path().join("towboat.toml")).unwrap();
+    fs::write(
+        stow.path().join("towboat.toml"),
+        manifest.replace(
+            "vim = { tags = [\"laptop\"] }",
+            "vim = { tags = [\"desktop\"] }",
+        ),
+    )
+    .unwrap();
+    fs::remove_file(state_dir(stow.path()).join("resolved/bash/.bashrc")).unwrap();
+    fs::write(state_dir(stow.path()).join("resolved/bash/old.txt"), "x").unwrap();
+
+    let findings = doctor::diagnose(stow.path(), target.path()).unwrap();
+    let find = |check: &str| {
+        findings
+            .iter()
+            .find(|f| f.check == check)
+            .unwrap_or_else(|| panic!("no {check} finding in {findings:?}"))
+    };
+
+    let invalid = find("invalid-config");
+    assert_eq!(invalid.severity, Severity::Error);
+    assert!(
+        invalid
+            .message
+            .contains("git/boat.toml:2:18: unknown field `tag`")
+    );
+    assert!(invalid.message.contains("did you mean `tags`?"));
+    assert_eq!(find("undefined-variable").severity, Severity::Error);
+    for var in ["user_name", "brew_prefix", "font"] {
+        assert!(
+            findings
+                .iter()
+                .any(|f| f.check == "undefined-variable" && f.message.contains(var)),
+            "{var} not reported in {findings:?}"
+        );
+    }
+    assert!(find("broken-symlink").message.contains(".bashrc"));
+    assert_eq!(find("orphaned-resolved").severity, Severity::Info);
+    assert!(findings.windows(2).all(|w| w[0].severity <= w[1].severity));
+
+    // Nothing was written by the check.
+    assert!(
+        !state_dir(stow.path())
+            .join("resolved/bash/.bashrc")
+            .exists()
+    );
+}
+
+#[test]
+fn doctor_keeps_checking_past_a_broken_manifest() {
+    use towboat::commands::doctor;
+
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
+    fs::remove_file(state_dir(stow.path()).join("resolved/bash/.bashrc")).unwrap();
+    fs::write(
+        stow.path().join("git/boat.toml"),
+        "[targets]\n\".gitconfig\" = { tag = \"linux\" }\n",
+    )
+    .unwrap();
+    fs::write(
+        stow.path().join("towboat.toml"),
+        "[system]\nstate_dir = \".state\"\ntag = []\n",
+    )
+    .unwrap();
+
+    let findings = doctor::diagnose(stow.path(), target.path()).unwrap();
+    let messages: Vec<&str> = findings
+        .iter()
+        .filter(|f| f.check == "invalid-config")
+        .map(|f| f.message.as_str())
+        .collect();
+    assert!(
+        messages.iter().any(|m| m.contains("towboat.toml:3:1")),
+        "{messages:?}"
+    );
+    assert!(
+        messages.iter().any(|m| m.contains("git/boat.toml:2:18")),
+        "{messages:?}"
+    );
+    assert!(
+        findings
+            .iter()
+            .any(|f| f.check == "broken-symlink" && f.message.contains(".bashrc")),
+        "{findings:?}"
+    );
+}
+
+#[test]
+fn cli_doctor_json_and_exit_status() {
+    use assert_cmd::Command;
+
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+    let doctor = || {
+        Command::cargo_bin("towboat")
+            .unwrap()
+            .args([
+                "--dir",
+                stow.path().to_str().unwrap(),
+                "--target",
+                target.path().to_str().unwrap(),
+                "doctor",
+                "--format",
+                "json",
+            ])
+            .output()
+            .unwrap()
+    };
+
+    assert!(doctor().status.success());
+
+    fs::write(
+        stow.path().join("bash/boat.toml"),
+        "[targets]\n\".bashrc\" = { tags = \"linux &\" }\n",
+    )
+    .unwrap();
+    let output = doctor();
+    assert!(!output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let finding = &report["findings"][0];
+    assert_eq!(finding["severity"], "error");
+    assert_eq!(finding["check"], "tag-expr");
+    assert!(finding["hint"].as_str().is_some());
+}
+
+#[test]
+fn sync_rejects_unknown_manifest_keys() {
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+    fs::write(
+        stow.path().join("towboat.toml"),
+        "[system]\ntags = [\"linux\"]\n\n[packages]\nbash = { tag = [\"laptop\"] }\n",
+    )
+    .unwrap();
+
+    let err =
+        towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+            .unwrap_err();
+    let message = format!("{err:#}");
+    assert!(
+        message.contains("towboat.toml:5:10: unknown field `tag`"),
+        "{message}"
+    );
+    assert!(message.contains("did you mean `tags`?"), "{message}");
+    assert!(!target.path().join(".bashrc").exists());
+}
+
+#[test]
+fn lint_flags_unknown_dead_and_unreachable_tags() {
+    use towboat::commands::lint;
+
+    let stow = setup_stow_dir();
+    assert!(
+        lint::lint(stow.path())
+            .unwrap()
+            .iter()
+            .any(|f| f.check == "no-vocabulary")
+    );
+
+    let manifest = fs::read_to_string(stow.path().join("towboat.toml")).unwrap();
+    fs::write(
+        stow.path().join("towboat.toml"),
+        manifest.replace(
+            "[variables]",
+            "[profiles.mac]\ntags = [\"macos\", \"laptop\"]\n\n[variables]",
+        ),
+    )
+    .unwrap();
+    fs::write(
+        stow.path().join("bash/.bashrc"),
+        "# {lnux-\na\n# -lnux}\n# {linux & !linux-\nb\n# -linux & !linux}\n# {macos & work-\nc\n# -macos & work}\n",
+    )
+    .unwrap();
+    fs::write(stow.path().join("git/.gitconfig##linux"), "x").unwrap();
+
+    let findings = lint::lint(stow.path()).unwrap();
+    let messages = |check: &str| -> Vec<String> {
+        findings
+            .iter()
+            .filter(|f| f.check == check)
+            .map(|f| f.message.clone())
+            .collect()
+    };
+
+    let unknown = findings.iter().find(|f| f.check == "unknown-tag").unwrap();
+    assert_eq!(
+        unknown.message,
+        "bash/.bashrc:1: section: unknown tag `lnux` in `lnux`"
+    );
+    assert!(unknown.hint.starts_with("Did you mean `linux`?"));
+    assert_eq!(
+        messages("always-false"),
+        vec!["bash/.bashrc:4: section: `linux & !linux` can never match"]
+    );
+    assert_eq!(
+        messages("unreachable"),
+        vec![
+            "bash/.bashrc:1: section: `lnux` matches none of the profiles (this machine, mac)",
+            "bash/.bashrc:7: section: `macos & work` matches none of the profiles (this machine, mac)",
+        ]
+    );
+    assert!(
+        messages("unreachable")
+            .iter()
+            .all(|m| !m.contains("gitconfig"))
+    );
+}
+
+#[test]
+fn cli_lint_known_tags_exit_status() {
+    use assert_cmd::Command;
+
+    let stow = setup_stow_dir();
+    let manifest = fs::read_to_string(stow.path().join("towboat.toml")).unwrap();
+    fs::write(
+        stow.path().join("towboat.toml"),
+        manifest.replace(
+            "tags = [\"linux\", \"laptop\", \"work\"]",
+            "tags = [\"linux\", \"laptop\", \"work\"]\nknown_tags = [\"linux\", \"macos\", \"laptop\", \"work\"]",
+        ),
+    )
+    .unwrap();
+    let lint = || {
+        Command::cargo_bin("towboat")
+            .unwrap()
+            .args(["--dir", stow.path().to_str().unwrap(), "lint"])
+            .output()
+            .unwrap()
+    };
+
+    let output = lint();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("No problems found."));
+
+    fs::write(
+        stow.path().join("vim/boat.toml"),
+        "[targets]\n\".vimrc\" = { tags = \"linux | server\" }\n",
+    )
+    .unwrap();
+    let output = lint();
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains(
+            "error[unknown-tag]: vim/boat.toml: [targets] \".vimrc\": unknown tag `server`"
+        )
+    );
+}
+
+#[test]
+fn cli_explain_target_path_shows_config_terms_and_sections() {
+    use assert_cmd::Command;
+
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
+
+    let explain = |path: &str| {
+        let output = Command::cargo_bin("towboat")
+            .unwrap()
+            .args([
+                "--dir",
+                stow.path().to_str().unwrap(),
+                "--target",
+                target.path().to_str().unwrap(),
+                "explain",
+                path,
+            ])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    // A deployed symlink maps back to its source.
+    let out = explain(target.path().join(".bashrc").to_str().unwrap());
+    assert!(out.contains("File: bash/.bashrc\n"), "{out}");
+    assert!(out.contains("Package tags: none required"), "{out}");
+    assert!(out.contains("Config: bash/boat.toml\n"), "{out}");
+    assert!(
+        out.contains(
+            "exact entry \".bashrc\": linux  <- decides: matched\n      true   linux (active)"
+        ),
+        "{out}"
+    );
+    assert!(
+        out.contains("Sections:\n  line 5: linux  kept\n  line 9: macos  dropped"),
+        "{out}"
+    );
+    assert!(out.contains("Result: deployed to"), "{out}");
+
+    // Package-level tags that aren't active.
+    let manifest = fs::read_to_string(stow.path().join("towboat.toml")).unwrap();
+    fs::write(
+        stow.path().join("towboat.toml"),
+        manifest.replace(
+            "vim = { tags = [\"laptop\"] }",
+            "vim = { tags = [\"laptop\", \"desktop\"] }",
+        ),
+    )
+    .unwrap();
+    let out = explain("vim/.vimrc");
+    assert!(
+        out.contains("Package tags: laptop, desktop (failed: desktop not active)"),
+        "{out}"
+    );
+    assert!(
+        out.contains("Result: not deployed (package tags not met)"),
+        "{out}"
+    );
+
+    // A target nothing deploys yet explains every variant that could.
+    fs::write(stow.path().join("git/.npmrc##macos"), "a").unwrap();
+    fs::write(stow.path().join("git/.npmrc##work,macos"), "b").unwrap();
+    fs::write(
+        stow.path().join("git/boat.toml"),
+        "[targets]\n\".gitconfig\" = { tags = [\"linux\", \"macos\"] }\n\".npmrc\" = { tags = \"macos | linux\" }\n",
+    )
+    .unwrap();
+    let out = explain(".npmrc");
+    assert!(out.contains("File: git/.npmrc##macos\n"), "{out}");
+    assert!(out.contains("File: git/.npmrc##work,macos\n"), "{out}");
+    assert!(out.contains("Filename tags: macos (not matched)"), "{out}");
+    assert!(
+        out.contains("      true   macos | linux\n        false  macos (inactive)\n        true   linux (active)"),
+        "{out}"
+    );
+}
+
+#[test]
+fn cli_explain_inline_config_source() {
+    use assert_cmd::Command;
+
+    let stow = setup_stow_dir();
+    fs::remove_file(stow.path().join("vim/boat.toml")).unwrap();
+    let manifest = fs::read_to_string(stow.path().join("towboat.toml")).unwrap();
+    fs::write(
+        stow.path().join("towboat.toml"),
+        manifest.replace(
+            "vim = { tags = [\"laptop\"] }",
+            "vim = { tags = [\"laptop\"], targets = { \".vimrc\" = { tags = \"linux\" } } }",
+        ),
+    )
+    .unwrap();
+
+    Command::cargo_bin("towboat")
+        .unwrap()
+        .args([
+            "--dir",
+            stow.path().to_str().unwrap(),
+            "explain",
+            "vim/.vimrc",
+        ])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Package tags: laptop (passed)"))
+        .stdout(predicates::str::contains(
+            "Config: inline [packages.vim] in towboat.toml",
+        ));
+}
+
+#[test]
+fn cli_render_and_simulated_hosts() {
+    use assert_cmd::Command;
+
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+    let manifest = fs::read_to_string(stow.path().join("towboat.toml")).unwrap();
+    fs::write(
+        stow.path().join("towboat.toml"),
+        format!(
+            "{manifest}\n[profiles.mac]\ntags = [\"macos\", \"work\"]\nvariables = {{ hostname = \"macbook\" }}\n"
+        ),
+    )
+    .unwrap();
+    let towboat = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("towboat").unwrap();
+        cmd.args([
+            "--dir",
+            stow.path().to_str().unwrap(),
+            "--target",
+            target.path().to_str().unwrap(),
+        ]);
+        cmd.args(args).output().unwrap()
+    };
+    let stdout = |output: std::process::Output| {
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let out = stdout(towboat(&[
+        "render",
+        "bash/.bashrc",
+        "--tags",
+        "+macos,-linux",
+    ]));
+    assert!(
+        out.contains("alias ls='ls -G'") && !out.contains("--color"),
+        "{out}"
+    );
+
+    let out = stdout(towboat(&[
+        "render",
+        "git/.gitconfig",
+        "--as-host",
+        "mac",
+        "--set",
+        "email=a@b",
+    ]));
+    assert_eq!(out, "[user]\n    name = macbook\n    email = a@b\n");
+
+    let out = stdout(towboat(&["explain", "git/.gitconfig", "--tags", "server"]));
+    assert!(out.contains("false  linux (inactive)"), "{out}");
+    assert!(out.contains("Result: not deployed"), "{out}");
+
+    // Overrides never touch the state dir and require --dry-run for sync
+    let output = towboat(&["sync", "--tags", "+macos"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("add --dry-run"));
+    let out = stdout(towboat(&["sync", "--dry-run", "--as-host", "mac"]));
+    assert!(out.contains("Would symlink"), "{out}");
+    assert!(!state_dir(stow.path()).exists());
+
+    let output = towboat(&["render", "bash/.bashrc", "--as-host", "nope"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("profile not found: nope"));
+}
+
+#[test]
+fn cli_diff_with_simulated_tags() {
+    use assert_cmd::Command;
+
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
+
+    // vim requires `laptop`; without it the diff reports its removal
+    Command::cargo_bin("towboat")
+        .unwrap()
+        .args([
+            "--dir",
+            stow.path().to_str().unwrap(),
+            "--target",
+            target.path().to_str().unwrap(),
+            "diff",
+            "--format",
+            "porcelain",
+            "--tags=-laptop",
+        ])
+        .assert()
+        .success()
+        .stdout("D\tvim\tvim/.vimrc\t.vimrc\n");
+}
+
+#[test]
+fn cli_export_with_simulated_tags() {
+    use assert_cmd::Command;
+
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+    let out = TempDir::new().unwrap();
+    let tree = out.path().join("tree");
+
+    Command::cargo_bin("towboat")
+        .unwrap()
+        .args([
+            "--dir",
+            stow.path().to_str().unwrap(),
+            "--target",
+            target.path().to_str().unwrap(),
+            "export",
+            "--tags",
+            "macos",
+            "--out",
+            tree.to_str().unwrap(),
+        ])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("from 2 package(s)"));
+
+    // Real files, resolved for macOS; vim needs `laptop`, which was dropped
+    assert!(!tree.join(".bashrc").exists());
+    let profile = tree.join(".profile").symlink_metadata().unwrap();
+    assert!(profile.is_file());
+    assert!(tree.join(".gitconfig").is_file());
+    assert!(!tree.join(".vimrc").exists());
+
+    // Neither the target nor the towboat state was touched
+    assert_eq!(fs::read_dir(target.path()).unwrap().count(), 0);
+    assert!(!state_dir(stow.path()).exists());
+
+    // Exactly one destination is required
+    Command::cargo_bin("towboat")
+        .unwrap()
+        .args(["--dir", stow.path().to_str().unwrap(), "export"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn variable_and_tag_changes_mark_files_source_changed() {
+    use assert_cmd::Command;
+
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
+
+    let status = || {
+        let output = Command::cargo_bin("towboat")
+            .unwrap()
+            .args([
+                "--dir",
+                stow.path().to_str().unwrap(),
+                "--target",
+                target.path().to_str().unwrap(),
+                "status",
+            ])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let manifest_path = stow.path().join("towboat.toml");
+    let manifest = fs::read_to_string(&manifest_path).unwrap();
+    fs::write(
+        &manifest_path,
+        manifest
+            .replace("user@work.com", "me@home.org")
+            .replace("\"work\"]", "\"work\", \"macos\"]"),
+    )
+    .unwrap();
+
+    let out = status();
+    assert!(
+        out.contains(".gitconfig: source changed (variable `email` changed)"),
+        "{out}"
+    );
+    assert!(
+        out.contains(".bashrc: source changed (tag `macos` now active)"),
+        "{out}"
+    );
+    assert!(out.contains("   .profile: up to date"), "{out}");
+
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
+    let gitconfig = fs::read_to_string(target.path().join(".gitconfig")).unwrap();
+    assert!(gitconfig.contains("me@home.org"), "{gitconfig}");
+    assert!(!status().contains("source changed"));
+}
+
+#[test]
+fn cli_status_lists_new_stale_and_foreign_files() {
+    use assert_cmd::Command;
+
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+    towboat::commands::sync::run(
+        stow.path(),
+        target.path(),
+        Some("bash"),
+        &SyncOptions::default(),
+    )
+    .unwrap();
+
+    // .profile's rule is dropped, .gitconfig is occupied by a regular file
+    fs::write(
+        stow.path().join("bash/boat.toml"),
+        "[targets]\n\".bashrc\" = { tags = \"linux\" }\n",
+    )
+    .unwrap();
+    fs::write(target.path().join(".gitconfig"), "mine\n").unwrap();
+
+    let status = |format: &str| {
+        let output = Command::cargo_bin("towboat")
+            .unwrap()
+            .args([
+                "--dir",
+                stow.path().to_str().unwrap(),
+                "--target",
+                target.path().to_str().unwrap(),
+                "status",
+                "--format",
+                format,
+            ])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(
+        status("porcelain"),
+        "up_to_date\tbash\tbash/.bashrc\t.bashrc\n\
+         stale\tbash\tbash/.profile\t.profile\n\
+         foreign\tgit\tgit/.gitconfig\t.gitconfig\n\
+         new\tvim\tvim/.vimrc\t.vimrc\n"
+    );
+
+    let out = status("text");
+    assert!(out.contains("F  .gitconfig: foreign"), "{out}");
+    assert!(out.contains("N  .vimrc: new"), "{out}");
+    assert!(
+        out.contains("1 up to date, 1 stale, 1 new, 1 foreign\n"),
+        "{out}"
+    );
+
+    let report: serde_json::Value = serde_json::from_str(&status("json")).unwrap();
+    assert_eq!(report["summary"]["foreign"], 1);
+    assert_eq!(report["summary"]["new"], 1);
+    assert!(report["files"][3]["locked_source_hash"].is_null());
+}
+
+#[test]
+fn cli_status_check_exit_codes() {
+    use assert_cmd::Command;
+
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+    let check = |quiet: bool| {
+        let mut cmd = Command::cargo_bin("towboat").unwrap();
+        cmd.args([
+            "--dir",
+            stow.path().to_str().unwrap(),
+            "--target",
+            target.path().to_str().unwrap(),
+            "status",
+            "--check",
+        ]);
+        if quiet {
+            cmd.arg("--quiet");
+        }
+        cmd.output().unwrap()
+    };
+
+    // Nothing synced yet: everything is pending
+    assert_eq!(check(false).status.code(), Some(10));
+
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
+    let output = check(true);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+
+    fs::write(
+        state_dir(stow.path()).join("resolved/vim/.vimrc"),
+        "edited\n",
+    )
+    .unwrap();
+    assert_eq!(check(true).status.code(), Some(11));
+
+    fs::remove_file(target.path().join(".profile")).unwrap();
+    fs::write(target.path().join(".profile"), "mine\n").unwrap();
+    assert_eq!(check(true).status.code(), Some(12));
+
+    fs::remove_file(target.path().join(".bashrc")).unwrap();
+    let output = check(false);
+    assert_eq!(output.status.code(), Some(13));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("broken symlink"));
+
+    // --quiet only makes sense with --check; usage errors keep clap's code
+    Command::cargo_bin("towboat")
+        .unwrap()
+        .args(["--dir", stow.path().to_str().unwrap(), "status", "--quiet"])
+        .assert()
+        .code(2);
+    Command::cargo_bin("towboat")
+        .unwrap()
+        .arg("status")
+        .arg("--help")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("10  pending"));
+}
+
+#[test]
+fn sync_output_and_lock_do_not_depend_on_jobs() {
+    use assert_cmd::Command;
+
+    let sync = |jobs: &str| {
+        let stow = setup_stow_dir();
+        let target = TempDir::new().unwrap();
+        let themes = stow.path().join("themes");
+        for i in 0..200 {
+            let dir = themes.join(format!(".themes/t{}", i % 7));
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(
+                dir.join(format!("theme{i}.conf")),
+                format!("# {{linux-\nhost = ${{{{ hostname }}}}\n# -linux}}\nid = {i}\n"),
+            )
+            .unwrap();
+        }
+        fs::write(
+            themes.join("boat.toml"),
+            "[default]\ninclude_all = true\ndefault_tag = \"linux\"\n",
+        )
+        .unwrap();
+        let manifest = fs::read_to_string(stow.path().join("towboat.toml")).unwrap();
+        fs::write(
+            stow.path().join("towboat.toml"),
+            format!("{manifest}themes = {{}}\n"),
+        )
+        .unwrap();
+
+        let output = Command::cargo_bin("towboat")
+            .unwrap()
+            .args([
+                "--dir",
+                stow.path().to_str().unwrap(),
+                "--target",
+                target.path().to_str().unwrap(),
+                "sync",
+                "--jobs",
+                jobs,
+            ])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        assert_eq!(
+            fs::read_to_string(target.path().join(".themes/t3/theme10.conf")).unwrap(),
+            "host = workbox\nid = 10\n"
+        );
+
+        let lock = fs::read_to_string(state_dir(stow.path()).join("towboat.lock")).unwrap();
+        let root = stow.path().to_str().unwrap().to_string();
+        let entries: Vec<String> = lock
+            .lines()
+            // Timestamps and file stats differ between runs
+            .filter(|l| {
+                !["last_sync", "mtime_ns", "inode"]
+                    .iter()
+                    .any(|k| l.starts_with(k))
+            })
+            .map(|l| l.replace(&root, "<stow>"))
+            .collect();
+        (String::from_utf8(output.stdout).unwrap(), entries)
+    };
+
+    let serial = sync("1");
+    assert_eq!(sync("8"), serial);
+    assert!(serial.0.contains("204 file(s) resolved"), "{}", serial.0);
+}
+
+#[test]
+fn unchanged_stats_skip_hashing_unless_paranoid() {
+    use assert_cmd::Command;
+    use std::time::{Duration, SystemTime};
+
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+    let source = stow.path().join("vim/.vimrc");
+    let resolved = state_dir(stow.path()).join("resolved/vim/.vimrc");
+    let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+    let set_mtime = |path: &std::path::Path| {
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+    };
+
+    // Backdate both files so the second sync records stats older than the lock
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
+    set_mtime(&source);
+    set_mtime(&resolved);
+    towboat::commands::sync::run(stow.path(), target.path(), None, &SyncOptions::default())
+        .unwrap();
+
+    // Same size, same inode, same mtime: only hashing can tell
+    fs::write(&resolved, "set numbex\nset ruler\n").unwrap();
+    set_mtime(&resolved);
+
+    let status = |paranoid: bool| {
+        let mut cmd = Command::cargo_bin("towboat").unwrap();
+        cmd.args([
+            "--dir",
+            stow.path().to_str().unwrap(),
+            "--target",
+            target.path().to_str().unwrap(),
+            "status",
+            "vim",
+            "--format",
+            "porcelain",
+        ]);
+        if paranoid {
+            cmd.arg("--paranoid");
+        }
+        String::from_utf8(cmd.output().unwrap().stdout).unwrap()
+    };
+    assert_eq!(status(false), "up_to_date\tvim\tvim/.vimrc\t.vimrc\n");
+    assert_eq!(status(true), "drifted\tvim\tvim/.vimrc\t.vimrc\n");
+}
+
+#[test]
+fn watch_resyncs_changed_package_and_reports_drift() {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_towboat"))
+        .args([
+            "--dir",
+            stow.path().to_str().unwrap(),
+            "--target",
+            target.path().to_str().unwrap(),
+            "watch",
+            "--debounce",
+            "50",
+        ])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let (tx, rx) = mpsc::channel();
+    let stdout = BufReader::new(child.stdout.take().unwrap());
+    std::thread::spawn(move || {
+        for line in stdout.lines().map_while(Result::ok) {
+            let _ = tx.send(line);
+        }
+    });
+    let mut seen = Vec::new();
+    let mut expect = |needle: &str| {
+        while !seen.iter().any(|line: &String| line.contains(needle)) {
+            match rx.recv_timeout(Duration::from_secs(10)) {
+                Ok(line) => seen.push(line),
+                Err(_) => panic!("no line containing {needle:?} in:\n{}", seen.join("\n")),
+            }
+        }
+        seen.clear();
+    };
+
+    expect("Watching");
+    let bashrc = stow.path().join("bash/.bashrc");
+    let mut source = fs::read_to_string(&bashrc).unwrap();
+    source.push_str("export EDITOR=vim\n");
+    fs::write(&bashrc, source).unwrap();
+    expect("bash: synced");
+    assert!(
+        fs::read_to_string(target.path().join(".bashrc"))
+            .unwrap()
+            .contains("EDITOR=vim")
+    );
+
+    // An edit through the deployed link is reported and kept
+    fs::write(target.path().join(".vimrc"), "set hand-edited\n").unwrap();
+    expect("vim: .vimrc drifted");
+    let _ = child.kill();
+    let _ = child.wait();
+    assert_eq!(
+        fs::read_to_string(state_dir(stow.path()).join("resolved/vim/.vimrc")).unwrap(),
+        "set hand-edited\n"
+    );
+    // Only the initial sync recorded a generation
+    let generations = state_dir(stow.path()).join("generations");
+    assert!(generations.join("1").is_dir());
+    assert!(!generations.join("2").exists());
+}
+
+#[test]
+fn resync_with_relative_target_keeps_target_dir() {
+    use assert_cmd::Command;
+
+    // A single deployed file, so the link is alone in the target dir
+    let stow = TempDir::new().unwrap();
+    fs::write(
+        stow.path().join("towboat.toml"),
+        "[system]\nstate_dir = \".state\"\ntags = [\"linux\"]\n\n[packages.bash]\n",
+    )
+    .unwrap();
+    fs::create_dir(stow.path().join("bash")).unwrap();
+    fs::write(
+        stow.path().join("bash/boat.toml"),
+        "[targets]\n\".bashrc\" = { tags = \"linux\" }\n",
+    )
+    .unwrap();
+    fs::write(stow.path().join("bash/.bashrc"), "one\n").unwrap();
+    let work = TempDir::new().unwrap();
+    fs::create_dir(work.path().join("home")).unwrap();
+    let sync = || {
+        Command::cargo_bin("towboat")
+            .unwrap()
+            .current_dir(work.path())
+            .args([
+                "--dir",
+                stow.path().to_str().unwrap(),
+                "--target",
+                "home",
+                "sync",
+            ])
+            .assert()
+            .success();
+    };
+
+    sync();
+    fs::write(stow.path().join("bash/.bashrc"), "two\n").unwrap();
+    sync();
+    assert_eq!(
+        fs::read_to_string(work.path().join("home/.bashrc")).unwrap(),
+        "two\n"
+    );
+}
+
+#[test]
+fn rollback_restores_previous_generation() {
+    use assert_cmd::Command;
+
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+    let towboat = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("towboat").unwrap();
+        cmd.args([
+            "--dir",
+            stow.path().to_str().unwrap(),
+            "--target",
+            target.path().to_str().unwrap(),
+        ])
+        .args(args);
+        cmd
+    };
+    let bashrc = target.path().join(".bashrc");
+
+    towboat(&["sync"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Generation 1 recorded"));
+    let good = fs::read_to_string(&bashrc).unwrap();
+    fs::write(stow.path().join("bash/.bashrc"), "broken(\n").unwrap();
+    towboat(&["sync"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Generation 2 recorded"));
+    assert_eq!(fs::read_to_string(&bashrc).unwrap(), "broken(\n");
+    towboat(&["generations"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("*    2"));
+
+    towboat(&["rollback"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "Rolled back to generation 1 (1 file(s) restored, 0 removed)",
+        ));
+    assert_eq!(fs::read_to_string(&bashrc).unwrap(), good);
+    towboat(&["generations"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("*    1"));
+    towboat(&["status", "bash", "--format", "porcelain"])
+        .assert()
+        .stdout(predicates::str::contains(
+            "source_changed\tbash\tbash/.bashrc",
+        ));
+    towboat(&["rollback"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("No earlier generation"));
+
+    // A file the generation holds no contents for is left out of the restored lock
+    let generation = state_dir(stow.path()).join("generations/2/generation.toml");
+    let recorded = fs::read_to_string(&generation).unwrap();
+    let recorded: String = recorded
+        .lines()
+        .filter(|line| !line.starts_with("\"git/.gitconfig\""))
+        .map(|line| format!("{line}\n"))
+        .collect();
+    fs::write(&generation, recorded).unwrap();
+    towboat(&["rollback", "2"])
+        .assert()
+        .success()
+        .stderr(predicates::str::contains(
+            "Skipped .gitconfig: no contents recorded in generation 2",
+        ));
+    towboat(&["status", "git", "--format", "porcelain"])
+        .assert()
+        .stdout(predicates::str::contains("new\tgit\tgit/.gitconfig"));
+}
+
+#[test]
+fn log_shows_when_a_packages_files_changed() {
+    use assert_cmd::Command;
+
+    let stow = setup_stow_dir();
+    let target = TempDir::new().unwrap();
+    let towboat = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("towboat").unwrap();
+        cmd.args([
+            "--dir",
+            stow.path().to_str().unwrap(),
+            "--target",
+            target.path().to_str().unwrap(),
+        ])
+        .args(args);
+        cmd
+    };
+
+    towboat(&["sync"]).assert().success();
+    fs::write(stow.path().join("git/.gitconfig"), "[user]\n\tname = me\n").unwrap();
+    towboat(&["sync"]).assert().success();
+
+    let output = towboat(&["log", "--package", "git"]).output().unwrap();
+    let log = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = log.lines().filter(|l| l.starts_with("    ")).collect();
+    assert_eq!(
+        lines,
+        [
+            "    updated  .gitconfig (git)",
+            "    added    .gitconfig (git)"
+        ],
+        "{log}"
+    );
+    assert!(
+        log.contains("sync  ") && log.contains("generation 2"),
+        "{log}"
+    );
+
+    towboat(&["log", "--since", "2999-01-01"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("No matching history."));
+    towboat(&["log", "--since", "soon"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("invalid --since"));
+}
+
+#[test]
+fn relocate_repoints_links_after_the_state_dir_moves() {
+    use assert_cmd::Command;
+
+    let stow = setup_stow_dir();
+    let manifest = fs::read_to_string(stow.path().join("towboat.toml")).unwrap();
+    fs::write(
+        stow.path().join("towboat.toml"),
+        manifest.replace("state_dir = \".state\"", "state_dir = \"state\""),
+    )
+    .unwrap();
+    let home = TempDir::new().unwrap();
+    let target = home.path().join("target");
+    let towboat = |dir: &std::path::Path, args: &[&str]| {
+        let mut cmd = Command::cargo_bin("towboat").unwrap();
+        cmd.args([
+            "--dir",
+            dir.to_str().unwrap(),
+            "--target",
+            target.to_str().unwrap(),
+        ])
+        .args(args);
+        cmd
+    };
+    let bashrc = target.join(".bashrc");
+
+    towboat(stow.path(), &["sync"]).assert().success();
+    let content = fs::read_to_string(&bashrc).unwrap();
+
+    // The state dir lives inside the stow dir, so moving one moves the other
+    let moved = home.path().join("dotfiles");
+    fs::rename(stow.path(), &moved).unwrap();
+    assert!(!bashrc.exists());
+    towboat(&moved, &["status", "--format", "porcelain"])
+        .assert()
+        .stdout(predicates::str::contains("moved\tbash\tbash/.bashrc"));
+    towboat(&moved, &["status", "--check"]).assert().code(13);
+    towboat(&moved, &["doctor"])
+        .assert()
+        .stdout(predicates::str::contains("moved-state-dir"));
+
+    towboat(&moved, &["repair", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Would re-point 4 link(s)"));
+    assert!(!bashrc.exists());
+    towboat(&moved, &["relocate"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Re-pointed 4 link(s)"))
+        .stdout(predicates::str::contains(
+            stow.path().join("state").to_str().unwrap(),
+        ));
+    assert_eq!(fs::read_to_string(&bashrc).unwrap(), content);
+    towboat(&moved, &["status", "--check"]).assert().success();
+    towboat(&moved, &["log"])
+        .assert()
+        .stdout(predicates::str::contains("relocate"));
+
+    // Relative links survive moving the state and target dirs together
+    let manifest = fs::read_to_string(moved.join("towboat.toml")).unwrap();
+    fs::write(
+        moved.join("towboat.toml"),
+        manifest.replace("[system]\n", "[system]\nrelative_links = true\n"),
+    )
+    .unwrap();
+    towboat(&moved, &["sync"]).assert().success();
+    assert_eq!(
+        fs::read_link(&bashrc).unwrap(),
+        std::path::Path::new("../dotfiles/state/resolved/bash/.bashrc")
+    );
+    towboat(&moved, &["status", "--check"]).assert().success();
+
+    // A dangling link that leads somewhere else is the user's, not a moved one
+    let profile = target.join(".profile");
+    fs::remove_file(&profile).unwrap();
+    std::os::unix::fs::symlink("/nonexistent/mnt/profile", &profile).unwrap();
+    towboat(&moved, &["relocate"])
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("Skipped .profile"));
+    assert_eq!(
+        fs::read_link(&profile).unwrap(),
+        std::path::Path::new("/nonexistent/mnt/profile")
+    );
+}
+
+#[test]
+fn relocate_from_takes_over_the_state_of_a_moved_stow_dir() {
+    use assert_cmd::Command;
+
+    let stow = setup_stow_dir();
+    use_default_state_dir(stow.path());
+    let home = TempDir::new().unwrap();
+    let state_home = home.path().join("state");
+    let target = home.path().join("target");
+    let towboat = |dir: &std::path::Path, args: &[&str]| {
+        let mut cmd = Command::cargo_bin("towboat").unwrap();
+        cmd.env("XDG_STATE_HOME", &state_home)
+            .args([
+                "--dir",
+                dir.to_str().unwrap(),
+                "--target",
+                target.to_str().unwrap(),
+            ])
+            .args(args);
+        cmd
+    };
+
+    towboat(stow.path(), &["sync"]).assert().success();
+    let old_state = default_state_dir(&state_home, stow.path());
+    assert!(old_state.join("towboat.lock").is_file());
+    assert!(!stow.path().join(".towboat").exists());
+
+    let moved = home.path().join("dotfiles");
+    fs::rename(stow.path(), &moved).unwrap();
+    let new_state = default_state_dir(&state_home, &moved);
+    assert_ne!(new_state, old_state);
+
+    // Without --from, status and sync spot the old state and name its stow dir
+    let hint = format!("towboat relocate --from {}", stow.path().display());
+    towboat(&moved, &["status"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(".bashrc: moved"))
+        .stdout(predicates::str::contains(hint.as_str()));
+    towboat(&moved, &["status", "--check", "--quiet"])
+        .assert()
+        .code(13);
+    towboat(&moved, &["sync"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(hint.as_str()));
+    assert!(!new_state.exists());
+
+    towboat(
+        &moved,
+        &["relocate", "--from", stow.path().to_str().unwrap()],
+    )
+    .assert()
+    .success()
+    .stdout(predicates::str::contains(format!(
+        "Moved {} to {}",
+        old_state.display(),
+        new_state.display()
+    )))
+    .stdout(predicates::str::contains("Re-pointed 4 link(s)"));
+    assert!(!old_state.exists());
+    assert!(
+        fs::read_link(target.join(".bashrc"))
+            .unwrap()
+            .starts_with(&new_state)
+    );
+    towboat(&moved, &["status", "--check"]).assert().success();
+
+    // A relative --from names the same old dir as its absolute path
+    let again = home.path().join("again");
+    fs::create_dir(&again).unwrap();
+    let moved_again = again.join("dotfiles");
+    fs::rename(&moved, &moved_again).unwrap();
+    towboat(&moved_again, &["relocate", "--from", "../dotfiles"])
+        .current_dir(&again)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Re-pointed 4 link(s)"));
+    assert!(!new_state.exists());
+    towboat(&moved_again, &["status", "--check"])
+        .assert()
+        .success();
+    towboat(&moved_again, &["relocate", "--from", "/nowhere/dotfiles"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("No state recorded for"));
+}
+
+#[test]
+fn sync_moves_legacy_towboat_dir_into_the_state_dir() {
+    use assert_cmd::Command;
+
+    let stow = setup_stow_dir();
+    use_default_state_dir(stow.path());
+    let target = TempDir::new().unwrap();
+    let state_home = TempDir::new().unwrap();
+    let towboat = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("towboat").unwrap();
+        cmd.env("XDG_STATE_HOME", state_home.path())
+            .args([
+                "--dir",
+                stow.path().to_str().unwrap(),
+                "--target",
+                target.path().to_str().unwrap(),
+            ])
+            .args(args);
+        cmd
+    };
+    let manifest_path = stow.path().join("towboat.toml");
+    let manifest = fs::read_to_string(&manifest_path).unwrap();
+    let legacy = stow.path().join(".towboat");
+
+    // Deploy with the old layout, then drop the setting
+    fs::write(
+        &manifest_path,
+        manifest.replace("[system]\n", "[system]\nstate_dir = \".towboat\"\n"),
+    )
+    .unwrap();
+    towboat(&["sync"]).assert().success();
+    assert!(legacy.join("towboat.lock").is_file());
+    fs::write(&manifest_path, &manifest).unwrap();
+
+    // Read-only commands keep using it until a sync moves it
+    towboat(&["status", "--check"]).assert().success();
+    towboat(&["sync", "--dry-run"]).assert().success();
+    assert!(legacy.is_dir());
+    assert!(!default_state_dir(state_home.path(), stow.path()).exists());
+
+    towboat(&["sync"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(format!(
+            "Moved {}",
+            legacy.display()
+        )));
+    assert!(!legacy.exists());
+    let state = default_state_dir(state_home.path(), stow.path());
+    assert!(state.join("towboat.lock").is_file());
+    assert!(state.join("generations").is_dir());
+    assert!(
+        fs::read_link(target.path().join(".bashrc"))
+            .unwrap()
+            .starts_with(&state)
+    );
+    towboat(&["status", "--check"]).assert().success();
+    towboat(&["log"])
+        .assert()
+        .stdout(predicates::str::contains("generation 1"));
+}
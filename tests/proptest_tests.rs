@@ -4,8 +4,8 @@ use std::collections::{HashMap, HashSet};
 
 use proptest::prelude::*;
 
-use towboat::tags::matcher;
 use towboat::tags::parser;
+use towboat::tags::{TagExpr, matcher};
 use towboat::template::engine;
 
 // --- Tag expression strategies ---
@@ -92,6 +92,66 @@ proptest! {
     }
 }
 
+// --- Tag expression API properties ---
+
+/// Generate an arbitrary expression over a small alphabet, so tags repeat.
+fn tag_expr_strategy() -> impl Strategy<Value = TagExpr> {
+    let leaf = prop::sample::select(vec!["a", "b", "c", "d"]).prop_map(|t| TagExpr::Tag(t.into()));
+    leaf.prop_recursive(4, 24, 2, |inner| {
+        prop_oneof![
+            inner.clone().prop_map(|e| TagExpr::Not(Box::new(e))),
+            (inner.clone(), inner.clone())
+                .prop_map(|(l, r)| TagExpr::And(Box::new(l), Box::new(r))),
+            (inner.clone(), inner).prop_map(|(l, r)| TagExpr::Or(Box::new(l), Box::new(r))),
+        ]
+    })
+}
+
+/// Every subset of the alphabet.
+fn all_assignments() -> Vec<HashSet<String>> {
+    (0..16u8)
+        .map(|mask| {
+            ["a", "b", "c", "d"]
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| mask & (1 << i) != 0)
+                .map(|(_, t)| t.to_string())
+                .collect()
+        })
+        .collect()
+}
+
+proptest! {
+    #[test]
+    fn display_round_trips_to_an_equivalent_expression(expr in tag_expr_strategy()) {
+        let reparsed = matcher::parse(&expr.to_string()).unwrap();
+        prop_assert_eq!(reparsed.to_string(), expr.to_string());
+        for tags in all_assignments() {
+            prop_assert_eq!(reparsed.evaluate(&tags), expr.evaluate(&tags));
+        }
+    }
+
+    #[test]
+    fn normal_forms_are_equivalent(expr in tag_expr_strategy()) {
+        let dnf = expr.to_dnf();
+        let cnf = expr.to_cnf();
+        let holds = |l: &towboat::tags::Literal, tags: &HashSet<String>| tags.contains(&l.tag) != l.negated;
+        for tags in all_assignments() {
+            let expected = expr.evaluate(&tags);
+            prop_assert_eq!(dnf.terms.iter().any(|t| t.iter().all(|l| holds(l, &tags))), expected);
+            prop_assert_eq!(cnf.clauses.iter().all(|c| c.iter().any(|l| holds(l, &tags))), expected);
+            prop_assert_eq!(expr.trace(&tags).value, expected);
+        }
+    }
+
+    #[test]
+    fn constant_detection_matches_truth_table(expr in tag_expr_strategy()) {
+        let values: Vec<bool> = all_assignments().iter().map(|t| expr.evaluate(t)).collect();
+        prop_assert_eq!(expr.is_always_true(), values.iter().all(|v| *v));
+        prop_assert_eq!(expr.is_always_false(), values.iter().all(|v| !*v));
+    }
+}
+
 // --- Tag parser properties ---
 
 proptest! {
@@ -102,6 +162,7 @@ proptest! {
         let result = parser::process_tags(&content, &tags).unwrap();
         prop_assert!(!result.had_tags);
         // Account for trailing newline normalization
+        #[allow(clippy::if_same_then_else)]
         let expected = if content.ends_with('\n') {
             content.clone()
         } else {